@@ -36,6 +36,9 @@ fn test_rs_nx_gov_main_src_test_rs() {
     };
     let payload = ProposalPayload {
         depends_on: vec![],
+        depends_on_any: None,
+        conflicts_with: None,
+        batch_depends_on: None,
         messages: vec![],
     };
     let activates = Schedule::At(0);
@@ -127,6 +130,526 @@ fn test_rs_nx_gov_main_src_test_rs() {
     );
 }
 
+// ICRC-1 ledger init types, as expected by the ic-icrc1-ledger wasm.
+#[derive(CandidType, Deserialize)]
+enum LedgerArg {
+    Init(LedgerInitArgs),
+}
+
+#[derive(CandidType, Deserialize)]
+struct LedgerInitArgs {
+    minting_account: Account,
+    fee_collector_account: Option<Account>,
+    transfer_fee: candid::Nat,
+    decimals: Option<u8>,
+    max_memo_length: Option<u16>,
+    token_symbol: String,
+    token_name: String,
+    metadata: Vec<(String, candid::types::reference::Func)>,
+    initial_balances: Vec<(Account, candid::Nat)>,
+    feature_flags: Option<LedgerFeatureFlags>,
+    maximum_number_of_accounts: Option<u64>,
+    accounts_overflow_trim_quantity: Option<u64>,
+    archive_options: LedgerArchiveOptions,
+}
+
+#[derive(CandidType, Deserialize)]
+struct LedgerFeatureFlags {
+    icrc2: bool,
+}
+
+#[derive(CandidType, Deserialize)]
+struct LedgerArchiveOptions {
+    num_blocks_to_archive: u64,
+    trigger_threshold: u64,
+    controller_id: Principal,
+    max_transactions_per_response: Option<u64>,
+    max_message_size_bytes: Option<u64>,
+    cycles_for_archive_creation: Option<u64>,
+    node_max_memory_size_bytes: Option<u64>,
+}
+
+// Subset of the governance user roles; access control is not exported by the nx-gov-main lib.
+#[derive(CandidType, Deserialize)]
+enum UserRole {
+    Proposer,
+}
+
+#[derive(CandidType, Deserialize)]
+struct ApproveArgs {
+    from_subaccount: Option<Subaccount>,
+    spender: Account,
+    amount: candid::Nat,
+    expected_allowance: Option<candid::Nat>,
+    expires_at: Option<u64>,
+    fee: Option<candid::Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+fn reply(res: Result<WasmResult, pocket_ic::UserError>) -> Vec<u8> {
+    match res.expect("Failed to call canister") {
+        WasmResult::Reply(bytes) => bytes,
+        WasmResult::Reject(msg) => panic!("Reject: {}", msg),
+    }
+}
+
+fn balance_of(pic: &PocketIc, ledger: Principal, account: Account) -> u128 {
+    let bytes = reply(pic.query_call(
+        ledger,
+        Principal::anonymous(),
+        "icrc1_balance_of",
+        encode_one(account).unwrap(),
+    ));
+    let balance = Decode!(&bytes, candid::Nat).unwrap();
+    balance.0.try_into().unwrap()
+}
+
+fn proposal_deposit(pic: &PocketIc, gov: Principal, proposal_id: Index) -> ProposalDeposit {
+    let bytes = reply(pic.query_call(
+        gov,
+        Principal::anonymous(),
+        "get_proposal_deposit",
+        encode_one(proposal_id).unwrap(),
+    ));
+    Decode!(&bytes, Option<ProposalDeposit>).unwrap().unwrap()
+}
+
+fn submit_empty_proposal(pic: &PocketIc, gov: Principal, proposer: Principal) -> Index {
+    submit_proposal(pic, gov, proposer, vec![])
+}
+
+fn submit_proposal(
+    pic: &PocketIc,
+    gov: Principal,
+    proposer: Principal,
+    messages: Vec<CanisterMessage>,
+) -> Index {
+    let metadata = ProposalMetadata {
+        name: String::from("Test proposal"),
+        description: String::from("AAA"),
+        memo: vec![],
+    };
+    let payload = ProposalPayload {
+        depends_on: vec![],
+        depends_on_any: None,
+        conflicts_with: None,
+        batch_depends_on: None,
+        messages,
+    };
+    let activates = Schedule::At(0);
+    let expires = Schedule::In(100000000000000000);
+    let auto_execute: bool = false;
+    let encoded_arg = Encode!(&metadata, &payload, &activates, &expires, &auto_execute).unwrap();
+    let bytes = reply(pic.update_call(gov, proposer, "submit", encoded_arg));
+    Decode!(&bytes, Result<Index, ReturnError>)
+        .unwrap()
+        .unwrap()
+}
+
+/// Validates, accepts and executes a proposal. `operator` holds the validator, vote manager and executor roles.
+fn pass_proposal(pic: &PocketIc, gov: Principal, operator: Principal, proposal_id: Index) {
+    let now = pic
+        .get_time()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64;
+    let voting_end_time = Some(now + 86400 * 1_000_000_000 * 4);
+    let passing_threshold = Some(ProposalPassingThreshold::default());
+    let encoded_arg = Encode!(&proposal_id, &voting_end_time, &passing_threshold, &true).unwrap();
+    let bytes = reply(pic.update_call(gov, operator, "validate", encoded_arg));
+    Decode!(&bytes, Result<(), ReturnError>).unwrap().unwrap();
+
+    let power: VotingPower = 1;
+    let zero: VotingPower = 0;
+    let encoded_arg = Encode!(&proposal_id, &power, &zero, &zero, &power).unwrap();
+    let bytes = reply(pic.update_call(
+        gov,
+        operator,
+        "update_vote_result_and_total_voting_power",
+        encoded_arg,
+    ));
+    Decode!(&bytes, Result<(), ReturnError>).unwrap().unwrap();
+
+    let bytes = reply(pic.update_call(gov, operator, "execute", encode_one(proposal_id).unwrap()));
+    Decode!(&bytes, Result<(), ReturnError>).unwrap().unwrap();
+}
+
+/// Validates a proposal that needs 90% "yes" votes, and rejects it with the given votes.
+/// `operator` holds the validator and vote manager roles.
+fn reject_proposal(
+    pic: &PocketIc,
+    gov: Principal,
+    operator: Principal,
+    proposal_id: Index,
+    yes: VotingPower,
+    no: VotingPower,
+) {
+    let now = pic
+        .get_time()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64;
+    let voting_end_time = Some(now + 86400 * 1_000_000_000 * 4);
+    // A passing mode lets the vote end early once all voting power is cast.
+    let passing_threshold = Some(ProposalPassingThreshold {
+        passing_threshold: Percentage::<PercentagePrecision>::from_percent(90),
+        mode: Some(PassingMode::SimpleMajority),
+        ..Default::default()
+    });
+    let encoded_arg = Encode!(&proposal_id, &voting_end_time, &passing_threshold, &true).unwrap();
+    let bytes = reply(pic.update_call(gov, operator, "validate", encoded_arg));
+    Decode!(&bytes, Result<(), ReturnError>).unwrap().unwrap();
+
+    let abstain: VotingPower = 0;
+    let total = yes + no;
+    let encoded_arg = Encode!(&proposal_id, &yes, &no, &abstain, &total).unwrap();
+    let bytes = reply(pic.update_call(
+        gov,
+        operator,
+        "update_vote_result_and_total_voting_power",
+        encoded_arg,
+    ));
+    Decode!(&bytes, Result<(), ReturnError>).unwrap().unwrap();
+
+    let bytes = reply(pic.update_call(
+        gov,
+        operator,
+        "finalize_vote_result",
+        encode_one(proposal_id).unwrap(),
+    ));
+    Decode!(&bytes, Result<(), ReturnError>).unwrap().unwrap();
+}
+
+#[test]
+fn test_proposal_deposit_refund_and_slash() {
+    const FEE: u128 = 10_000;
+    const DEPOSIT: u128 = 1_000_000;
+    const INITIAL_BALANCE: u128 = 100_000_000;
+
+    let pic = PocketIc::new();
+    let operator = generate_random_principal();
+    let user = generate_random_principal();
+    let treasury = generate_random_principal();
+
+    let ledger_can_id = pic.create_canister();
+    pic.add_cycles(ledger_can_id, INIT_CYCLES);
+    let ledger_init = LedgerArg::Init(LedgerInitArgs {
+        minting_account: Account::from(generate_random_principal()),
+        fee_collector_account: None,
+        transfer_fee: candid::Nat::from(FEE),
+        decimals: None,
+        max_memo_length: None,
+        token_symbol: String::from("TST"),
+        token_name: String::from("Test token"),
+        metadata: vec![],
+        initial_balances: vec![(Account::from(user), candid::Nat::from(INITIAL_BALANCE))],
+        feature_flags: Some(LedgerFeatureFlags { icrc2: true }),
+        maximum_number_of_accounts: None,
+        accounts_overflow_trim_quantity: None,
+        archive_options: LedgerArchiveOptions {
+            num_blocks_to_archive: 1000,
+            trigger_threshold: 2000,
+            controller_id: Principal::anonymous(),
+            max_transactions_per_response: None,
+            max_message_size_bytes: None,
+            cycles_for_archive_creation: None,
+            node_max_memory_size_bytes: None,
+        },
+    });
+    pic.install_canister(
+        ledger_can_id,
+        icrc1_ledger_wasm(),
+        encode_one(ledger_init).unwrap(),
+        None,
+    );
+
+    let gov = pic.create_canister();
+    pic.add_cycles(gov, INIT_CYCLES);
+    pic.install_canister(gov, nx_gov_main_wasm(), vec![], None);
+    let no_wl: Vec<Principal> = vec![];
+    let encoded_arg = Encode!(&operator, &operator, &operator, &no_wl).unwrap();
+    reply(pic.update_call(gov, Principal::anonymous(), "initialize", encoded_arg));
+
+    // Governance enables deposits and makes `user` a proposer.
+    let bytes = reply(pic.query_call(
+        gov,
+        Principal::anonymous(),
+        "get_config",
+        encode_one(()).unwrap(),
+    ));
+    let mut config = Decode!(&bytes, Result<Config, ReturnError>)
+        .unwrap()
+        .unwrap();
+    config.deposit = Some(DepositConfig {
+        ledger: ledger_can_id,
+        amount: DEPOSIT,
+        reject_slash_threshold: Percentage::<PercentagePrecision>::from_percent(67),
+        slash_destination: Some(Account::from(treasury)),
+    });
+    let self_call = |method: &str, message: Vec<u8>| CanisterMessage {
+        canister_id: gov,
+        method: String::from(method),
+        message,
+        payment: 0,
+        pre_validate: None,
+        post_validate: None,
+    };
+    let messages = vec![
+        self_call("update_config", encode_one(config).unwrap()),
+        self_call("add_role", Encode!(&UserRole::Proposer, &user).unwrap()),
+    ];
+    let config_proposal = submit_proposal(&pic, gov, Principal::anonymous(), messages);
+    pass_proposal(&pic, gov, operator, config_proposal);
+
+    let approve = ApproveArgs {
+        from_subaccount: None,
+        spender: Account::from(gov),
+        amount: candid::Nat::from(10 * (DEPOSIT + FEE)),
+        expected_allowance: None,
+        expires_at: None,
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+    let bytes = reply(pic.update_call(
+        ledger_can_id,
+        user,
+        "icrc2_approve",
+        encode_one(approve).unwrap(),
+    ));
+    assert!(Decode!(&bytes, Result<candid::Nat, candid::Reserved>)
+        .unwrap()
+        .is_ok());
+    let escrow = Account {
+        owner: gov,
+        subaccount: Some(DEPOSIT_SUBACCOUNT),
+    };
+
+    // A proposal that fails validation is slashed.
+    let slashed = submit_empty_proposal(&pic, gov, user);
+    assert_eq!(balance_of(&pic, ledger_can_id, escrow.clone()), DEPOSIT);
+    let no_end_time: Option<TimeNs> = None;
+    let no_threshold: Option<ProposalPassingThreshold> = None;
+    let encoded_arg = Encode!(&slashed, &no_end_time, &no_threshold, &false).unwrap();
+    reply(pic.update_call(gov, operator, "validate", encoded_arg));
+    for _ in 0..5 {
+        pic.tick();
+    }
+    assert!(matches!(
+        proposal_deposit(&pic, gov, slashed).status,
+        DepositStatus::Slashed {
+            block_index: Some(_)
+        }
+    ));
+    assert_eq!(
+        balance_of(&pic, ledger_can_id, Account::from(treasury)),
+        DEPOSIT - FEE
+    );
+    assert_eq!(balance_of(&pic, ledger_can_id, escrow.clone()), 0);
+
+    // A proposal that is executed successfully is refunded.
+    let refunded = submit_empty_proposal(&pic, gov, user);
+    assert_eq!(balance_of(&pic, ledger_can_id, escrow.clone()), DEPOSIT);
+    pass_proposal(&pic, gov, operator, refunded);
+    for _ in 0..5 {
+        pic.tick();
+    }
+    assert!(matches!(
+        proposal_deposit(&pic, gov, refunded).status,
+        DepositStatus::Refunded {
+            block_index: Some(_)
+        }
+    ));
+    assert_eq!(balance_of(&pic, ledger_can_id, escrow.clone()), 0);
+
+    // A proposal rejected with at least 67% "no" votes is slashed.
+    let rejected_widely = submit_empty_proposal(&pic, gov, user);
+    reject_proposal(&pic, gov, operator, rejected_widely, 1, 3);
+    for _ in 0..5 {
+        pic.tick();
+    }
+    assert!(matches!(
+        proposal_deposit(&pic, gov, rejected_widely).status,
+        DepositStatus::Slashed {
+            block_index: Some(_)
+        }
+    ));
+    assert_eq!(
+        balance_of(&pic, ledger_can_id, Account::from(treasury)),
+        2 * (DEPOSIT - FEE)
+    );
+
+    // A proposal rejected with fewer "no" votes is refunded.
+    let rejected_narrowly = submit_empty_proposal(&pic, gov, user);
+    reject_proposal(&pic, gov, operator, rejected_narrowly, 1, 1);
+    for _ in 0..5 {
+        pic.tick();
+    }
+    assert!(matches!(
+        proposal_deposit(&pic, gov, rejected_narrowly).status,
+        DepositStatus::Refunded {
+            block_index: Some(_)
+        }
+    ));
+    assert_eq!(balance_of(&pic, ledger_can_id, escrow), 0);
+    // approve fee, four deposits with their transfer fees, two refunds minus their fees
+    assert_eq!(
+        balance_of(&pic, ledger_can_id, Account::from(user)),
+        INITIAL_BALANCE - FEE - 4 * (DEPOSIT + FEE) + 2 * (DEPOSIT - FEE)
+    );
+}
+
+/// Proposals and execution results written by the baseline release are readable after an upgrade.
+#[test]
+fn test_upgrade_from_baseline_storage_layout() {
+    let pic = PocketIc::new();
+    let operator = generate_random_principal();
+
+    let gov = pic.create_canister();
+    pic.add_cycles(gov, INIT_CYCLES);
+    pic.install_canister(gov, nx_gov_main_baseline_wasm(), vec![], None);
+    let no_wl: Vec<Principal> = vec![];
+    let encoded_arg = Encode!(&operator, &operator, &operator, &no_wl).unwrap();
+    reply(pic.update_call(gov, Principal::anonymous(), "initialize", encoded_arg));
+
+    let message = CanisterMessage {
+        canister_id: gov,
+        method: String::from("get_next_proposal_id"),
+        message: encode_one(()).unwrap(),
+        payment: 0,
+        pre_validate: None,
+        post_validate: None,
+    };
+    let executed = submit_proposal(&pic, gov, Principal::anonymous(), vec![message]);
+    pass_proposal(&pic, gov, operator, executed);
+    let pending = submit_empty_proposal(&pic, gov, Principal::anonymous());
+
+    pic.upgrade_canister(gov, nx_gov_main_wasm(), vec![], None)
+        .expect("Failed to upgrade canister");
+
+    let get_proposal = |proposal_id: Index| {
+        let bytes = reply(pic.query_call(
+            gov,
+            Principal::anonymous(),
+            "get_proposal",
+            encode_one(proposal_id).unwrap(),
+        ));
+        Decode!(&bytes, Option<Proposal>).unwrap().unwrap()
+    };
+    let proposal = get_proposal(executed);
+    assert_eq!(proposal.state, ProposalState::Succeeded);
+    assert_eq!(proposal.runs(), 0);
+    assert_eq!(get_proposal(pending).state, ProposalState::Submitted);
+
+    // The execution result of the baseline becomes the first run.
+    let bytes = reply(pic.query_call(
+        gov,
+        Principal::anonymous(),
+        "get_proposal_execution_results",
+        encode_one(executed).unwrap(),
+    ));
+    let results = Decode!(&bytes, Vec<ProposalExec>).unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].execution_result[0].0.is_ok());
+
+    let bytes = reply(pic.query_call(
+        gov,
+        Principal::anonymous(),
+        "get_pending_proposal_count",
+        encode_one(()).unwrap(),
+    ));
+    assert_eq!(Decode!(&bytes, u64).unwrap(), 1);
+    assert_eq!(
+        submit_empty_proposal(&pic, gov, Principal::anonymous()),
+        pending + 1
+    );
+}
+
+// Multisig vote, as expected by the multisig-voting wasm.
+#[derive(CandidType, Deserialize)]
+enum Vote {
+    Yes,
+    No,
+    Abstain,
+}
+
+/// Governance with simple-validator and a 2-of-3 multisig as vote manager.
+/// Returns the governance, validator and multisig canisters, and the signers.
+fn deploy_multisig_governance(pic: &PocketIc) -> (Principal, Principal, Principal, Vec<Principal>) {
+    let gov = pic.create_canister();
+    let validator = pic.create_canister();
+    let multisig = pic.create_canister();
+    for canister_id in [gov, validator, multisig] {
+        pic.add_cycles(canister_id, INIT_CYCLES);
+    }
+    pic.install_canister(gov, nx_gov_main_wasm(), vec![], None);
+    pic.install_canister(
+        validator,
+        simple_validator_wasm(),
+        encode_one(gov).unwrap(),
+        None,
+    );
+    let signers: Vec<Principal> = (0..3).map(|_| generate_random_principal()).collect();
+    let encoded_arg = Encode!(&gov, &2u64, &3u64, &signers).unwrap();
+    pic.install_canister(multisig, multisig_voting_wasm(), encoded_arg, None);
+
+    let no_wl: Vec<Principal> = vec![];
+    let encoded_arg = Encode!(&validator, &multisig, &generate_random_principal(), &no_wl).unwrap();
+    reply(pic.update_call(gov, Principal::anonymous(), "initialize", encoded_arg));
+    (gov, validator, multisig, signers)
+}
+
+/// A proposal rejected by the multisig is rejected in governance, even when its counts pass the threshold that
+/// simple-validator sets for standard proposals.
+#[test]
+fn test_multisig_rejection_is_binding() {
+    let pic = PocketIc::new();
+    let (gov, validator, multisig, signers) = deploy_multisig_governance(&pic);
+    let proposal_id = submit_empty_proposal(&pic, gov, Principal::anonymous());
+
+    // Both canisters pick up the proposal by syncing, in the order governance would notify them.
+    for canister_id in [validator, multisig] {
+        let bytes = reply(pic.update_call(
+            canister_id,
+            Principal::anonymous(),
+            "sync_with_governance",
+            encode_one(()).unwrap(),
+        ));
+        Decode!(&bytes, Result<(), ReturnError>).unwrap().unwrap();
+    }
+
+    // 1 yes, 1 no and 1 abstain: the multisig cannot reach 2 yes, but yes / (yes + no) passes 20%.
+    for (signer, vote) in signers.iter().zip([Vote::Yes, Vote::No, Vote::Abstain]) {
+        let encoded_arg = Encode!(&proposal_id, &vote).unwrap();
+        let bytes = reply(pic.update_call(multisig, *signer, "vote_proposal", encoded_arg));
+        Decode!(&bytes, Result<(), ReturnError>).unwrap().unwrap();
+    }
+    // Runs the push timer and its call to governance.
+    for _ in 0..5 {
+        pic.tick();
+    }
+
+    let bytes = reply(pic.query_call(
+        gov,
+        Principal::anonymous(),
+        "get_proposal",
+        encode_one(proposal_id).unwrap(),
+    ));
+    let proposal = Decode!(&bytes, Option<Proposal>).unwrap().unwrap();
+    assert_eq!(proposal.passing_threshold.unwrap().mode, None);
+    assert_eq!(proposal.state, ProposalState::Rejected);
+    assert_eq!(
+        (
+            proposal.votes_yes,
+            proposal.votes_no,
+            proposal.votes_abstain,
+            proposal.total_voting_power
+        ),
+        (1, 1, 1, 3)
+    );
+}
+
 fn nx_gov_main_wasm() -> Vec<u8> {
     let wasm_path = std::env::var_os("NX_GOV_MAIN_WASM").expect("Missing nx-gov-main wasm file");
     if let Ok(regular_string) = wasm_path.clone().into_string() {
@@ -137,6 +660,18 @@ fn nx_gov_main_wasm() -> Vec<u8> {
     std::fs::read(wasm_path).unwrap()
 }
 
+// Load nx-gov-main canister as built from the baseline release, to test upgrades
+fn nx_gov_main_baseline_wasm() -> Vec<u8> {
+    let wasm_path = std::env::var_os("NX_GOV_MAIN_BASELINE_WASM")
+        .expect("Missing baseline nx-gov-main wasm file");
+    if let Ok(regular_string) = wasm_path.clone().into_string() {
+        println!("{regular_string}");
+    } else {
+        println!("Not a string");
+    };
+    std::fs::read(wasm_path).unwrap()
+}
+
 fn simple_validator_wasm() -> Vec<u8> {
     let wasm_path =
         std::env::var_os("SIMPLE_VALIDATOR_WASM").expect("Missing simple-validator wasm file");
@@ -159,3 +694,14 @@ fn multisig_voting_wasm() -> Vec<u8> {
     };
     std::fs::read(wasm_path).unwrap()
 }
+
+// Load ICRC-1 ledger canister (ic-icrc1-ledger, built with ICRC-2 support)
+fn icrc1_ledger_wasm() -> Vec<u8> {
+    let wasm_path = std::env::var_os("ICRC1_LEDGER_WASM").expect("Missing ICRC-1 ledger wasm file");
+    if let Ok(regular_string) = wasm_path.clone().into_string() {
+        println!("{regular_string}");
+    } else {
+        println!("Not a string");
+    };
+    std::fs::read(wasm_path).unwrap()
+}
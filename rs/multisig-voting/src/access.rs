@@ -71,7 +71,7 @@ pub fn clear_users_of_role(role: UserRole) {
     require_caller_has_role(UserRole::Admin);
     _clear_users_of_role(role);
 }
-fn _clear_users_of_role(role: UserRole) {
+pub(crate) fn _clear_users_of_role(role: UserRole) {
     let op = |roles: &RefCell<StableVec<StablePrincipal, VM>>| {
         let r = roles.borrow_mut();
         for _ in 0..r.len() {
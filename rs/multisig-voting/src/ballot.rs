@@ -0,0 +1,202 @@
+//! Ballots signed offline by key holders, which any relayer can submit with `vote_proposal_signed`.
+//!
+//! A key holder takes part as the self-authenticating principal of its public key (see `SignerKey::principal`),
+//! so it is added to the signer set, weighted and snapshotted like any other signer.
+//! Each public key is a signer of its own: an Ed25519 key and a secp256k1 key of the same person are two signers,
+//! and each must be added to the signer set.
+
+use crate::types::{Index, ReturnError, Vote};
+
+use candid::{CandidType, Principal};
+use ed25519_dalek::Verifier;
+use serde::Deserialize;
+use serde_bytes::ByteBuf;
+
+/// Domain separator of ballots, so that a ballot signature is never valid for another kind of message.
+pub const BALLOT_DOMAIN: &[u8] = b"nx-governance/multisig-voting/ballot/v1";
+
+const ED25519_DER_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+const SECP256K1_DER_PREFIX: [u8; 23] = [
+    0x30, 0x56, 0x30, 0x10, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x05, 0x2b,
+    0x81, 0x04, 0x00, 0x0a, 0x03, 0x42, 0x00,
+];
+
+/// A vote signed by a key holder.
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq, Eq)]
+pub struct Ballot {
+    /// The governance canister of the proposal.
+    pub governance: Principal,
+    pub proposal: Index,
+    pub vote: Vote,
+    /// Must be greater than the nonce of every previous ballot of the key.
+    pub nonce: u64,
+}
+
+impl Ballot {
+    /// The canonical encoding that is signed:
+    /// `domain || multisig || governance || proposal || vote || nonce`.
+    /// The domain and principals are prefixed with their length in one byte, integers are big-endian u64,
+    /// and the vote is one byte: 0 for yes, 1 for no, 2 for abstain.
+    /// The id of the multisig canister keeps a ballot from being replayed on another multisig.
+    pub fn signing_bytes(&self, multisig: &Principal) -> Vec<u8> {
+        let mut bytes = vec![];
+        for field in [
+            BALLOT_DOMAIN,
+            multisig.as_slice(),
+            self.governance.as_slice(),
+        ] {
+            bytes.push(field.len() as u8);
+            bytes.extend_from_slice(field);
+        }
+        bytes.extend_from_slice(&self.proposal.to_be_bytes());
+        bytes.push(self.vote.as_byte());
+        bytes.extend_from_slice(&self.nonce.to_be_bytes());
+        bytes
+    }
+}
+
+/// Public key of a key holder. Every key is a different signer, whatever its curve.
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq, Eq)]
+pub enum SignerKey {
+    /// 32-byte Ed25519 public key.
+    Ed25519(ByteBuf),
+    /// SEC1-encoded secp256k1 public key, compressed or not. Signatures are ECDSA over SHA-256.
+    Secp256k1(ByteBuf),
+}
+
+impl SignerKey {
+    /// DER encoding of the key as a SubjectPublicKeyInfo.
+    pub fn der(&self) -> Result<Vec<u8>, ReturnError> {
+        match self {
+            SignerKey::Ed25519(key) => {
+                ed25519_verifying_key(key)?;
+                Ok([&ED25519_DER_PREFIX[..], key].concat())
+            }
+            SignerKey::Secp256k1(key) => {
+                let point = secp256k1_verifying_key(key)?.to_encoded_point(false);
+                Ok([&SECP256K1_DER_PREFIX[..], point.as_bytes()].concat())
+            }
+        }
+    }
+
+    /// The self-authenticating principal of the key, which is the key holder in the signer set.
+    pub fn principal(&self) -> Result<Principal, ReturnError> {
+        Ok(Principal::self_authenticating(self.der()?))
+    }
+
+    /// Verifies a 64-byte signature of `message`. High-S secp256k1 signatures are rejected.
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), ReturnError> {
+        match self {
+            SignerKey::Ed25519(key) => {
+                let signature = ed25519_dalek::Signature::from_slice(signature)
+                    .map_err(|_| ReturnError::InvalidSignature)?;
+                ed25519_verifying_key(key)?
+                    .verify_strict(message, &signature)
+                    .map_err(|_| ReturnError::InvalidSignature)
+            }
+            SignerKey::Secp256k1(key) => {
+                let signature = k256::ecdsa::Signature::from_slice(signature)
+                    .map_err(|_| ReturnError::InvalidSignature)?;
+                if signature.normalize_s().is_some() {
+                    return Err(ReturnError::InvalidSignature);
+                }
+                secp256k1_verifying_key(key)?
+                    .verify(message, &signature)
+                    .map_err(|_| ReturnError::InvalidSignature)
+            }
+        }
+    }
+}
+
+fn ed25519_verifying_key(key: &[u8]) -> Result<ed25519_dalek::VerifyingKey, ReturnError> {
+    let key: &[u8; 32] = key.try_into().map_err(|_| ReturnError::InputError)?;
+    ed25519_dalek::VerifyingKey::from_bytes(key).map_err(|_| ReturnError::InputError)
+}
+
+fn secp256k1_verifying_key(key: &[u8]) -> Result<k256::ecdsa::VerifyingKey, ReturnError> {
+    k256::ecdsa::VerifyingKey::from_sec1_bytes(key).map_err(|_| ReturnError::InputError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::Signer;
+
+    fn ballot(nonce: u64) -> Ballot {
+        Ballot {
+            governance: Principal::from_slice(&[1; 10]),
+            proposal: 7,
+            vote: Vote::Yes,
+            nonce,
+        }
+    }
+
+    fn multisig() -> Principal {
+        Principal::from_slice(&[2; 10])
+    }
+
+    #[test]
+    fn verifies_ed25519_ballots() {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[3; 32]);
+        let key = SignerKey::Ed25519(ByteBuf::from(
+            signing_key.verifying_key().to_bytes().to_vec(),
+        ));
+        let message = ballot(1).signing_bytes(&multisig());
+        let signature = signing_key.sign(&message).to_bytes();
+        assert_eq!(key.verify(&message, &signature), Ok(()));
+        // Another nonce or another multisig is another message.
+        assert_eq!(
+            key.verify(&ballot(2).signing_bytes(&multisig()), &signature),
+            Err(ReturnError::InvalidSignature)
+        );
+        assert_eq!(
+            key.verify(
+                &ballot(1).signing_bytes(&Principal::anonymous()),
+                &signature
+            ),
+            Err(ReturnError::InvalidSignature)
+        );
+        assert!(key.principal().is_ok());
+    }
+
+    #[test]
+    fn verifies_secp256k1_ballots() {
+        let signing_key = k256::ecdsa::SigningKey::from_slice(&[4; 32]).unwrap();
+        let verifying_key = signing_key.verifying_key();
+        let compressed = SignerKey::Secp256k1(ByteBuf::from(
+            verifying_key.to_encoded_point(true).as_bytes().to_vec(),
+        ));
+        let uncompressed = SignerKey::Secp256k1(ByteBuf::from(
+            verifying_key.to_encoded_point(false).as_bytes().to_vec(),
+        ));
+        let message = ballot(1).signing_bytes(&multisig());
+        let signature: k256::ecdsa::Signature =
+            k256::ecdsa::signature::Signer::sign(&signing_key, &message);
+        assert_eq!(compressed.verify(&message, &signature.to_bytes()), Ok(()));
+        // Both encodings of a key are the same signer.
+        assert_eq!(compressed.principal(), uncompressed.principal());
+        // A key of another curve is another signer.
+        let ed25519 = SignerKey::Ed25519(ByteBuf::from(
+            ed25519_dalek::SigningKey::from_bytes(&[4; 32])
+                .verifying_key()
+                .to_bytes()
+                .to_vec(),
+        ));
+        assert_ne!(compressed.principal(), ed25519.principal());
+        assert_eq!(
+            compressed.verify(&ballot(2).signing_bytes(&multisig()), &signature.to_bytes()),
+            Err(ReturnError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_keys() {
+        let key = SignerKey::Ed25519(ByteBuf::from(vec![0; 31]));
+        assert_eq!(key.principal(), Err(ReturnError::InputError));
+        // secp256k1 has no point with x = 0.
+        let key = SignerKey::Secp256k1(ByteBuf::from([&[2][..], &[0; 32]].concat()));
+        assert_eq!(key.principal(), Err(ReturnError::InputError));
+    }
+}
@@ -1,9 +1,14 @@
 use crate::access::*;
 use crate::memory::*;
+use crate::push::schedule_push;
+use crate::{get_num_proposals, schedule_reveal_close, set_signers};
 
 use candid::Principal;
-use ic_cdk_macros::{init, update};
+use ic_cdk_macros::{init, post_upgrade, update};
+use std::time::Duration;
 
+/// Initializes an m-of-n multisig, where every signer has a weight of 1.
+/// Weights can be changed later with `update_signers`.
 #[init]
 pub fn init(
     gov_main_principal: Principal,
@@ -11,22 +16,45 @@ pub fn init(
     total_votes: u64,
     signers: Vec<Principal>,
 ) {
-    assert!(votes_required <= total_votes);
-    assert_eq!(total_votes, signers.len() as u64); // SAFETY: Uniqueness of signers is checked in add_role_internal.
+    assert_eq!(total_votes, signers.len() as u64);
 
     #[allow(clippy::expect_used)]
     add_role_internal(UserRole::Admin, gov_main_principal).expect("admin init failed");
 
-    config_set_m_of_n(votes_required, total_votes);
-
-    signers
-        .into_iter()
-        .for_each(|p| add_role_internal(UserRole::Signer, p).expect("signer init failed"));
+    set_signers(
+        votes_required,
+        signers.into_iter().map(|p| (p, 1)).collect(),
+    );
     config_set_governance(gov_main_principal);
 
     // Other init code here
 }
 
+/// Builds the proposal indexes if the proposals were stored before the indexes existed.
+/// Timers do not survive upgrades, so scheduled pushes and the closing of reveal phases are scheduled again,
+/// once the indexes are complete.
+#[post_upgrade]
+pub fn post_upgrade() {
+    if is_index_empty() && get_num_proposals() > 0 && !is_indexing() {
+        start_index_rebuild();
+    }
+    continue_index_rebuild();
+}
+
+/// Continues the index rebuild, one batch per message, then schedules the timers that depend on the indexes.
+fn continue_index_rebuild() {
+    if !rebuild_indexes() {
+        ic_cdk_timers::set_timer(Duration::ZERO, continue_index_rebuild);
+        return;
+    }
+    for proposal in get_proposal_ids_with_scheduled_push() {
+        schedule_push(proposal, Duration::ZERO);
+    }
+    for (proposal, reveal_end) in get_proposals_with_open_reveal() {
+        schedule_reveal_close(proposal, reveal_end);
+    }
+}
+
 #[update]
 pub fn initialize() {
     assert!(ic_cdk::api::is_controller(&ic_cdk::api::caller()));
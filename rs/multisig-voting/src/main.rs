@@ -1,35 +1,41 @@
 //! This is a simple multisig voting canister to be used with NX Governance.
-//! In order to use this canister, use a validator (such as simple-validator)
-//! that sets passing threshold to any arbitrary value.
+//! The signer weight required to pass a proposal is the configured weight, raised to the weight derived from the
+//! passing threshold that its validator sets.
+//! The decision of the multisig is binding in governance. A proposal passed here reaches the passing threshold with
+//! the pushed weighted counts, and a proposal rejected here is pushed with `reject_vote_result`, which rejects it in
+//! governance whatever the counts.
 
 mod access;
+mod ballot;
 mod lifecycle;
 mod memory;
+mod push;
+mod secret;
 mod types;
 
 use crate::access::*;
+use crate::ballot::*;
 use crate::memory::*;
+use crate::push::*;
+use crate::secret::*;
 use crate::types::*;
 
 use candid::{decode_one, encode_args, encode_one, Principal};
 use ic_cdk_macros::{query, update};
+use serde_bytes::ByteBuf;
+use std::time::Duration;
 
 /// Notification called by the governance canister to notify the multisig canister that a proposal is ready for voting.
 #[update]
 pub async fn notify_multisig(proposal: Index, voting_end_time: TimeNs) {
     assert_eq!(ic_cdk::api::caller(), get_governance());
 
-    PROPOSAL_VOTES.with(|v| {
-        let mut v = v.borrow_mut();
-        v.insert(
-            proposal,
-            Cbor(ProposalState {
-                expiration: voting_end_time,
-                state: State::Open,
-                vote_record: VoteRecord::default(),
-            }),
-        );
-    });
+    let threshold = fetch_passing_threshold(get_governance(), proposal)
+        .await
+        .ok()
+        .flatten();
+    let signer_set = signer_set_for(proposal, threshold);
+    register_proposal(proposal, ProposalState::new(voting_end_time, signer_set));
 }
 
 #[update]
@@ -47,96 +53,374 @@ pub async fn sync_with_governance() -> Result<(), ReturnError> {
 
     // Set PropsalState for each non-existing proposal
     for (proposal, expiration) in result {
-        add_proposal_state(
-            proposal,
-            ProposalState {
-                expiration,
-                state: State::Open,
-                vote_record: VoteRecord::default(),
-            },
-        );
+        if get_proposal_state(proposal).is_some() {
+            continue;
+        }
+        let threshold = fetch_passing_threshold(config.governance_canister, proposal)
+            .await
+            .ok()
+            .flatten();
+        // The proposal may have been notified during the call.
+        if get_proposal_state(proposal).is_some() {
+            continue;
+        }
+        let signer_set = signer_set_for(proposal, threshold);
+        register_proposal(proposal, ProposalState::new(expiration, signer_set));
     }
 
     Ok(())
 }
 
-/// Each signer calls this function to vote on a proposal.
+/// The signers of a proposal, with the weight required raised by its passing threshold if known.
+/// A proposal registered again, e.g. for another voting round, keeps the signers it was first registered with.
+/// Read after the threshold is fetched, since the signers may change during the call.
+fn signer_set_for(proposal: Index, threshold: Option<ProposalPassingThreshold>) -> SignerSet {
+    let mut signer_set = get_proposal_state(proposal)
+        .and_then(|s| s.signer_set)
+        .unwrap_or_else(current_signer_set);
+    if let Some(threshold) = threshold {
+        signer_set.require_threshold(&threshold);
+    }
+    signer_set
+}
+
+/// Stores the state of a newly registered proposal, with a secret ballot if configured.
+/// The reveal phase ends at `expiration - vote_buffer_time`, and is closed by a timer or by `submit_vote_result`.
+/// A proposal whose voting ends too soon to commit before the reveal window is voted on with public ballots.
+fn register_proposal(proposal: Index, mut proposal_state: ProposalState) {
+    #[allow(clippy::unwrap_used)]
+    let config = get_config().unwrap();
+    let now = ic_cdk::api::time();
+    if let Some(reveal_window) = config.reveal_window {
+        let reveal_end = proposal_state
+            .expiration
+            .saturating_sub(config.vote_buffer_time);
+        proposal_state.secret = SecretBallot::new(reveal_end, reveal_window, now);
+        if proposal_state.secret.is_some() {
+            schedule_reveal_close(proposal, reveal_end);
+        }
+    }
+    set_proposal_state(proposal, proposal_state);
+}
+
+/// Fetches the passing threshold of a governance proposal.
+async fn fetch_passing_threshold(
+    governance: Principal,
+    proposal: Index,
+) -> Result<Option<ProposalPassingThreshold>, ReturnError> {
+    let res = ic_cdk::api::call::call_raw128(
+        governance,
+        "get_proposal",
+        &encode_one(proposal).unwrap(),
+        0,
+    )
+    .await
+    .map_err(|_| ReturnError::InterCanisterCallError)?;
+    let result: Option<GovernanceProposal> =
+        decode_one(&res).map_err(|_| ReturnError::InterCanisterCallError)?;
+    Ok(result.and_then(|p| p.passing_threshold))
+}
+
+/// Each signer of the proposal calls this function to vote on it.
+/// If the vote decides the proposal, the result is pushed to governance automatically.
+/// Proposals with secret ballots are voted on with `commit_vote` and `reveal_vote` instead.
 #[update]
 pub fn vote_proposal(proposal: Index, vote: Vote) -> Result<(), ReturnError> {
-    require_caller_has_role(UserRole::Signer);
+    cast_vote(ic_cdk::api::caller(), proposal, vote)
+}
 
+/// Each signer of a proposal with secret ballots calls this function during the commit phase,
+/// with `sha256(vote || salt)` where the vote is one byte (0 yes, 1 no, 2 abstain) and the salt at least 16 random bytes.
+/// Committing again replaces the commitment.
+#[update]
+pub fn commit_vote(proposal: Index, commitment: ByteBuf) -> Result<(), ReturnError> {
     let caller = ic_cdk::api::caller();
+    let mut proposal_state = get_proposal_state(proposal).ok_or(ReturnError::InputError)?;
+    if signer_set_of(&proposal_state).weight_of(&caller).is_none() {
+        return Err(ReturnError::Unauthorized);
+    }
+    proposal_state
+        .secret
+        .as_mut()
+        .ok_or(ReturnError::InputError)?
+        .commit(caller, commitment, ic_cdk::api::time())?;
+    set_proposal_state(proposal, proposal_state);
+    Ok(())
+}
 
-    PROPOSAL_VOTES.with(|v| {
-        let mut v = v.borrow_mut();
-        let mut proposal_state = v.get(&proposal).unwrap().0.clone();
-        assert!(
-            !proposal_state.vote_record.yes_votes.contains(&caller)
-                && !proposal_state.vote_record.no_votes.contains(&caller)
-                && !proposal_state.vote_record.abstain_votes.contains(&caller),
-            "caller has already voted"
-        );
-        if ic_cdk::api::time() > proposal_state.expiration - get_vote_buffer_time() {
-            return Err(ReturnError::Expired);
-        }
-        match vote {
-            Vote::Yes => {
-                proposal_state.vote_record.yes_votes.push(caller);
-            }
-            Vote::No => {
-                proposal_state.vote_record.no_votes.push(caller);
-            }
-            Vote::Abstain => {
-                proposal_state.vote_record.abstain_votes.push(caller);
+/// Each signer who committed calls this function during the reveal phase, with the committed vote and salt.
+/// The revealed vote is recorded, and pushed to governance if it decides the proposal.
+#[update]
+pub fn reveal_vote(proposal: Index, vote: Vote, salt: ByteBuf) -> Result<(), ReturnError> {
+    let caller = ic_cdk::api::caller();
+    let mut proposal_state = get_proposal_state(proposal).ok_or(ReturnError::InputError)?;
+    proposal_state
+        .secret
+        .as_mut()
+        .ok_or(ReturnError::InputError)?
+        .reveal(&caller, &vote, &salt, ic_cdk::api::time())?;
+    add_vote(proposal, proposal_state, caller, vote)
+}
+
+/// Closes the reveal phase of a proposal with a timer, right after it ends at `reveal_end`.
+pub(crate) fn schedule_reveal_close(proposal: Index, reveal_end: TimeNs) {
+    let delay = reveal_end.saturating_sub(ic_cdk::api::time()) + 1;
+    ic_cdk_timers::set_timer(Duration::from_nanos(delay), move || close_reveal(proposal));
+}
+
+/// Closes the reveal phase of a proposal, counts the unrevealed commitments as abstain,
+/// and pushes the result if the proposal is decided.
+fn close_reveal(proposal: Index) {
+    if let Some(mut proposal_state) = get_proposal_state(proposal) {
+        if close_reveal_if_due(&mut proposal_state) {
+            let push = request_push(&mut proposal_state);
+            set_proposal_state(proposal, proposal_state);
+            if push {
+                schedule_push(proposal, Duration::ZERO);
             }
         }
-        v.insert(proposal, Cbor(proposal_state));
+    }
+}
+
+/// Counts the unrevealed commitments as abstain once the reveal phase is over. Returns whether it did.
+fn close_reveal_if_due(proposal_state: &mut ProposalState) -> bool {
+    let unrevealed = match proposal_state.secret.as_mut() {
+        Some(secret) => match secret.close(ic_cdk::api::time()) {
+            Some(unrevealed) => unrevealed,
+            None => return false,
+        },
+        None => return false,
+    };
+    for signer in unrevealed {
+        proposal_state.vote_record.add(signer, Vote::Abstain);
+    }
+    if proposal_state.state == State::Open {
+        if let Some(decision) = signer_set_of(proposal_state).decide(&proposal_state.vote_record) {
+            proposal_state.state = decision;
+        }
+    }
+    true
+}
+
+/// Anyone can call this function to relay a ballot signed by a key holder of the proposal.
+/// The signature is over `ballot.signing_bytes` with the id of this canister.
+/// The key holder votes as the principal of `public_key` returned by `get_key_holder`, which must be in the
+/// signer set of the proposal. Each key is a separate signer: an Ed25519 key and a secp256k1 key are never the same one.
+/// The nonce of the ballot must be greater than the nonce of every previous ballot of the key.
+#[update]
+pub fn vote_proposal_signed(
+    ballot: Ballot,
+    signature: ByteBuf,
+    public_key: SignerKey,
+) -> Result<(), ReturnError> {
+    if ballot.governance != get_governance() {
+        return Err(ReturnError::InputError);
+    }
+    public_key.verify(&ballot.signing_bytes(&ic_cdk::api::id()), &signature)?;
+    let key_holder = public_key.principal()?;
+    if get_ballot_nonce(&key_holder).is_some_and(|nonce| ballot.nonce <= nonce) {
+        return Err(ReturnError::InvalidNonce);
+    }
+    cast_vote(key_holder, ballot.proposal, ballot.vote)?;
+    set_ballot_nonce(&key_holder, ballot.nonce);
+    Ok(())
+}
+
+/// Returns the principal to add to the signer set for a key holder, and the nonce of its last ballot.
+/// Every public key has its own principal, so a signer with keys on both curves is added once per key.
+#[query]
+pub fn get_key_holder(public_key: SignerKey) -> Result<(Principal, Option<u64>), ReturnError> {
+    let key_holder = public_key.principal()?;
+    Ok((key_holder, get_ballot_nonce(&key_holder)))
+}
+
+fn cast_vote(caller: Principal, proposal: Index, vote: Vote) -> Result<(), ReturnError> {
+    let proposal_state = get_proposal_state(proposal).ok_or(ReturnError::InputError)?;
+    if proposal_state.secret.is_some() {
+        return Err(ReturnError::InputError);
+    }
+    add_vote(proposal, proposal_state, caller, vote)
+}
+
+/// Records the vote of a signer of the proposal, and pushes the result if the vote decides the proposal.
+fn add_vote(
+    proposal: Index,
+    mut proposal_state: ProposalState,
+    caller: Principal,
+    vote: Vote,
+) -> Result<(), ReturnError> {
+    let signer_set = signer_set_of(&proposal_state);
+    if signer_set.weight_of(&caller).is_none() {
+        return Err(ReturnError::Unauthorized);
+    }
+    assert!(
+        proposal_state.vote_record.vote_of(&caller).is_none(),
+        "caller has already voted"
+    );
+    if ic_cdk::api::time() > proposal_state.expiration - get_vote_buffer_time() {
+        return Err(ReturnError::Expired);
+    }
+    proposal_state.vote_record.add(caller, vote);
+    if proposal_state.state == State::Open {
+        if let Some(decision) = signer_set.decide(&proposal_state.vote_record) {
+            proposal_state.state = decision;
+        }
+    }
+    let push = request_push(&mut proposal_state);
+    set_proposal_state(proposal, proposal_state);
+    if push {
+        schedule_push(proposal, Duration::ZERO);
+    }
+    Ok(())
+}
+
+/// A signer calls this function to replace their vote on a proposal that is not decided yet.
+#[update]
+pub fn change_vote(proposal: Index, vote: Vote) -> Result<(), ReturnError> {
+    let caller = ic_cdk::api::caller();
+    amend_vote(proposal, |record| {
+        record.remove(&caller).ok_or(ReturnError::InputError)?;
+        record.add(caller, vote);
         Ok(())
     })
 }
 
-/// Signer can call this function to submit vote result back to governance.
-/// Can only be called when threshold is reached.
-/// The submitted result is simply a yes/no voting power of 1 with a total voting power of 1.
+/// A signer calls this function to withdraw their vote on a proposal that is not decided yet.
 #[update]
-pub async fn submit_vote_result(proposal: Index) -> Result<(), ReturnError> {
-    require_caller_has_role(UserRole::Signer);
+pub fn retract_vote(proposal: Index) -> Result<(), ReturnError> {
+    let caller = ic_cdk::api::caller();
+    amend_vote(proposal, |record| {
+        record.remove(&caller).ok_or(ReturnError::InputError)?;
+        Ok(())
+    })
+}
 
-    let (pass_threshold, _) = get_m_of_n();
+/// Applies a change to the vote record of a proposal that is still open for voting, see `ProposalState::check_amendable`.
+/// Nothing is reported before the proposal is decided, so an amendment never needs a correcting delta.
+/// If the change decides the proposal, the result is pushed to governance.
+fn amend_vote(
+    proposal: Index,
+    amend: impl FnOnce(&mut VoteRecord) -> Result<(), ReturnError>,
+) -> Result<(), ReturnError> {
+    let mut proposal_state = get_proposal_state(proposal).ok_or(ReturnError::InputError)?;
+    proposal_state.check_amendable(ic_cdk::api::time(), get_vote_buffer_time())?;
+    // Only votes of signers of the proposal are recorded, so a recorded vote can always be amended by its signer.
+    amend(&mut proposal_state.vote_record)?;
+    if let Some(decision) = signer_set_of(&proposal_state).decide(&proposal_state.vote_record) {
+        proposal_state.state = decision;
+    }
+    let push = request_push(&mut proposal_state);
+    set_proposal_state(proposal, proposal_state);
+    if push {
+        schedule_push(proposal, Duration::ZERO);
+    }
+    Ok(())
+}
 
-    let vote_passed = PROPOSAL_VOTES.with(|v| {
-        let mut v = v.borrow_mut();
-        let mut proposal_state = v.get(&proposal).unwrap().0.clone();
-        // If already passed then return true to allow re-sync in case of error.
-        if proposal_state.state == State::Passed {
-            return Ok(true);
+/// A signer of the proposal can call this function to submit vote result back to governance.
+/// Can only be called once the vote is decided: when the weighted threshold is reached, or when it can no longer be
+/// reached even if all remaining signers vote yes.
+/// The submitted result is the weighted voting power of the yes, no and abstain votes, with the total weight of the
+/// signers of the proposal as total voting power. Governance decides a passed proposal from these counts with the
+/// passing threshold of the validator, and rejects a rejected proposal whatever the counts.
+/// Only the change since the last submission is sent, so a result can be submitted again safely.
+#[update]
+pub async fn submit_vote_result(proposal: Index) -> Result<(), ReturnError> {
+    let vote_decided = {
+        let mut proposal_state = get_proposal_state(proposal).ok_or(ReturnError::InputError)?;
+        // Signers removed since the proposal was registered still belong to its snapshot.
+        if signer_set_of(&proposal_state)
+            .weight_of(&ic_cdk::api::caller())
+            .is_none()
+        {
+            return Err(ReturnError::Unauthorized);
+        }
+        // Count unrevealed commitments, in case the timer did not run, e.g. because of an upgrade.
+        if close_reveal_if_due(&mut proposal_state) {
+            set_proposal_state(proposal, proposal_state.clone());
         }
-        // Check if voting has expired.
-        if ic_cdk::api::time() > proposal_state.expiration {
+        // If already decided then return true to allow re-sync in case of error or of votes cast since.
+        if proposal_state.is_decided() {
+            true
+        } else if ic_cdk::api::time() > proposal_state.expiration {
+            // Voting has expired.
             proposal_state.state = State::Failed;
-            v.insert(proposal, Cbor(proposal_state));
+            set_proposal_state(proposal, proposal_state);
             return Err(ReturnError::Expired);
+        } else {
+            // Check if threshold is reached or unreachable.
+            let decision = signer_set_of(&proposal_state).decide(&proposal_state.vote_record);
+            let decided = decision.is_some();
+            proposal_state.state = decision.unwrap_or(State::Open);
+            // Update state.
+            set_proposal_state(proposal, proposal_state);
+            decided
         }
-        // Check if threshold is reached.
-        let yes_votes = proposal_state.vote_record.yes_votes.len() as u64;
-        let passed = yes_votes >= pass_threshold;
-        if passed {
-            proposal_state.state = State::Passed;
-        }
-        // Update state.
-        v.insert(proposal, Cbor(proposal_state));
-        Ok(passed)
-    })?;
-    // If a vote has not passed, it simply won't be submitted.
-    if vote_passed {
-        submit_result(proposal, 1, 0, 0, 1).await
+    };
+    // If a vote is not decided yet, it simply won't be submitted.
+    if vote_decided {
+        report_tally(proposal).await
     } else {
         Err(ReturnError::GenericError)
     }
 }
 
+/// Sends the change of the weighted tally since the last report to governance, and records the push status.
+/// The tally is marked as reported before the call, so that concurrent reports do not count it twice,
+/// and its delta is taken back if the call fails, even if later reports went out in the meantime.
+/// Votes recorded during the call are pushed with another report.
+pub(crate) async fn report_tally(proposal: Index) -> Result<(), ReturnError> {
+    let mut proposal_state = get_proposal_state(proposal).ok_or(ReturnError::InputError)?;
+    let signer_set = signer_set_of(&proposal_state);
+    let tally = signer_set.tally(&proposal_state.vote_record);
+    let previous = std::mem::replace(&mut proposal_state.reported, tally.clone());
+    let rejected = proposal_state.state == State::Rejected;
+    set_proposal_state(proposal, proposal_state);
+
+    let delta = tally.delta_from(&previous);
+    let res = submit_result(
+        proposal,
+        rejected,
+        delta.yes,
+        delta.no,
+        delta.abstain,
+        signer_set.total_weight as VotingPower,
+    )
+    .await;
+    if let Some(mut proposal_state) = get_proposal_state(proposal) {
+        let mut push_again = false;
+        match &res {
+            Ok(()) => {
+                let current = signer_set_of(&proposal_state).tally(&proposal_state.vote_record);
+                if current != proposal_state.reported {
+                    proposal_state.push = PushStatus::Pending;
+                    push_again = true;
+                } else {
+                    proposal_state.push = PushStatus::Pushed {
+                        at: ic_cdk::api::time(),
+                    }
+                }
+            }
+            Err(e) => {
+                proposal_state.reported.take_back(&delta);
+                proposal_state.push = PushStatus::Failed {
+                    attempts: proposal_state.push.failed_attempts() + 1,
+                    last_error: e.clone(),
+                };
+            }
+        }
+        set_proposal_state(proposal, proposal_state);
+        if push_again {
+            schedule_push(proposal, Duration::ZERO);
+        }
+    }
+    res
+}
+
+/// Sends a tally update to governance. A rejected proposal is rejected in governance with the update.
 async fn submit_result(
     proposal: Index,
+    rejected: bool,
     yes_voting_power: VotingPower,
     no_voting_power: VotingPower,
     abstain_voting_power: VotingPower,
@@ -149,20 +433,19 @@ async fn submit_result(
         abstain_voting_power,
         total_voting_power,
     ));
-    let res = ic_cdk::api::call::call_raw128(
-        get_governance(),
-        "update_vote_result_and_total_voting_power",
-        &args_raw.unwrap(),
-        0,
-    )
-    .await
-    .map_err(|_| ReturnError::InterCanisterCallError)?;
+    let method = match rejected {
+        true => "reject_vote_result",
+        false => "update_vote_result_and_total_voting_power",
+    };
+    let res = ic_cdk::api::call::call_raw128(get_governance(), method, &args_raw.unwrap(), 0)
+        .await
+        .map_err(|_| ReturnError::InterCanisterCallError)?;
     let result: Result<(), ReturnError> = decode_one(&res).unwrap();
     result
 }
 
-/// Update the m-of-n configuration of the multisig.
-/// It could be dangerous to update the m-of-n configuration when there are open proposals.
+/// Update the m-of-n configuration of the multisig, where every signer has a weight of 1.
+/// Proposals that are already registered keep their signers and threshold.
 /// # Panics
 /// Panics if `votes_required` is greater than `total_votes`.
 /// Panics if `total_votes` is not equal to the number of signers.
@@ -170,15 +453,41 @@ async fn submit_result(
 #[update]
 pub fn update_m_of_n(votes_required: u64, total_votes: u64, signers: Vec<Principal>) {
     require_caller_has_role(UserRole::Admin);
-    assert!(votes_required <= total_votes);
-    assert_eq!(total_votes, signers.len() as u64); // SAFETY: Uniqueness of signers is checked in add_role_internal.
+    assert_eq!(total_votes, signers.len() as u64);
+    set_signers(
+        votes_required,
+        signers.into_iter().map(|p| (p, 1)).collect(),
+    );
+}
+
+/// Update the signers with their weights, and the weight required to pass.
+/// Proposals that are already registered keep their signers and threshold.
+/// # Panics
+/// Panics if any weight is 0, or if `weight_required` is greater than the total weight.
+/// Panics if any memory operation fails.
+#[update]
+pub fn update_signers(weight_required: u64, signers: Vec<(Principal, u64)>) {
+    require_caller_has_role(UserRole::Admin);
+    set_signers(weight_required, signers);
+}
 
-    config_set_m_of_n(votes_required, total_votes);
+/// Replaces the signer set and the weighted threshold.
+pub(crate) fn set_signers(weight_required: u64, signers: Vec<(Principal, u64)>) {
+    assert!(signers.iter().all(|(_, weight)| *weight > 0));
+    let total_weight = signers
+        .iter()
+        .try_fold(0u64, |sum, (_, weight)| sum.checked_add(*weight))
+        .expect("total weight overflow");
+    assert!(weight_required <= total_weight);
 
-    clear_users_of_role(UserRole::Signer);
+    config_set_weight_threshold(weight_required, total_weight);
+
+    _clear_users_of_role(UserRole::Signer);
+    // SAFETY: Uniqueness of signers is checked in add_role_internal.
     signers
-        .into_iter()
-        .for_each(|p| add_role_internal(UserRole::Signer, p).expect("signer init failed"));
+        .iter()
+        .for_each(|(p, _)| add_role_internal(UserRole::Signer, *p).expect("signer init failed"));
+    set_signer_weights(&signers);
 }
 
 #[update]
@@ -187,6 +496,24 @@ pub fn set_governance(canister_id: Principal) {
     config_set_governance(canister_id);
 }
 
+/// Enables commit-reveal ballots for new proposals, with a reveal phase of `reveal_window` nano-seconds
+/// that ends `vote_buffer_time` before the end of voting. `None` disables them for new proposals.
+/// New proposals that leave no time to commit before the reveal phase use public ballots.
+/// # Panics
+/// Panics if `reveal_window` is 0, or too long to fit before the end of voting of any proposal.
+#[update]
+pub fn set_reveal_window(reveal_window: Option<TimeNs>) {
+    require_caller_has_role(UserRole::Admin);
+    if let Some(reveal_window) = reveal_window {
+        assert!(reveal_window_can_fit(
+            reveal_window,
+            get_vote_buffer_time(),
+            ic_cdk::api::time()
+        ));
+    }
+    config_set_reveal_window(reveal_window);
+}
+
 #[update]
 pub fn set_name_description(name: String, description: String) {
     require_caller_has_role(UserRole::Admin);
@@ -200,18 +527,7 @@ pub fn get_num_proposals() -> u64 {
 
 #[query]
 pub fn get_open_proposals() -> Vec<Index> {
-    PROPOSAL_VOTES.with(|v| {
-        let v = v.borrow();
-        v.iter()
-            .filter_map(|(k, _)| {
-                if v.get(&k).unwrap().0.state == State::Open {
-                    Some(k)
-                } else {
-                    None
-                }
-            })
-            .collect()
-    })
+    get_all_proposal_ids_by_state(&State::Open)
 }
 
 #[query]
@@ -234,10 +550,11 @@ pub fn get_governance() -> Principal {
     get_config().unwrap().governance_canister
 }
 
+/// Returns the signer weight required to pass, and the total weight of all signers.
 #[query]
 pub fn get_m_of_n() -> (u64, u64) {
     let config = get_config().unwrap();
-    (config.votes_required, config.total_votes)
+    (config.weight_required, config.total_weight)
 }
 
 #[query]
@@ -249,3 +566,33 @@ pub fn get_vote_buffer_time() -> u64 {
 ic_cdk::export_candid!();
 
 fn main() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registered_proposals_keep_their_signer_snapshot() {
+        let (a, b, c) = (
+            Principal::from_slice(&[1; 29]),
+            Principal::from_slice(&[2; 29]),
+            Principal::from_slice(&[3; 29]),
+        );
+        set_signers(2, vec![(a, 1), (b, 1), (c, 1)]);
+        let snapshot = signer_set_for(7, None);
+        set_proposal_state(7, ProposalState::new(1_000, snapshot.clone()));
+
+        // As done by `update_m_of_n` and `update_signers`.
+        set_signers(1, vec![(c, 1)]);
+        set_signers(4, vec![(a, 3), (c, 2)]);
+        let proposal_state = get_proposal_state(7).unwrap();
+        assert_eq!(signer_set_of(&proposal_state), snapshot);
+        assert_eq!(signer_set_of(&proposal_state).weight_of(&b), Some(1));
+        assert_eq!(signer_set_of(&proposal_state).weight_required, 2);
+
+        // Registering the proposal again keeps the first snapshot, new proposals take the current signers.
+        assert_eq!(signer_set_for(7, None), snapshot);
+        assert_eq!(signer_set_for(8, None), current_signer_set());
+        assert_eq!(signer_set_for(8, None).weight_of(&b), None);
+    }
+}
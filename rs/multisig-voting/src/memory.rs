@@ -1,10 +1,19 @@
-use crate::types::{Cbor, Config, Index, ProposalState, ReturnError, StablePrincipal, RM, VM};
+use crate::access::{users_of_role, UserRole};
+use crate::types::{
+    Cbor, Config, Index, ProposalState, PushStatus, ReturnError, SignerSet, SignerStats,
+    StablePrincipal, State, TimeNs, Vote, RM, VM,
+};
 
 use candid::Principal;
 use ic_cdk_macros::query;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, StableVec};
 use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+/// Maximum number of entries returned by a paginated query.
+pub const MAX_PAGE_SIZE: u64 = 100;
 
 const CONFIG_PAGE_SIZE: u64 = 1;
 
@@ -17,6 +26,15 @@ const MM_PAGE_START: u64 = 512;
 const ADMIN_ROLES_MEM_ID: MemoryId = MemoryId::new(0);
 const SIGNER_ROLES_MEM_ID: MemoryId = MemoryId::new(1);
 const PROPOSAL_VOTES_MEM_ID: MemoryId = MemoryId::new(2);
+const SIGNER_WEIGHTS_MEM_ID: MemoryId = MemoryId::new(3);
+const BALLOT_NONCES_MEM_ID: MemoryId = MemoryId::new(4);
+const STATE_INDEX_MEM_ID: MemoryId = MemoryId::new(5);
+const VOTER_INDEX_MEM_ID: MemoryId = MemoryId::new(6);
+const SIGNER_STATS_MEM_ID: MemoryId = MemoryId::new(7);
+const INDEX_REBUILD_MEM_ID: MemoryId = MemoryId::new(8);
+
+/// Number of proposals indexed per message while the indexes are rebuilt.
+const INDEX_REBUILD_BATCH_SIZE: u64 = 1_000;
 
 thread_local! {
     pub static CONFIG: RefCell<StableCell<Cbor<Option<Config>>, RM>> =
@@ -28,9 +46,10 @@ thread_local! {
                 description: "An m-of-n multisig canister".to_string(),
                 initialized: false,
                 governance_canister: Principal::anonymous(),
-                votes_required: 2,
-                total_votes: 3,
+                weight_required: 2,
+                total_weight: 3,
                 vote_buffer_time: 900 * 1_000_000_000, // voting here ends 15 minutes early to ensure async update back to governance
+                reveal_window: None,
             })),
         ).expect("Failed to initialize config")
     );
@@ -52,6 +71,49 @@ thread_local! {
                 mm.borrow().get(SIGNER_ROLES_MEM_ID)).expect("init failed"))
     });
 
+    // map of signer to its weight. Signers without a weight have a weight of 1.
+    pub static SIGNER_WEIGHTS: RefCell<StableBTreeMap<StablePrincipal, u64, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(SIGNER_WEIGHTS_MEM_ID)))
+    });
+
+    // map of key holder to the nonce of its last signed ballot
+    pub static BALLOT_NONCES: RefCell<StableBTreeMap<StablePrincipal, u64, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(BALLOT_NONCES_MEM_ID)))
+    });
+
+    // set of (state, proposal index), kept in sync with PROPOSAL_VOTES by set_proposal_state
+    pub static STATE_INDEX: RefCell<StableBTreeMap<(u8, Index), (), VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(STATE_INDEX_MEM_ID)))
+    });
+
+    // map of (signer, proposal index) to the vote of the signer, kept in sync with PROPOSAL_VOTES by set_proposal_state
+    pub static VOTER_INDEX: RefCell<StableBTreeMap<(StablePrincipal, Index), u8, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(VOTER_INDEX_MEM_ID)))
+    });
+
+    // map of signer to its participation, kept in sync with PROPOSAL_VOTES by set_proposal_state
+    pub static SIGNER_STATS: RefCell<StableBTreeMap<StablePrincipal, Cbor<SignerStats>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(SIGNER_STATS_MEM_ID)))
+    });
+
+    // next proposal to index while the indexes are rebuilt, or None once they are complete
+    pub static INDEX_REBUILD: RefCell<StableCell<Cbor<Option<Index>>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            #[allow(clippy::expect_used)]
+            RefCell::new(StableCell::init(
+                mm.borrow().get(INDEX_REBUILD_MEM_ID), Cbor(None)).expect("init failed"))
+    });
+
     // map of proposal index to proposal state
     pub static PROPOSAL_VOTES: RefCell<StableBTreeMap<Index, Cbor<ProposalState>, VM>> =
         MEMORY_MANAGER.with(|mm| {
@@ -98,43 +160,368 @@ pub fn config_set_name_description(name: String, description: String) {
     });
 }
 
-pub fn config_set_m_of_n(m: u64, n: u64) {
+pub fn config_set_reveal_window(reveal_window: Option<TimeNs>) {
     CONFIG.with(|c| {
         let mut c = c.borrow_mut();
         let mut config = c.get().0.clone().unwrap();
-        config.votes_required = m;
-        config.total_votes = n;
+        config.reveal_window = reveal_window;
         c.set(Cbor(Some(config))).expect("config update failed");
     });
 }
 
+pub fn config_set_weight_threshold(weight_required: u64, total_weight: u64) {
+    CONFIG.with(|c| {
+        let mut c = c.borrow_mut();
+        let mut config = c.get().0.clone().unwrap();
+        config.weight_required = weight_required;
+        config.total_weight = total_weight;
+        c.set(Cbor(Some(config))).expect("config update failed");
+    });
+}
+
+// ==== Signer Weights ====
+/// Replaces the weights of all signers.
+pub fn set_signer_weights(signers: &[(Principal, u64)]) {
+    SIGNER_WEIGHTS.with(|w| {
+        let mut w = w.borrow_mut();
+        let keys: Vec<_> = w.iter().map(|(k, _)| k).collect();
+        for key in keys {
+            w.remove(&key);
+        }
+        for (signer, weight) in signers {
+            w.insert(StablePrincipal::from(signer), *weight);
+        }
+    })
+}
+
+pub fn get_signer_weight(signer: &Principal) -> u64 {
+    SIGNER_WEIGHTS.with(|w| w.borrow().get(&StablePrincipal::from(signer)).unwrap_or(1))
+}
+
+/// Returns every signer with its weight.
+#[query]
+pub fn get_signer_weights() -> Vec<(Principal, u64)> {
+    users_of_role(UserRole::Signer)
+        .into_iter()
+        .map(|p| (p, get_signer_weight(&p)))
+        .collect()
+}
+
+/// The current signers and threshold, which are snapshotted into newly registered proposals.
+pub fn current_signer_set() -> SignerSet {
+    #[allow(clippy::unwrap_used)]
+    let config = get_config().unwrap();
+    SignerSet {
+        weight_required: config.weight_required,
+        total_weight: config.total_weight,
+        signers: get_signer_weights(),
+    }
+}
+
+/// The signers and threshold that apply to a proposal.
+pub fn signer_set_of(proposal_state: &ProposalState) -> SignerSet {
+    proposal_state
+        .signer_set
+        .clone()
+        .unwrap_or_else(current_signer_set)
+}
+
+// ==== Ballot Nonces ====
+pub fn get_ballot_nonce(key_holder: &Principal) -> Option<u64> {
+    BALLOT_NONCES.with(|n| n.borrow().get(&StablePrincipal::from(key_holder)))
+}
+
+pub fn set_ballot_nonce(key_holder: &Principal, nonce: u64) {
+    BALLOT_NONCES.with(|n| {
+        n.borrow_mut()
+            .insert(StablePrincipal::from(key_holder), nonce)
+    });
+}
+
 // ==== Proposal Votes ====
 #[query]
 pub fn get_proposal_state(index: Index) -> Option<ProposalState> {
     PROPOSAL_VOTES.with(|p| p.borrow().get(&index).map(|Cbor(state)| state.clone()))
 }
 
-#[allow(unused)]
+/// Stores the state of a proposal, and updates the indexes.
+/// A proposal that the index rebuild has not reached yet is indexed by the rebuild instead.
 pub fn set_proposal_state(index: Index, state: ProposalState) {
-    PROPOSAL_VOTES.with(|p| {
-        p.borrow_mut().insert(index, Cbor(state));
-    })
+    let previous = PROPOSAL_VOTES.with(|p| p.borrow_mut().insert(index, Cbor(state.clone())));
+    if is_indexed(index) {
+        update_indexes(index, previous.as_ref().map(|Cbor(s)| s), &state);
+    }
 }
 
 // Sets proposal state if not existing.
+#[allow(unused)]
 pub fn add_proposal_state(index: Index, state: ProposalState) {
-    PROPOSAL_VOTES.with(|p| {
-        let mut p = p.borrow_mut();
-        if !p.contains_key(&index) {
-            p.insert(index, Cbor(state));
+    if get_proposal_state(index).is_none() {
+        set_proposal_state(index, state);
+    }
+}
+
+/// Updates the state, voter and signer indexes with the difference between two states of a proposal.
+fn update_indexes(index: Index, previous: Option<&ProposalState>, current: &ProposalState) {
+    if previous.map(|s| &s.state) != Some(&current.state) {
+        STATE_INDEX.with(|i| {
+            let mut i = i.borrow_mut();
+            if let Some(previous) = previous {
+                i.remove(&(previous.state.as_byte(), index));
+            }
+            i.insert((current.state.as_byte(), index), ());
+        });
+    }
+
+    let eligible = |s: Option<&ProposalState>| -> Vec<Principal> {
+        s.and_then(|s| s.signer_set.as_ref())
+            .map(|set| set.signers.iter().map(|(p, _)| *p).collect())
+            .unwrap_or_default()
+    };
+    let (was_eligible, is_eligible) = (eligible(previous), eligible(Some(current)));
+    for signer in is_eligible.iter().filter(|p| !was_eligible.contains(p)) {
+        update_signer_stats(signer, |stats| stats.eligible += 1);
+    }
+    for signer in was_eligible.iter().filter(|p| !is_eligible.contains(p)) {
+        update_signer_stats(signer, |stats| {
+            stats.eligible = stats.eligible.saturating_sub(1)
+        });
+    }
+
+    let votes = |s: Option<&ProposalState>| -> BTreeMap<Principal, Vote> {
+        let mut votes = BTreeMap::new();
+        if let Some(s) = s {
+            let record = &s.vote_record;
+            for (list, vote) in [
+                (&record.yes_votes, Vote::Yes),
+                (&record.no_votes, Vote::No),
+                (&record.abstain_votes, Vote::Abstain),
+            ] {
+                votes.extend(list.iter().map(|p| (*p, vote.clone())));
+            }
+        }
+        votes
+    };
+    let (was_voted, is_voted) = (votes(previous), votes(Some(current)));
+    for (signer, vote) in &was_voted {
+        if is_voted.get(signer) != Some(vote) {
+            VOTER_INDEX.with(|i| {
+                i.borrow_mut()
+                    .remove(&(StablePrincipal::from(signer), index))
+            });
+            update_signer_stats(signer, |stats| stats.remove_vote(vote));
+        }
+    }
+    for (signer, vote) in &is_voted {
+        if was_voted.get(signer) != Some(vote) {
+            VOTER_INDEX.with(|i| {
+                i.borrow_mut()
+                    .insert((StablePrincipal::from(signer), index), vote.as_byte())
+            });
+            update_signer_stats(signer, |stats| stats.add_vote(vote));
         }
+    }
+}
+
+fn update_signer_stats(signer: &Principal, update: impl FnOnce(&mut SignerStats)) {
+    SIGNER_STATS.with(|s| {
+        let mut s = s.borrow_mut();
+        let key = StablePrincipal::from(signer);
+        let mut stats = s.get(&key).map(|Cbor(stats)| stats).unwrap_or_default();
+        update(&mut stats);
+        s.insert(key, Cbor(stats));
     })
 }
 
+/// Starts rebuilding the indexes from the proposal states, e.g. for proposals stored before the indexes existed.
+/// The indexes must be empty. The rebuild runs in batches with `rebuild_indexes`.
+pub fn start_index_rebuild() {
+    set_index_rebuild_cursor(Some(0));
+}
+
+/// Indexes the next batch of proposals of a rebuild. Returns whether the rebuild is complete.
+/// Runs on every upgrade, then in later messages until complete.
+pub fn rebuild_indexes() -> bool {
+    rebuild_indexes_batch(INDEX_REBUILD_BATCH_SIZE)
+}
+
+/// Indexes up to `limit` proposals from the cursor, which is a proposal index. A proposal stored during the rebuild
+/// is indexed once: by `set_proposal_state` if the cursor passed it, and by the rebuild otherwise.
+fn rebuild_indexes_batch(limit: u64) -> bool {
+    let Some(next) = INDEX_REBUILD.with(|c| c.borrow().get().0) else {
+        return true;
+    };
+    let batch: Vec<_> =
+        PROPOSAL_VOTES.with(|p| p.borrow().range(next..).take(limit as usize).collect());
+    for (index, Cbor(state)) in &batch {
+        update_indexes(*index, None, state);
+    }
+    let cursor = match batch.last() {
+        Some((index, _)) if batch.len() as u64 == limit => index.checked_add(1),
+        _ => None,
+    };
+    set_index_rebuild_cursor(cursor);
+    cursor.is_none()
+}
+
+fn set_index_rebuild_cursor(cursor: Option<Index>) {
+    INDEX_REBUILD.with(|c| {
+        #[allow(clippy::expect_used)]
+        c.borrow_mut()
+            .set(Cbor(cursor))
+            .expect("index rebuild cursor update failed");
+    });
+}
+
+/// Whether the indexes are being rebuilt, so that queries on them would be incomplete.
+pub fn is_indexing() -> bool {
+    INDEX_REBUILD.with(|c| c.borrow().get().0.is_some())
+}
+
+/// Whether the proposal is in the indexes, i.e. no rebuild is running or the rebuild has reached it.
+fn is_indexed(index: Index) -> bool {
+    INDEX_REBUILD.with(|c| !matches!(c.borrow().get().0, Some(next) if index >= next))
+}
+
+fn require_indexes() -> Result<(), ReturnError> {
+    match is_indexing() {
+        true => Err(ReturnError::IndexingInProgress),
+        false => Ok(()),
+    }
+}
+
+pub fn is_index_empty() -> bool {
+    STATE_INDEX.with(|i| i.borrow().is_empty())
+}
+
+/// Start of a page of an index keyed by `(key, proposal index)`, after the proposal `start_after` if given.
+fn page_start<T>(key: T, start_after: Option<Index>) -> Bound<(T, Index)> {
+    match start_after {
+        Some(index) => Bound::Excluded((key, index)),
+        None => Bound::Included((key, 0)),
+    }
+}
+
+/// Returns a page of the proposals in a state, oldest first, after the proposal `start_after` if given.
+/// At most `MAX_PAGE_SIZE` proposals are returned. Fails with `IndexingInProgress` while the indexes are rebuilt.
+#[query]
+pub fn get_proposals_by_state(
+    state: State,
+    start_after: Option<Index>,
+    take: u64,
+) -> Result<Vec<(Index, ProposalState)>, ReturnError> {
+    require_indexes()?;
+    Ok(get_proposal_ids_by_state(&state, start_after, take)
+        .into_iter()
+        .filter_map(|index| get_proposal_state(index).map(|s| (index, s)))
+        .collect())
+}
+
+/// Returns a page of the IDs of the proposals in a state. See `get_proposals_by_state`.
+pub fn get_proposal_ids_by_state(
+    state: &State,
+    start_after: Option<Index>,
+    take: u64,
+) -> Vec<Index> {
+    let tag = state.as_byte();
+    STATE_INDEX.with(|i| {
+        i.borrow()
+            .range((
+                page_start(tag, start_after),
+                Bound::Included((tag, Index::MAX)),
+            ))
+            .take(take.min(MAX_PAGE_SIZE) as usize)
+            .map(|((_, index), _)| index)
+            .collect()
+    })
+}
+
+/// Returns the IDs of all the proposals in a state, oldest first.
+/// While the indexes are rebuilt, all proposals are scanned instead.
+pub fn get_all_proposal_ids_by_state(state: &State) -> Vec<Index> {
+    if is_indexing() {
+        return PROPOSAL_VOTES.with(|p| {
+            p.borrow()
+                .iter()
+                .filter(|(_, Cbor(s))| s.state == *state)
+                .map(|(index, _)| index)
+                .collect()
+        });
+    }
+    let tag = state.as_byte();
+    STATE_INDEX.with(|i| {
+        i.borrow()
+            .range((tag, 0)..=(tag, Index::MAX))
+            .map(|((_, index), _)| index)
+            .collect()
+    })
+}
+
+/// Returns the decided proposals with a push that is scheduled or waiting for a retry.
+pub fn get_proposal_ids_with_scheduled_push() -> Vec<Index> {
+    [State::Passed, State::Rejected]
+        .iter()
+        .flat_map(get_all_proposal_ids_by_state)
+        .filter(|index| {
+            get_proposal_state(*index).is_some_and(|s| {
+                matches!(s.push, PushStatus::Pending | PushStatus::Retrying { .. })
+            })
+        })
+        .collect()
+}
+
+/// Returns the proposals with secret ballots whose reveal phase is not closed yet, with the end of the phase.
+/// Failed proposals are left out, since their votes are no longer reported.
+pub fn get_proposals_with_open_reveal() -> Vec<(Index, TimeNs)> {
+    [State::Open, State::Passed, State::Rejected]
+        .iter()
+        .flat_map(get_all_proposal_ids_by_state)
+        .filter_map(|index| {
+            let secret = get_proposal_state(index)?.secret?;
+            (!secret.closed).then_some((index, secret.reveal_end))
+        })
+        .collect()
+}
+
+/// Returns a page of the votes of a signer, by proposal index, oldest first, after the proposal `start_after` if given.
+/// At most `MAX_PAGE_SIZE` votes are returned. Fails with `IndexingInProgress` while the indexes are rebuilt.
+#[query]
+pub fn get_votes_by_signer(
+    signer: Principal,
+    start_after: Option<Index>,
+    take: u64,
+) -> Result<Vec<(Index, Vote)>, ReturnError> {
+    require_indexes()?;
+    let key = StablePrincipal::from(signer);
+    Ok(VOTER_INDEX.with(|i| {
+        i.borrow()
+            .range((
+                page_start(key, start_after),
+                Bound::Included((key, Index::MAX)),
+            ))
+            .take(take.min(MAX_PAGE_SIZE) as usize)
+            .filter_map(|((_, index), vote)| Vote::from_byte(vote).map(|vote| (index, vote)))
+            .collect()
+    }))
+}
+
+/// Returns the participation of a signer. Fails with `IndexingInProgress` while the indexes are rebuilt.
+#[query]
+pub fn get_signer_stats(signer: Principal) -> Result<SignerStats, ReturnError> {
+    require_indexes()?;
+    Ok(SIGNER_STATS.with(|s| {
+        s.borrow()
+            .get(&StablePrincipal::from(signer))
+            .map(|Cbor(stats)| stats)
+            .unwrap_or_default()
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{State, VoteRecord};
+    use crate::secret::SecretBallot;
+    use crate::{VoteRecord, VoteTally};
     use rand::Rng;
     fn generate_random_principal() -> Principal {
         let mut rng = rand::thread_rng();
@@ -150,8 +537,8 @@ mod tests {
         assert_eq!(config.description, "An m-of-n multisig canister");
         assert!(!config.initialized);
         assert_eq!(config.governance_canister, Principal::anonymous());
-        assert_eq!(config.votes_required, 2);
-        assert_eq!(config.total_votes, 3);
+        assert_eq!(config.weight_required, 2);
+        assert_eq!(config.total_weight, 3);
         assert_eq!(config.vote_buffer_time, 900 * 1_000_000_000);
     }
 
@@ -180,11 +567,22 @@ mod tests {
     }
 
     #[test]
-    fn test_config_set_m_of_n() {
-        config_set_m_of_n(3, 4);
+    fn test_config_set_weight_threshold() {
+        config_set_weight_threshold(3, 4);
         let config = get_config().unwrap();
-        assert_eq!(config.votes_required, 3);
-        assert_eq!(config.total_votes, 4);
+        assert_eq!(config.weight_required, 3);
+        assert_eq!(config.total_weight, 4);
+    }
+
+    #[test]
+    fn test_signer_weights() {
+        let (a, b) = (generate_random_principal(), generate_random_principal());
+        set_signer_weights(&[(a, 3)]);
+        assert_eq!(get_signer_weight(&a), 3);
+        assert_eq!(get_signer_weight(&b), 1); // default weight
+        set_signer_weights(&[(b, 2)]); // replaces all weights
+        assert_eq!(get_signer_weight(&a), 1);
+        assert_eq!(get_signer_weight(&b), 2);
     }
 
     #[test]
@@ -199,6 +597,10 @@ mod tests {
                 no_votes: vec![],
                 abstain_votes: vec![],
             },
+            reported: VoteTally::default(),
+            push: PushStatus::default(),
+            signer_set: None,
+            secret: None,
         };
         let state_failed = ProposalState {
             expiration: expir,
@@ -208,6 +610,10 @@ mod tests {
                 no_votes: vec![],
                 abstain_votes: vec![],
             },
+            reported: VoteTally::default(),
+            push: PushStatus::default(),
+            signer_set: None,
+            secret: None,
         };
         add_proposal_state(index, state_open.clone());
         let state_res = get_proposal_state(index).unwrap();
@@ -225,4 +631,157 @@ mod tests {
         let state_res = get_proposal_state(index + 1).unwrap();
         assert_eq!(state_failed, state_res);
     }
+
+    #[test]
+    fn test_indexes() {
+        let (a, b) = (generate_random_principal(), generate_random_principal());
+        let signer_set = SignerSet {
+            weight_required: 2,
+            total_weight: 2,
+            signers: vec![(a, 1), (b, 1)],
+        };
+        let mut state = ProposalState::new(100, signer_set.clone());
+        set_proposal_state(1, state.clone());
+        set_proposal_state(2, ProposalState::new(100, signer_set.clone()));
+        assert_eq!(
+            get_proposal_ids_by_state(&State::Open, None, 10),
+            vec![1, 2]
+        );
+
+        state.vote_record.add(a, Vote::Yes);
+        state.vote_record.add(b, Vote::No);
+        state.state = State::Rejected;
+        set_proposal_state(1, state.clone());
+        assert_eq!(get_proposal_ids_by_state(&State::Open, None, 10), vec![2]);
+        assert_eq!(
+            get_proposal_ids_by_state(&State::Rejected, None, 10),
+            vec![1]
+        );
+        assert_eq!(
+            get_votes_by_signer(a, None, 10).unwrap(),
+            vec![(1, Vote::Yes)]
+        );
+
+        // A changed vote replaces the indexed vote.
+        state.vote_record.remove(&a);
+        state.vote_record.add(a, Vote::Abstain);
+        set_proposal_state(1, state);
+        assert_eq!(
+            get_votes_by_signer(a, None, 10).unwrap(),
+            vec![(1, Vote::Abstain)]
+        );
+        assert_eq!(
+            get_signer_stats(a).unwrap(),
+            SignerStats {
+                eligible: 2,
+                yes: 0,
+                no: 0,
+                abstain: 1
+            }
+        );
+        assert_eq!(get_votes_by_signer(b, Some(1), 10).unwrap(), vec![]);
+
+        // Proposals stored before the indexes existed are indexed in batches.
+        let stats_before = get_signer_stats(b).unwrap();
+        STATE_INDEX.with(|i| clear_map(&mut i.borrow_mut()));
+        VOTER_INDEX.with(|i| clear_map(&mut i.borrow_mut()));
+        SIGNER_STATS.with(|s| clear_map(&mut s.borrow_mut()));
+        start_index_rebuild();
+        assert!(!rebuild_indexes_batch(1));
+        assert_eq!(
+            get_signer_stats(b).err(),
+            Some(ReturnError::IndexingInProgress)
+        );
+        assert_eq!(get_all_proposal_ids_by_state(&State::Open), vec![2]);
+        // A proposal that the rebuild has not reached yet is indexed once, by the rebuild.
+        set_proposal_state(3, ProposalState::new(100, signer_set));
+        assert!(!rebuild_indexes_batch(1));
+        assert!(!rebuild_indexes_batch(1));
+        // The rebuild ends with the first batch that is not full.
+        assert!(rebuild_indexes_batch(1));
+        assert!(!is_indexing());
+        assert_eq!(
+            get_signer_stats(b).unwrap().eligible,
+            stats_before.eligible + 1
+        );
+        assert_eq!(
+            get_proposal_ids_by_state(&State::Open, None, 10),
+            vec![2, 3]
+        );
+    }
+
+    fn clear_map<K, V>(map: &mut StableBTreeMap<K, V, VM>)
+    where
+        K: ic_stable_structures::Storable + Ord + Clone,
+        V: ic_stable_structures::Storable,
+    {
+        let keys: Vec<_> = map.iter().map(|(k, _)| k).collect();
+        for key in keys {
+            map.remove(&key);
+        }
+    }
+
+    #[test]
+    fn scheduled_pushes_of_decided_proposals() {
+        let mut state = ProposalState::default();
+        set_proposal_state(1, state.clone());
+        state.state = State::Passed;
+        state.push = PushStatus::Pending;
+        set_proposal_state(2, state.clone());
+        state.push = PushStatus::Pushed { at: 1 };
+        set_proposal_state(3, state.clone());
+        state.state = State::Rejected;
+        state.push = PushStatus::Retrying {
+            attempts: 1,
+            last_error: ReturnError::InterCanisterCallError,
+        };
+        set_proposal_state(4, state);
+        assert_eq!(get_proposal_ids_with_scheduled_push(), vec![2, 4]);
+    }
+
+    #[test]
+    fn open_reveals_of_secret_ballots() {
+        let mut state = ProposalState {
+            secret: SecretBallot::new(500, 100, 0),
+            ..Default::default()
+        };
+        set_proposal_state(1, state.clone());
+        set_proposal_state(2, ProposalState::default());
+        state.state = State::Passed;
+        set_proposal_state(3, state.clone());
+        state.state = State::Failed;
+        set_proposal_state(4, state.clone());
+        state.state = State::Open;
+        state.secret.as_mut().unwrap().closed = true;
+        set_proposal_state(5, state);
+        assert_eq!(get_proposals_with_open_reveal(), vec![(1, 500), (3, 500)]);
+    }
+
+    #[test]
+    fn pages_start_after_the_cursor() {
+        for index in 0..MAX_PAGE_SIZE + 5 {
+            set_proposal_state(index, ProposalState::default());
+        }
+        let first = get_proposal_ids_by_state(&State::Open, None, 3);
+        assert_eq!(first, vec![0, 1, 2]);
+        assert_eq!(
+            get_proposal_ids_by_state(&State::Open, first.last().copied(), 3),
+            vec![3, 4, 5]
+        );
+        // Proposals leaving the state do not shift the next page.
+        let passed = ProposalState {
+            state: State::Passed,
+            ..Default::default()
+        };
+        set_proposal_state(1, passed);
+        assert_eq!(
+            get_proposal_ids_by_state(&State::Open, Some(2), 2),
+            vec![3, 4]
+        );
+        assert_eq!(
+            get_proposal_ids_by_state(&State::Open, None, u64::MAX).len() as u64,
+            MAX_PAGE_SIZE
+        );
+        assert!(get_proposal_ids_by_state(&State::Open, Some(Index::MAX), 10).is_empty());
+    }
 }
@@ -0,0 +1,78 @@
+//! Automatic submission of decided vote results to governance.
+//!
+//! `ic_cdk_timers` does not allow async closures, so each push is spawned from a one-shot timer.
+
+use crate::memory::*;
+use crate::report_tally;
+use crate::types::*;
+
+use std::time::Duration;
+
+/// Number of attempts after which a failed push is no longer retried.
+pub const MAX_PUSH_ATTEMPTS: u32 = 5;
+/// Delay before the first retry. Each further retry waits one more delay.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Marks the result of a decided proposal for pushing, unless a push is already underway.
+/// Returns whether the caller should schedule the push with `schedule_push` after saving the state.
+pub(crate) fn request_push(proposal_state: &mut ProposalState) -> bool {
+    if !proposal_state.is_decided()
+        || matches!(
+            proposal_state.push,
+            PushStatus::Pending | PushStatus::Retrying { .. }
+        )
+    {
+        return false;
+    }
+    proposal_state.push = PushStatus::Pending;
+    true
+}
+
+pub(crate) fn schedule_push(proposal: Index, delay: Duration) {
+    ic_cdk_timers::set_timer(delay, move || ic_cdk::spawn(push(proposal)));
+}
+
+/// Pushes the result, and schedules a retry if the push failed and the proposal may still be voted on.
+async fn push(proposal: Index) {
+    if report_tally(proposal).await.is_ok() {
+        return;
+    }
+    let mut proposal_state = match get_proposal_state(proposal) {
+        Some(proposal_state) => proposal_state,
+        None => return,
+    };
+    if let PushStatus::Failed {
+        attempts,
+        last_error,
+    } = proposal_state.push.clone()
+    {
+        if attempts < MAX_PUSH_ATTEMPTS
+            && last_error != ReturnError::Expired
+            && ic_cdk::api::time() < proposal_state.expiration
+        {
+            proposal_state.push = PushStatus::Retrying {
+                attempts,
+                last_error,
+            };
+            set_proposal_state(proposal, proposal_state);
+            schedule_push(proposal, RETRY_DELAY * attempts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_one_push_per_decided_result() {
+        let mut proposal_state = ProposalState::default();
+        assert!(!request_push(&mut proposal_state)); // still open
+        proposal_state.state = State::Rejected;
+        assert!(request_push(&mut proposal_state));
+        assert_eq!(proposal_state.push, PushStatus::Pending);
+        assert!(!request_push(&mut proposal_state)); // already scheduled
+        proposal_state.push = PushStatus::Pushed { at: 1 };
+        assert!(request_push(&mut proposal_state)); // a later vote is pushed as well
+    }
+}
@@ -0,0 +1,190 @@
+//! Commit-reveal ballots, which keep votes secret until every signer had the chance to vote.
+//!
+//! Signers commit `sha256(vote || salt)` until `commit_end`, then reveal the vote and salt until `reveal_end`.
+//! Revealed votes are recorded like public votes. Commitments that are not revealed count as abstain.
+
+use crate::types::{ReturnError, TimeNs, Vote};
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+/// Minimum length of a salt, so that the three possible votes cannot be guessed from a commitment.
+pub const MIN_SALT_LEN: usize = 16;
+
+/// The commit-reveal state of a proposal.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SecretBallot {
+    /// End of the commit phase, and start of the reveal phase.
+    pub commit_end: TimeNs,
+    /// End of the reveal phase, which is `expiration - vote_buffer_time` of the proposal.
+    pub reveal_end: TimeNs,
+    /// Commitments that are not revealed yet.
+    pub commitments: Vec<(Principal, ByteBuf)>,
+    /// Whether the reveal phase was closed, and the unrevealed commitments counted as abstain.
+    pub closed: bool,
+}
+
+/// `sha256(vote || salt)`, where the vote is encoded as one byte. See `Vote::as_byte`.
+pub fn commitment_of(vote: &Vote, salt: &[u8]) -> ByteBuf {
+    let mut hasher = Sha256::new();
+    hasher.update([vote.as_byte()]);
+    hasher.update(salt);
+    ByteBuf::from(hasher.finalize().to_vec())
+}
+
+/// Whether a reveal window can fit before the end of voting of a proposal registered at `now`, for some expiration.
+pub fn reveal_window_can_fit(reveal_window: TimeNs, vote_buffer_time: TimeNs, now: TimeNs) -> bool {
+    reveal_window > 0
+        && now
+            .checked_add(vote_buffer_time)
+            .and_then(|t| t.checked_add(reveal_window))
+            .is_some_and(|t| t < TimeNs::MAX)
+}
+
+impl SecretBallot {
+    /// A ballot whose reveal phase takes the last `reveal_window` before `reveal_end`.
+    /// Returns `None` if the reveal window is empty, or leaves no time to commit after `now`.
+    pub fn new(reveal_end: TimeNs, reveal_window: TimeNs, now: TimeNs) -> Option<Self> {
+        let commit_end = reveal_end.checked_sub(reveal_window)?;
+        (reveal_window > 0 && commit_end > now).then(|| Self {
+            commit_end,
+            reveal_end,
+            commitments: vec![],
+            closed: false,
+        })
+    }
+
+    /// Records or replaces the commitment of a signer during the commit phase.
+    pub fn commit(
+        &mut self,
+        signer: Principal,
+        commitment: ByteBuf,
+        now: TimeNs,
+    ) -> Result<(), ReturnError> {
+        if now > self.commit_end {
+            return Err(ReturnError::Expired);
+        }
+        if commitment.len() != 32 {
+            return Err(ReturnError::InputError);
+        }
+        self.commitments.retain(|(p, _)| *p != signer);
+        self.commitments.push((signer, commitment));
+        Ok(())
+    }
+
+    /// Checks a revealed vote against the commitment of the signer during the reveal phase, and consumes it.
+    pub fn reveal(
+        &mut self,
+        signer: &Principal,
+        vote: &Vote,
+        salt: &[u8],
+        now: TimeNs,
+    ) -> Result<(), ReturnError> {
+        if now <= self.commit_end {
+            return Err(ReturnError::InputError);
+        }
+        if now > self.reveal_end {
+            return Err(ReturnError::Expired);
+        }
+        let position = self
+            .commitments
+            .iter()
+            .position(|(p, _)| p == signer)
+            .ok_or(ReturnError::InputError)?;
+        if salt.len() < MIN_SALT_LEN || commitment_of(vote, salt) != self.commitments[position].1 {
+            return Err(ReturnError::InvalidSignature);
+        }
+        self.commitments.remove(position);
+        Ok(())
+    }
+
+    /// Closes the reveal phase once it is over, and returns the signers whose commitments count as abstain.
+    /// Returns `None` before the end of the reveal phase, or if already closed.
+    pub fn close(&mut self, now: TimeNs) -> Option<Vec<Principal>> {
+        if self.closed || now <= self.reveal_end {
+            return None;
+        }
+        self.closed = true;
+        Some(
+            std::mem::take(&mut self.commitments)
+                .into_iter()
+                .map(|(p, _)| p)
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SALT: &[u8] = b"0123456789abcdef";
+
+    #[test]
+    fn commit_then_reveal() {
+        let (a, b) = (
+            Principal::from_slice(&[1; 29]),
+            Principal::from_slice(&[2; 29]),
+        );
+        let mut ballot = SecretBallot::new(100, 40, 0).unwrap();
+        assert_eq!(ballot.commit_end, 60);
+        ballot
+            .commit(a, commitment_of(&Vote::No, SALT), 10)
+            .unwrap();
+        // A commitment can be replaced during the commit phase.
+        ballot
+            .commit(a, commitment_of(&Vote::Yes, SALT), 20)
+            .unwrap();
+        ballot
+            .commit(b, commitment_of(&Vote::No, SALT), 30)
+            .unwrap();
+        assert_eq!(
+            ballot.commit(b, commitment_of(&Vote::Yes, SALT), 61),
+            Err(ReturnError::Expired)
+        );
+        assert_eq!(
+            ballot.reveal(&a, &Vote::Yes, SALT, 60),
+            Err(ReturnError::InputError)
+        );
+        assert_eq!(
+            ballot.reveal(&a, &Vote::No, SALT, 70),
+            Err(ReturnError::InvalidSignature)
+        );
+        assert_eq!(ballot.reveal(&a, &Vote::Yes, SALT, 70), Ok(()));
+        assert_eq!(
+            ballot.reveal(&a, &Vote::Yes, SALT, 71),
+            Err(ReturnError::InputError)
+        );
+        assert_eq!(ballot.close(100), None);
+        assert_eq!(ballot.close(101), Some(vec![b]));
+        assert_eq!(ballot.close(102), None);
+    }
+
+    #[test]
+    fn reveal_window_must_leave_time_to_commit() {
+        assert_eq!(SecretBallot::new(100, 40, 59).unwrap().commit_end, 60);
+        assert_eq!(SecretBallot::new(100, 40, 60), None);
+        assert_eq!(SecretBallot::new(100, 100, 0), None);
+        assert_eq!(SecretBallot::new(100, 120, 0), None);
+        assert_eq!(SecretBallot::new(100, 0, 0), None);
+
+        assert!(reveal_window_can_fit(40, 10, 100));
+        assert!(!reveal_window_can_fit(0, 10, 100));
+        assert!(!reveal_window_can_fit(TimeNs::MAX - 110, 10, 100));
+    }
+
+    #[test]
+    fn rejects_short_salts() {
+        let a = Principal::from_slice(&[1; 29]);
+        let mut ballot = SecretBallot::new(100, 40, 0).unwrap();
+        ballot
+            .commit(a, commitment_of(&Vote::Yes, b"short"), 10)
+            .unwrap();
+        assert_eq!(
+            ballot.reveal(&a, &Vote::Yes, b"short", 70),
+            Err(ReturnError::InvalidSignature)
+        );
+    }
+}
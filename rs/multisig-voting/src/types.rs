@@ -1,3 +1,4 @@
+use crate::secret::SecretBallot;
 use candid::{CandidType, Principal};
 use ic_stable_structures::{
     memory_manager::VirtualMemory,
@@ -10,7 +11,7 @@ use std::borrow::Cow;
 pub type RM = RestrictedMemory<DefaultMemoryImpl>;
 pub type VM = VirtualMemory<RM>;
 
-#[derive(CandidType, candid::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(CandidType, Serialize, candid::Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum ReturnError {
     GenericError,
     InputError,
@@ -19,19 +20,44 @@ pub enum ReturnError {
     InterCanisterCallError,
     MemoryError,
     AlreadyExists,
+    InvalidSignature,
+    /// The nonce of a signed ballot is not greater than the nonce of the previous ballot of the key.
+    InvalidNonce,
+    /// The proposal indexes are being rebuilt after an upgrade. Retry later.
+    IndexingInProgress,
 }
 
 /// nano seconds since UNIX Epoch.
 pub type TimeNs = u64;
 pub type VotingPower = i128; // A negative value nullifies a prior vote.
 
-#[derive(Clone, Debug, CandidType, Deserialize)]
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq, Eq)]
 pub enum Vote {
     Yes,
     No,
     Abstain,
 }
 
+impl Vote {
+    /// One-byte encoding of the vote in signed ballots and commitments.
+    pub fn as_byte(&self) -> u8 {
+        match self {
+            Vote::Yes => 0,
+            Vote::No => 1,
+            Vote::Abstain => 2,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Vote> {
+        match byte {
+            0 => Some(Vote::Yes),
+            1 => Some(Vote::No),
+            2 => Some(Vote::Abstain),
+            _ => None,
+        }
+    }
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
 pub struct VoteRecord {
     pub yes_votes: Vec<Principal>,
@@ -39,19 +65,332 @@ pub struct VoteRecord {
     pub abstain_votes: Vec<Principal>,
 }
 
+impl VoteRecord {
+    /// The current vote of a signer, if any.
+    pub fn vote_of(&self, signer: &Principal) -> Option<Vote> {
+        if self.yes_votes.contains(signer) {
+            Some(Vote::Yes)
+        } else if self.no_votes.contains(signer) {
+            Some(Vote::No)
+        } else if self.abstain_votes.contains(signer) {
+            Some(Vote::Abstain)
+        } else {
+            None
+        }
+    }
+
+    pub fn add(&mut self, signer: Principal, vote: Vote) {
+        match vote {
+            Vote::Yes => self.yes_votes.push(signer),
+            Vote::No => self.no_votes.push(signer),
+            Vote::Abstain => self.abstain_votes.push(signer),
+        }
+    }
+
+    /// Removes the vote of a signer, and returns it.
+    pub fn remove(&mut self, signer: &Principal) -> Option<Vote> {
+        let vote = self.vote_of(signer)?;
+        self.yes_votes.retain(|p| p != signer);
+        self.no_votes.retain(|p| p != signer);
+        self.abstain_votes.retain(|p| p != signer);
+        Some(vote)
+    }
+
+    /// Weighted voting power of the votes, given the weight of each signer.
+    pub fn tally(&self, weight_of: impl Fn(&Principal) -> u64) -> VoteTally {
+        let sum = |votes: &[Principal]| votes.iter().map(|p| weight_of(p) as VotingPower).sum();
+        VoteTally {
+            yes: sum(&self.yes_votes),
+            no: sum(&self.no_votes),
+            abstain: sum(&self.abstain_votes),
+        }
+    }
+}
+
+/// Weighted voting power of yes, no and abstain votes.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub struct VoteTally {
+    pub yes: VotingPower,
+    pub no: VotingPower,
+    pub abstain: VotingPower,
+}
+
+impl VoteTally {
+    /// The increments that turn `reported` into this tally.
+    pub fn delta_from(&self, reported: &VoteTally) -> VoteTally {
+        VoteTally {
+            yes: self.yes - reported.yes,
+            no: self.no - reported.no,
+            abstain: self.abstain - reported.abstain,
+        }
+    }
+
+    /// Takes back a delta that was counted as reported but not delivered.
+    /// Field by field, so it holds even if later deltas were reported on top of it in the meantime.
+    pub fn take_back(&mut self, delta: &VoteTally) {
+        self.yes -= delta.yes;
+        self.no -= delta.no;
+        self.abstain -= delta.abstain;
+    }
+
+    pub fn cast(&self) -> VotingPower {
+        self.yes + self.no + self.abstain
+    }
+
+    /// `Passed` once the yes votes reach `weight_required`, `Rejected` once they cannot reach it even if all
+    /// remaining weight votes yes, and `None` while undecided.
+    pub fn decide(&self, weight_required: u64, total_weight: u64) -> Option<State> {
+        let uncast = total_weight as VotingPower - self.cast();
+        if self.yes >= weight_required as VotingPower {
+            Some(State::Passed)
+        } else if self.yes + uncast.max(0) < weight_required as VotingPower {
+            Some(State::Rejected)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
 pub enum State {
     #[default]
     Open,
     Passed,
+    /// The weighted threshold can no longer be reached.
+    Rejected,
     Failed,
 }
 
+impl State {
+    /// One-byte encoding of the state in the state index.
+    pub fn as_byte(&self) -> u8 {
+        match self {
+            State::Open => 0,
+            State::Passed => 1,
+            State::Rejected => 2,
+            State::Failed => 3,
+        }
+    }
+}
+
+/// Participation of a signer across proposals.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub struct SignerStats {
+    /// Proposals whose signer set includes the signer. Proposals registered without a signer set are not counted.
+    pub eligible: u64,
+    pub yes: u64,
+    pub no: u64,
+    pub abstain: u64,
+}
+
+impl SignerStats {
+    fn count(&mut self, vote: &Vote) -> &mut u64 {
+        match vote {
+            Vote::Yes => &mut self.yes,
+            Vote::No => &mut self.no,
+            Vote::Abstain => &mut self.abstain,
+        }
+    }
+
+    pub fn add_vote(&mut self, vote: &Vote) {
+        *self.count(vote) += 1;
+    }
+
+    pub fn remove_vote(&mut self, vote: &Vote) {
+        let count = self.count(vote);
+        *count = count.saturating_sub(1);
+    }
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
 pub struct ProposalState {
     pub expiration: TimeNs,
     pub state: State,
     pub vote_record: VoteRecord,
+    /// The tally already reported to governance, which only accepts increments.
+    #[serde(default)]
+    pub reported: VoteTally,
+    /// Status of the automatic submission of the result to governance.
+    #[serde(default)]
+    pub push: PushStatus,
+    /// The signers and threshold of the proposal, taken when the proposal was registered.
+    /// Proposals registered before snapshots were taken use the current configuration.
+    #[serde(default)]
+    pub signer_set: Option<SignerSet>,
+    /// Commit-reveal state, if the proposal was registered with secret ballots.
+    #[serde(default)]
+    pub secret: Option<SecretBallot>,
+}
+
+/// Passing threshold of a governance proposal, as set by its validator.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ProposalPassingThreshold {
+    pub quorum: Percentage<PercentagePrecision>,
+    pub passing_threshold: Percentage<PercentagePrecision>,
+    pub mode: Option<PassingMode>,
+}
+
+impl ProposalPassingThreshold {
+    /// The signer weight required to pass, out of `total_weight`.
+    /// Each percentage and the majority of the passing mode is applied to the total weight, and the strictest wins.
+    /// With a passing mode, reaching it passes the proposal in governance as well. Without one, governance only
+    /// accepts early on an absolute majority of the total, so a smaller passing weight is accepted when voting ends.
+    pub fn required_weight(&self, total_weight: u64) -> u64 {
+        let total = total_weight as u128;
+        let share = |p: &Percentage<PercentagePrecision>| {
+            let (n, d) = p.as_fraction();
+            (total * n + d - 1) / d
+        };
+        let majority = match self.mode {
+            None => 0,
+            Some(PassingMode::TwoThirds) => (total * 2 + 2) / 3,
+            Some(PassingMode::Unanimity) => total,
+            Some(
+                PassingMode::SimpleMajority
+                | PassingMode::AbsoluteMajority
+                | PassingMode::AbstainInDenominator,
+            ) => total / 2 + 1,
+        };
+        let required = share(&self.passing_threshold)
+            .max(share(&self.quorum))
+            .max(majority)
+            .clamp(1, total.max(1));
+        required as u64
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PassingMode {
+    /// More than half of yes+no.
+    SimpleMajority,
+    /// More than half of the total voting power.
+    AbsoluteMajority,
+    /// At least two thirds of yes+no.
+    TwoThirds,
+    /// At least one "yes" vote and no "no" votes.
+    Unanimity,
+    /// More than half of yes+no+abstain.
+    AbstainInDenominator,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Percentage<T>(pub T);
+
+pub type PercentagePrecision = u16;
+
+impl Percentage<u16> {
+    pub fn as_fraction(&self) -> (u128, u128) {
+        (self.0 as u128, 40_000)
+    }
+}
+
+/// The fields of a governance proposal that the multisig reads. Other fields are ignored when decoding.
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct GovernanceProposal {
+    pub passing_threshold: Option<ProposalPassingThreshold>,
+}
+
+/// Signers with their weights, and the weighted threshold.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub struct SignerSet {
+    pub weight_required: u64,
+    pub total_weight: u64,
+    pub signers: Vec<(Principal, u64)>,
+}
+
+impl SignerSet {
+    /// The weight of a signer, or `None` if not a signer of the set.
+    pub fn weight_of(&self, signer: &Principal) -> Option<u64> {
+        self.signers
+            .iter()
+            .find(|(p, _)| p == signer)
+            .map(|(_, weight)| *weight)
+    }
+
+    /// Weighted tally of the votes. Votes of principals outside the set have no weight.
+    pub fn tally(&self, vote_record: &VoteRecord) -> VoteTally {
+        vote_record.tally(|p| self.weight_of(p).unwrap_or(0))
+    }
+
+    pub fn decide(&self, vote_record: &VoteRecord) -> Option<State> {
+        self.tally(vote_record)
+            .decide(self.weight_required, self.total_weight)
+    }
+
+    /// Raises the weight required to the weight derived from a passing threshold.
+    /// A lax passing threshold never lowers the configured weight.
+    pub fn require_threshold(&mut self, threshold: &ProposalPassingThreshold) {
+        self.weight_required = self
+            .weight_required
+            .max(threshold.required_weight(self.total_weight));
+    }
+}
+
+/// Status of the automatic submission of a decided result to governance.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub enum PushStatus {
+    /// No result was pushed yet.
+    #[default]
+    NotPushed,
+    /// A push is scheduled.
+    Pending,
+    /// The current tally was pushed at this time.
+    Pushed { at: TimeNs },
+    /// A push failed and a retry is scheduled.
+    Retrying {
+        attempts: u32,
+        last_error: ReturnError,
+    },
+    /// A push failed, and is only retried by `submit_vote_result`.
+    Failed {
+        attempts: u32,
+        last_error: ReturnError,
+    },
+}
+
+impl PushStatus {
+    /// Number of consecutive failed pushes.
+    pub fn failed_attempts(&self) -> u32 {
+        match self {
+            PushStatus::Retrying { attempts, .. } | PushStatus::Failed { attempts, .. } => {
+                *attempts
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl ProposalState {
+    pub fn new(expiration: TimeNs, signer_set: SignerSet) -> Self {
+        Self {
+            expiration,
+            signer_set: Some(signer_set),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_decided(&self) -> bool {
+        matches!(self.state, State::Passed | State::Rejected)
+    }
+
+    /// Checks that the votes of the proposal can still be changed or retracted at `now`.
+    ///
+    /// Only open public ballots before `expiration - vote_buffer_time` can be amended. Tallies are only pushed to
+    /// governance once the proposal is decided, and governance only accepts increments, so a decided proposal
+    /// cannot be amended: its reported tally could never be corrected downwards.
+    pub fn check_amendable(&self, now: TimeNs, vote_buffer_time: u64) -> Result<(), ReturnError> {
+        // Secret ballots cannot be amended once revealed, but commitments can be replaced.
+        if self.secret.is_some() {
+            return Err(ReturnError::InputError);
+        }
+        if self.state == State::Failed || now > self.expiration - vote_buffer_time {
+            return Err(ReturnError::Expired);
+        }
+        if self.state != State::Open {
+            return Err(ReturnError::InputError);
+        }
+        Ok(())
+    }
 }
 
 /// Config of the canister.
@@ -65,12 +404,17 @@ pub struct Config {
     pub initialized: bool,
     /// The principal of the governance canister.
     pub governance_canister: Principal,
-    /// The number of votes required to pass.
-    pub votes_required: u64,
-    /// The number of total votes.
-    pub total_votes: u64,
+    /// The signer weight required to pass.
+    #[serde(alias = "votes_required")]
+    pub weight_required: u64,
+    /// The total weight of all signers.
+    #[serde(alias = "total_votes")]
+    pub total_weight: u64,
     /// Vote ends early by this time. It ends early to ensure async update back to governance.
     pub vote_buffer_time: TimeNs,
+    /// If set, new proposals use commit-reveal ballots, with a reveal phase of this length before the end of voting.
+    #[serde(default)]
+    pub reveal_window: Option<TimeNs>,
 }
 
 pub type Index = u64;
@@ -158,3 +502,186 @@ impl Storable for StablePrincipal {
         Self(Blob::try_from(bytes.as_ref()).unwrap())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn change_and_remove_votes() {
+        let (a, b) = (
+            Principal::from_slice(&[1; 29]),
+            Principal::from_slice(&[2; 29]),
+        );
+        let mut record = VoteRecord::default();
+        record.add(a, Vote::Yes);
+        record.add(b, Vote::No);
+        assert_eq!(record.vote_of(&a), Some(Vote::Yes));
+        assert_eq!(record.remove(&a), Some(Vote::Yes));
+        record.add(a, Vote::Abstain);
+        assert_eq!(record.vote_of(&a), Some(Vote::Abstain));
+        assert!(record.yes_votes.is_empty());
+        assert_eq!(record.remove(&b), Some(Vote::No));
+        assert_eq!(record.remove(&b), None);
+        assert_eq!(record.vote_of(&b), None);
+    }
+
+    #[test]
+    fn decides_when_threshold_reached_or_unreachable() {
+        let tally = |yes, no, abstain| VoteTally { yes, no, abstain };
+        // 3 of 5
+        assert_eq!(tally(3, 2, 0).decide(3, 5), Some(State::Passed));
+        assert_eq!(tally(2, 1, 0).decide(3, 5), None);
+        assert_eq!(tally(2, 1, 1).decide(3, 5), None);
+        assert_eq!(tally(2, 2, 1).decide(3, 5), Some(State::Rejected));
+        assert_eq!(tally(0, 2, 1).decide(3, 5), Some(State::Rejected));
+        assert_eq!(tally(0, 0, 0).decide(3, 5), None);
+    }
+
+    #[test]
+    fn signer_set_ignores_outsiders() {
+        let (a, b, c) = (
+            Principal::from_slice(&[1; 29]),
+            Principal::from_slice(&[2; 29]),
+            Principal::from_slice(&[3; 29]),
+        );
+        let set = SignerSet {
+            weight_required: 3,
+            total_weight: 4,
+            signers: vec![(a, 3), (b, 1)],
+        };
+        let mut record = VoteRecord::default();
+        record.add(c, Vote::Yes);
+        assert_eq!(set.weight_of(&c), None);
+        assert_eq!(set.decide(&record), None);
+        record.add(a, Vote::Yes);
+        assert_eq!(set.decide(&record), Some(State::Passed));
+    }
+
+    #[test]
+    fn required_weight_from_passing_threshold() {
+        let threshold = |quorum: u16, passing: u16, mode| ProposalPassingThreshold {
+            quorum: Percentage(quorum * 400),
+            passing_threshold: Percentage(passing * 400),
+            mode,
+        };
+        assert_eq!(threshold(20, 40, None).required_weight(5), 2);
+        assert_eq!(threshold(20, 80, None).required_weight(5), 4);
+        assert_eq!(
+            threshold(20, 20, Some(PassingMode::SimpleMajority)).required_weight(5),
+            3
+        );
+        assert_eq!(
+            threshold(50, 67, Some(PassingMode::TwoThirds)).required_weight(6),
+            5
+        );
+        assert_eq!(
+            threshold(0, 0, Some(PassingMode::Unanimity)).required_weight(3),
+            3
+        );
+        assert_eq!(threshold(0, 0, None).required_weight(3), 1);
+    }
+
+    #[test]
+    fn passing_threshold_never_lowers_the_configured_weight() {
+        let threshold = |passing: u16| ProposalPassingThreshold {
+            quorum: Percentage(20 * 400),
+            passing_threshold: Percentage(passing * 400),
+            mode: None,
+        };
+        let mut set = SignerSet {
+            weight_required: 2,
+            total_weight: 3,
+            signers: vec![],
+        };
+        set.require_threshold(&threshold(20));
+        assert_eq!(set.weight_required, 2);
+        set.require_threshold(&threshold(100));
+        assert_eq!(set.weight_required, 3);
+    }
+
+    #[test]
+    fn weighted_tally_and_delta() {
+        let (a, b, c) = (
+            Principal::from_slice(&[1; 29]),
+            Principal::from_slice(&[2; 29]),
+            Principal::from_slice(&[3; 29]),
+        );
+        let record = VoteRecord {
+            yes_votes: vec![a, c],
+            no_votes: vec![b],
+            abstain_votes: vec![],
+        };
+        let tally = record.tally(|p| if *p == a { 3 } else { 1 });
+        assert_eq!(
+            tally,
+            VoteTally {
+                yes: 4,
+                no: 1,
+                abstain: 0
+            }
+        );
+        let reported = VoteTally {
+            yes: 3,
+            no: 1,
+            abstain: 0,
+        };
+        assert_eq!(
+            tally.delta_from(&reported),
+            VoteTally {
+                yes: 1,
+                no: 0,
+                abstain: 0
+            }
+        );
+    }
+
+    #[test]
+    fn failed_delta_is_taken_back_after_a_later_one() {
+        let tally = |yes, no| VoteTally {
+            yes,
+            no,
+            abstain: 0,
+        };
+        let (p0, t1, t2) = (tally(0, 0), tally(2, 0), tally(2, 1));
+        // Report A sends T1 - P0 and report B, started before A returns, sends T2 - T1.
+        let mut reported = p0.clone();
+        let delta_a = t1.delta_from(&reported);
+        reported = t1.clone();
+        let delta_b = t2.delta_from(&reported);
+        reported = t2.clone();
+        // A fails after B went out: only B's delta reached governance.
+        reported.take_back(&delta_a);
+        assert_eq!(reported, delta_b);
+        assert_eq!(t2.delta_from(&reported), delta_a);
+    }
+
+    #[test]
+    fn only_open_public_ballots_in_time_can_be_amended() {
+        let open = ProposalState::new(1_000, SignerSet::default());
+        assert_eq!(open.check_amendable(900, 100), Ok(()));
+        assert_eq!(open.check_amendable(901, 100), Err(ReturnError::Expired));
+
+        let failed = ProposalState {
+            state: State::Failed,
+            ..open.clone()
+        };
+        assert_eq!(failed.check_amendable(0, 100), Err(ReturnError::Expired));
+        for state in [State::Passed, State::Rejected] {
+            let decided = ProposalState {
+                state,
+                ..open.clone()
+            };
+            assert_eq!(
+                decided.check_amendable(0, 100),
+                Err(ReturnError::InputError)
+            );
+        }
+
+        let secret = ProposalState {
+            secret: SecretBallot::new(900, 100, 0),
+            ..open
+        };
+        assert_eq!(secret.check_amendable(0, 100), Err(ReturnError::InputError));
+    }
+}
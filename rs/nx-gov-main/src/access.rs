@@ -1,6 +1,6 @@
 use crate::memory::{
-    ADMIN_ROLES, EXECUTOR_ROLES, FORCE_EXECUTOR_ROLES, PROPOSER_ROLES, REVOKER_ROLES,
-    VALIDATOR_ROLES, VOTE_MANAGER_ROLES,
+    ADMIN_ROLES, APPELLANT_ROLES, EXECUTOR_ROLES, FORCE_EXECUTOR_ROLES, PROPOSER_ROLES,
+    REVIEWER_ROLES, REVOKER_ROLES, SPONSOR_ROLES, VALIDATOR_ROLES, VOTE_MANAGER_ROLES,
 };
 use crate::storage::StablePrincipal;
 use crate::types::{ReturnError, VM};
@@ -28,6 +28,12 @@ pub enum UserRole {
     ForceExecutor = 5,
     /// Validators can validate proposals, including assigning the proper proposal type.
     Validator = 6,
+    /// Sponsors can sponsor proposals, so that they go to validation.
+    Sponsor = 7,
+    /// Appellants can appeal rejected proposals without a deposit.
+    Appellant = 8,
+    /// Reviewers can attest to the content of embargoed payloads before they are revealed.
+    Reviewer = 9,
 }
 
 #[update]
@@ -58,6 +64,9 @@ pub(crate) fn add_role_internal(role: UserRole, principal: Principal) -> Result<
         UserRole::Executor => EXECUTOR_ROLES.with(op),
         UserRole::ForceExecutor => FORCE_EXECUTOR_ROLES.with(op),
         UserRole::Validator => VALIDATOR_ROLES.with(op),
+        UserRole::Sponsor => SPONSOR_ROLES.with(op),
+        UserRole::Appellant => APPELLANT_ROLES.with(op),
+        UserRole::Reviewer => REVIEWER_ROLES.with(op),
     }
 }
 
@@ -89,6 +98,9 @@ fn _remove_role(role: UserRole, principal: Principal) {
         UserRole::Executor => EXECUTOR_ROLES.with(op),
         UserRole::ForceExecutor => FORCE_EXECUTOR_ROLES.with(op),
         UserRole::Validator => VALIDATOR_ROLES.with(op),
+        UserRole::Sponsor => SPONSOR_ROLES.with(op),
+        UserRole::Appellant => APPELLANT_ROLES.with(op),
+        UserRole::Reviewer => REVIEWER_ROLES.with(op),
     };
 }
 
@@ -113,6 +125,9 @@ fn _clear_users_of_role(role: UserRole) {
         UserRole::Executor => EXECUTOR_ROLES.with(op),
         UserRole::ForceExecutor => FORCE_EXECUTOR_ROLES.with(op),
         UserRole::Validator => VALIDATOR_ROLES.with(op),
+        UserRole::Sponsor => SPONSOR_ROLES.with(op),
+        UserRole::Appellant => APPELLANT_ROLES.with(op),
+        UserRole::Reviewer => REVIEWER_ROLES.with(op),
     };
 }
 
@@ -132,6 +147,9 @@ pub fn has_role(role: UserRole, principal: Principal) -> bool {
         UserRole::Executor => EXECUTOR_ROLES.with(op),
         UserRole::ForceExecutor => FORCE_EXECUTOR_ROLES.with(op),
         UserRole::Validator => VALIDATOR_ROLES.with(op),
+        UserRole::Sponsor => SPONSOR_ROLES.with(op),
+        UserRole::Appellant => APPELLANT_ROLES.with(op),
+        UserRole::Reviewer => REVIEWER_ROLES.with(op),
     }
 }
 
@@ -154,6 +172,9 @@ pub fn users_of_role(role: UserRole) -> Vec<Principal> {
         UserRole::Executor => EXECUTOR_ROLES.with(op),
         UserRole::ForceExecutor => FORCE_EXECUTOR_ROLES.with(op),
         UserRole::Validator => VALIDATOR_ROLES.with(op),
+        UserRole::Sponsor => SPONSOR_ROLES.with(op),
+        UserRole::Appellant => APPELLANT_ROLES.with(op),
+        UserRole::Reviewer => REVIEWER_ROLES.with(op),
     }
 }
 
@@ -0,0 +1,83 @@
+use crate::deposit::DepositConfig;
+use crate::proposal::{ProposalState, Tally};
+use crate::types::*;
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+/// Appeals of proposals that ended in `Rejected` or `QuorumNotMet`.
+///
+/// An appeal reopens the proposal once, for a second vote under stricter thresholds.
+/// Members of the Appellant role can appeal for free. If `deposit` is set, the proposer can appeal by paying it.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AppealConfig {
+    /// Time after the end of the voting period during which a proposal can be appealed, in nano-seconds.
+    pub appeal_window: TimeNs,
+    /// Voting period of the second round, in nano-seconds. Never shorter than `Config.min_voting_period`.
+    pub voting_period: TimeNs,
+    /// Floor of the second round thresholds, on top of the thresholds of the first round.
+    pub passing_threshold: ProposalPassingThreshold,
+    /// Appeal deposit paid by proposers. Refunded unless the appeal is rejected by a wide enough margin.
+    /// If `None`, only appellants can appeal.
+    pub deposit: Option<DepositConfig>,
+}
+
+impl AppealConfig {
+    /// Whether the appeal window of a proposal whose voting period ends at `voting_end_time` is open.
+    pub fn is_window_open(&self, voting_end_time: Option<TimeNs>, now: TimeNs) -> bool {
+        voting_end_time.is_some_and(|end| now < end.saturating_add(self.appeal_window))
+    }
+}
+
+/// Votes of a finished voting round.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VotingRound {
+    /// The state the round ended in.
+    pub outcome: ProposalState,
+    pub voting_end_time: Option<TimeNs>,
+    pub passing_threshold: Option<ProposalPassingThreshold>,
+    pub votes_yes: VotingPower,
+    pub votes_no: VotingPower,
+    pub votes_abstain: VotingPower,
+    pub total_voting_power: VotingPower,
+}
+
+impl VotingRound {
+    /// Whether no/(yes+no) of the round reaches `threshold`. Never reached if the tally is invalid.
+    pub fn no_share_reaches(&self, threshold: &Percentage<PercentagePrecision>) -> bool {
+        let tally = Tally {
+            yes: self.votes_yes,
+            no: self.votes_no,
+            abstain: self.votes_abstain,
+            total: self.total_voting_power,
+        };
+        tally.no_share_reaches(threshold)
+    }
+}
+
+/// The appeal of a proposal. The vote fields of the proposal hold the second round.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Appeal {
+    pub appellant: Principal,
+    pub appealed_at: TimeNs,
+    pub first_round: VotingRound,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appeal_window() {
+        let config = AppealConfig {
+            appeal_window: 100,
+            voting_period: 1_000,
+            passing_threshold: ProposalPassingThreshold::default(),
+            deposit: None,
+        };
+        assert!(config.is_window_open(Some(1_000), 500));
+        assert!(config.is_window_open(Some(1_000), 1_099));
+        assert!(!config.is_window_open(Some(1_000), 1_100));
+        assert!(!config.is_window_open(None, 0));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::execution::ProposalPayload;
+use crate::proposal::ratio_reaches;
+use crate::types::*;
+use crate::validate::Validate;
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of choices of a multi-option proposal, including the submitted payload.
+pub const MAX_CHOICES: usize = 8;
+
+/// Candidate payloads of a multi-option proposal, given at submission time.
+///
+/// The submitted payload is the first choice, and the alternatives follow in order.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ChoiceOptions {
+    pub alternatives: Vec<ProposalPayload>,
+    pub selection: ChoiceSelection,
+}
+
+impl Validate for ChoiceOptions {
+    fn is_valid(&self) -> bool {
+        !self.alternatives.is_empty()
+            && self.alternatives.len() < MAX_CHOICES
+            && self.alternatives.iter().all(|p| p.is_valid())
+            && self.selection.is_valid()
+    }
+}
+
+/// How the winning choice is selected once the proposal is accepted.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ChoiceSelection {
+    /// The choice with the most votes wins. A tie for the most votes selects no winner.
+    Plurality,
+    /// The choice with the most votes wins, if its share of the votes for all choices reaches the threshold.
+    Threshold(Percentage<PercentagePrecision>),
+}
+
+impl Validate for ChoiceSelection {
+    fn is_valid(&self) -> bool {
+        match self {
+            ChoiceSelection::Plurality => true,
+            ChoiceSelection::Threshold(threshold) => threshold.is_valid(),
+        }
+    }
+}
+
+/// The choices of a multi-option proposal, with the tally of each.
+///
+/// Votes for any choice count as "yes" towards the quorum and passing threshold of the proposal,
+/// and "no" votes reject all choices. Only the winning choice is executed.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Choices {
+    /// Payload ids of the choices, in choice order.
+    pub payload_ids: Vec<Index>,
+    /// The voting power of the votes for each choice, in choice order.
+    pub tallies: Vec<VotingPower>,
+    pub selection: ChoiceSelection,
+    /// Position of the winning choice. Set when the proposal is accepted.
+    pub winner: Option<u8>,
+}
+
+impl Choices {
+    pub fn new(payload_ids: Vec<Index>, selection: ChoiceSelection) -> Self {
+        Self {
+            tallies: vec![0; payload_ids.len()],
+            payload_ids,
+            selection,
+            winner: None,
+        }
+    }
+
+    /// Adds incremental changes to the tallies, in choice order.
+    /// Fails with `InputError` if the number of changes does not match the number of choices,
+    /// or with `ArithmeticError` if a tally becomes negative or overflows. The tallies are unchanged on failure.
+    pub fn add_votes(&mut self, voting_power: &[VotingPower]) -> Result<(), ReturnError> {
+        if voting_power.len() != self.tallies.len() {
+            return Err(ReturnError::InputError);
+        }
+        let tallies = self
+            .tallies
+            .iter()
+            .zip(voting_power)
+            .map(|(tally, change)| tally.checked_add(*change).filter(|t| *t >= 0))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ReturnError::ArithmeticError)?;
+        self.tallies = tallies;
+        Ok(())
+    }
+
+    /// The voting power of the votes for all choices.
+    pub fn total(&self) -> Result<VotingPower, ReturnError> {
+        self.tallies
+            .iter()
+            .try_fold(0 as VotingPower, |sum, tally| sum.checked_add(*tally))
+            .ok_or(ReturnError::ArithmeticError)
+    }
+
+    /// Selects the winning choice according to `selection`. Returns `None` if no choice wins.
+    pub fn select_winner(&self) -> Option<u8> {
+        let (position, most) = self
+            .tallies
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, tally)| **tally)?;
+        if *most <= 0 || self.tallies.iter().filter(|t| *t == most).count() > 1 {
+            return None;
+        }
+        let wins = match &self.selection {
+            ChoiceSelection::Plurality => true,
+            ChoiceSelection::Threshold(threshold) => self
+                .total()
+                .is_ok_and(|total| ratio_reaches(*most as u128, total as u128, threshold)),
+        };
+        wins.then_some(position as u8)
+    }
+
+    /// Payload id of the winning choice, if selected.
+    pub fn winning_payload_id(&self) -> Option<Index> {
+        self.winner
+            .and_then(|w| self.payload_ids.get(w as usize))
+            .copied()
+    }
+
+    /// Clears the tallies and the winner, for a new voting round.
+    pub fn reset(&mut self) {
+        self.tallies = vec![0; self.payload_ids.len()];
+        self.winner = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choices(selection: ChoiceSelection, tallies: Vec<VotingPower>) -> Choices {
+        let mut c = Choices::new((10..10 + tallies.len() as Index).collect(), selection);
+        c.add_votes(&tallies).unwrap();
+        c
+    }
+
+    #[test]
+    fn plurality_selects_most_votes() {
+        let c = choices(ChoiceSelection::Plurality, vec![3, 5, 4]);
+        assert_eq!(c.select_winner(), Some(1));
+        // A tie for the most votes has no winner.
+        let c = choices(ChoiceSelection::Plurality, vec![5, 5, 4]);
+        assert_eq!(c.select_winner(), None);
+        let c = choices(ChoiceSelection::Plurality, vec![0, 0]);
+        assert_eq!(c.select_winner(), None);
+    }
+
+    #[test]
+    fn threshold_requires_share_of_choice_votes() {
+        let half = ChoiceSelection::Threshold(Percentage::<PercentagePrecision>::from_percent(50));
+        assert_eq!(
+            choices(half.clone(), vec![3, 5, 2]).select_winner(),
+            Some(1)
+        );
+        assert_eq!(choices(half, vec![3, 4, 2]).select_winner(), None);
+    }
+
+    #[test]
+    fn winning_payload_id_follows_winner() {
+        let mut c = choices(ChoiceSelection::Plurality, vec![1, 2]);
+        assert_eq!(c.winning_payload_id(), None);
+        c.winner = c.select_winner();
+        assert_eq!(c.winning_payload_id(), Some(11));
+        c.reset();
+        assert_eq!(c.tallies, vec![0, 0]);
+        assert_eq!(c.winning_payload_id(), None);
+    }
+
+    #[test]
+    fn invalid_votes_leave_tallies_unchanged() {
+        let mut c = choices(ChoiceSelection::Plurality, vec![1, 2]);
+        assert_eq!(c.add_votes(&[1]), Err(ReturnError::InputError));
+        assert_eq!(c.add_votes(&[1, -3]), Err(ReturnError::ArithmeticError));
+        assert_eq!(c.add_votes(&[-1, 1]), Ok(()));
+        assert_eq!(c.tallies, vec![0, 3]);
+        assert_eq!(c.total(), Ok(3));
+    }
+}
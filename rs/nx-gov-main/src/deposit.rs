@@ -0,0 +1,248 @@
+use crate::proposal::*;
+use crate::types::*;
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+pub type Subaccount = [u8; 32];
+
+/// Subaccount of the governance canister that holds the submission deposits.
+pub const DEPOSIT_SUBACCOUNT: Subaccount = *b"nx-gov-proposal-deposit-escrow\0\0";
+
+/// ICRC-1 account.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Account {
+    pub owner: Principal,
+    pub subaccount: Option<Subaccount>,
+}
+
+impl From<Principal> for Account {
+    fn from(owner: Principal) -> Self {
+        Self {
+            owner,
+            subaccount: None,
+        }
+    }
+}
+
+/// Submission deposit, paid in ICRC-1 tokens.
+///
+/// The proposer must approve the governance canister (ICRC-2) to spend `amount` plus the ledger fee before submitting.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DepositConfig {
+    /// The ICRC-1 ledger of the deposit token. The ledger must support ICRC-2.
+    pub ledger: Principal,
+    /// The deposit amount, excluding ledger fees.
+    pub amount: u128,
+    /// The deposit of a rejected proposal is slashed if the share of "no" votes in `yes + no` reaches this threshold.
+    pub reject_slash_threshold: Percentage<PercentagePrecision>,
+    /// Receiver of slashed deposits. If `None`, slashed deposits go to the default account of the governance canister.
+    pub slash_destination: Option<Account>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum DepositStatus {
+    /// The deposit is held in the escrow subaccount. Next states: Settling.
+    Held,
+    /// A refund or slash transfer is in flight. Next states: Held (transfer failed), Refunded, Slashed.
+    Settling,
+    /// The deposit was returned to the depositor, minus the ledger fee. END.
+    Refunded { block_index: Option<u128> },
+    /// The deposit was sent to the slash destination, minus the ledger fee. END.
+    Slashed { block_index: Option<u128> },
+}
+
+/// What a deposit was paid for. A proposal can have one deposit of each kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepositKind {
+    Submission,
+    Appeal,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DepositOutcome {
+    Refund,
+    Slash,
+}
+
+/// The submission deposit of a proposal.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProposalDeposit {
+    /// The ledger the deposit was paid on.
+    pub ledger: Principal,
+    /// The account the deposit was paid from, and is refunded to.
+    pub depositor: Account,
+    /// The amount held in escrow.
+    pub amount: u128,
+    /// Slashing parameters at submission time. Later config changes do not affect existing deposits.
+    pub reject_slash_threshold: Percentage<PercentagePrecision>,
+    pub slash_destination: Account,
+    /// Block index of the transfer into escrow.
+    pub block_index: u128,
+    pub status: DepositStatus,
+    /// Creation time of the transfer out of escrow, set by the first settlement attempt.
+    /// Retries reuse it, so the ledger rejects the retry of a transfer whose reply was lost as a duplicate.
+    #[serde(default)]
+    pub settled_at: Option<TimeNs>,
+}
+
+impl ProposalDeposit {
+    /// The deposit is slashed when validation fails, or when the proposal is rejected by a wide enough margin.
+    /// A rejected appeal is judged by the margin of the first round. The deposit is refunded in all other final states.
+    /// Returns `None` while the proposal has not reached a final state, or while it can still be appealed.
+    pub fn outcome(&self, proposal: &Proposal, appealable: bool) -> Option<DepositOutcome> {
+        match proposal.state {
+            ProposalState::Rejected | ProposalState::QuorumNotMet if appealable => None,
+            ProposalState::ValidationFailed => Some(DepositOutcome::Slash),
+            ProposalState::Rejected if proposal.no_share_reaches(&self.reject_slash_threshold) => {
+                Some(DepositOutcome::Slash)
+            }
+            ProposalState::AppealRejected
+                if proposal.appeal.as_ref().is_some_and(|appeal| {
+                    appeal
+                        .first_round
+                        .no_share_reaches(&self.reject_slash_threshold)
+                }) =>
+            {
+                Some(DepositOutcome::Slash)
+            }
+            ref state if state.is_final() => Some(DepositOutcome::Refund),
+            _ => None,
+        }
+    }
+
+    pub fn outcome_for(
+        &self,
+        kind: DepositKind,
+        proposal: &Proposal,
+        appealable: bool,
+    ) -> Option<DepositOutcome> {
+        match kind {
+            DepositKind::Submission => self.outcome(proposal, appealable),
+            DepositKind::Appeal => self.appeal_outcome(proposal),
+        }
+    }
+
+    /// The appeal deposit is slashed when the appeal is rejected by a wide enough margin,
+    /// and refunded once the appeal round ended in any other way.
+    /// Returns `None` while the appeal round is in progress.
+    pub fn appeal_outcome(&self, proposal: &Proposal) -> Option<DepositOutcome> {
+        match proposal.state {
+            ProposalState::Appealed => None,
+            ProposalState::AppealRejected
+                if proposal.no_share_reaches(&self.reject_slash_threshold) =>
+            {
+                Some(DepositOutcome::Slash)
+            }
+            _ => Some(DepositOutcome::Refund),
+        }
+    }
+
+    /// Memo of the transfer out of escrow. It tells apart the deposits of a batch, which share the escrow
+    /// transfer, and the submission and appeal deposits of a proposal.
+    /// `proposal_id` is `None` for a deposit returned before any proposal referenced it.
+    pub fn settlement_memo(
+        &self,
+        kind: DepositKind,
+        proposal_id: Option<Index>,
+        outcome: &DepositOutcome,
+    ) -> Vec<u8> {
+        let kind = match kind {
+            DepositKind::Submission => b'S',
+            DepositKind::Appeal => b'A',
+        };
+        let outcome = match outcome {
+            DepositOutcome::Refund => b'R',
+            DepositOutcome::Slash => b'S',
+        };
+        let mut memo = vec![kind, outcome];
+        memo.extend_from_slice(&self.block_index.to_be_bytes());
+        if let Some(proposal_id) = proposal_id {
+            memo.extend_from_slice(&proposal_id.to_be_bytes());
+        }
+        memo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proposal::tests::executing_proposal;
+
+    #[test]
+    fn appealable_rejection_defers_the_submission_deposit() {
+        let deposit = ProposalDeposit {
+            ledger: Principal::anonymous(),
+            depositor: Account::from(Principal::anonymous()),
+            amount: 100,
+            reject_slash_threshold: Percentage::<PercentagePrecision>::from_percent(67),
+            slash_destination: Account::from(Principal::anonymous()),
+            block_index: 0,
+            status: DepositStatus::Held,
+            settled_at: None,
+        };
+        let mut proposal = executing_proposal(None, u64::MAX);
+        proposal.state = ProposalState::Rejected;
+        proposal.votes_yes = 1;
+        proposal.votes_no = 3;
+        proposal.total_voting_power = 10;
+        assert_eq!(deposit.outcome(&proposal, true), None);
+        assert_eq!(
+            deposit.outcome(&proposal, false),
+            Some(DepositOutcome::Slash)
+        );
+
+        proposal
+            .reopen_for_appeal(Principal::anonymous(), 600, 2_000, &Default::default())
+            .unwrap();
+        assert_eq!(deposit.outcome(&proposal, false), None);
+        // A rejected appeal does not avoid the slash of the first round.
+        proposal.state = ProposalState::AppealRejected;
+        assert_eq!(
+            deposit.outcome(&proposal, false),
+            Some(DepositOutcome::Slash)
+        );
+        proposal.state = ProposalState::Accepted;
+        assert_eq!(deposit.outcome(&proposal, false), None);
+        proposal.state = ProposalState::Succeeded;
+        assert_eq!(
+            deposit.outcome(&proposal, false),
+            Some(DepositOutcome::Refund)
+        );
+    }
+
+    #[test]
+    fn settlement_memos_tell_deposits_apart() {
+        let deposit = ProposalDeposit {
+            ledger: Principal::anonymous(),
+            depositor: Account::from(Principal::anonymous()),
+            amount: 100,
+            reject_slash_threshold: Percentage::<PercentagePrecision>::from_percent(67),
+            slash_destination: Account::from(Principal::anonymous()),
+            block_index: 7,
+            status: DepositStatus::Held,
+            settled_at: None,
+        };
+        let refund = DepositOutcome::Refund;
+        let memo = deposit.settlement_memo(DepositKind::Submission, Some(1), &refund);
+        assert_eq!(
+            memo,
+            deposit.settlement_memo(DepositKind::Submission, Some(1), &refund)
+        );
+        // Deposits of a batch share the escrow transfer.
+        assert_ne!(
+            memo,
+            deposit.settlement_memo(DepositKind::Submission, Some(2), &refund)
+        );
+        assert_ne!(
+            memo,
+            deposit.settlement_memo(DepositKind::Appeal, Some(1), &refund)
+        );
+        assert_ne!(
+            memo,
+            deposit.settlement_memo(DepositKind::Submission, Some(1), &DepositOutcome::Slash)
+        );
+        // ICRC-1 ledgers accept memos of up to 32 bytes by default.
+        assert!(memo.len() <= 32);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::types::*;
+use crate::validate::Validate;
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Commitment to an embargoed payload, for example a security fix that must not be public during voting.
+///
+/// Only the hash of the payload and a human summary are public until the payload is executed.
+/// The validator receives the payload privately, like reviewers, and validates it against the hash during voting.
+/// Once the proposal is accepted, `reveal_and_execute` reveals the payload and executes it in the same call.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Embargo {
+    /// SHA-256 hash of the candid encoded payload, as it will be passed to `reveal_and_execute`. See `hash_payload`.
+    pub payload_hash: RawBytes,
+    /// Human summary of the payload, which is public during voting.
+    pub summary: String,
+}
+
+impl Validate for Embargo {
+    fn is_valid(&self) -> bool {
+        self.payload_hash.len() == 32 && !self.summary.is_empty()
+    }
+}
+
+/// The embargo of a proposal, with the attestations of reviewers.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct EmbargoedPayload {
+    pub embargo: Embargo,
+    /// Reviewers who attested to the content of the payload, in the order they attested.
+    pub attestations: Vec<Principal>,
+    /// Time when the payload was revealed, as its execution started.
+    pub revealed_at: Option<TimeNs>,
+    /// Whether the validator accepted the payload, before it was revealed.
+    pub validated: Option<bool>,
+}
+
+/// SHA-256 hash of an encoded payload.
+/// The bytes are hashed as they are, since candid encodings of the same payload are not unique.
+pub fn hash_payload(bytes: &[u8]) -> RawBytes {
+    Sha256::digest(bytes).to_vec()
+}
+
+impl EmbargoedPayload {
+    pub fn new(embargo: Embargo) -> Self {
+        Self {
+            embargo,
+            attestations: vec![],
+            revealed_at: None,
+            validated: None,
+        }
+    }
+
+    pub fn is_revealed(&self) -> bool {
+        self.revealed_at.is_some()
+    }
+
+    /// Records the attestation of a reviewer. Attestations are only accepted before the payload is revealed.
+    pub fn attest(&mut self, reviewer: Principal) -> Result<(), ReturnError> {
+        if self.is_revealed() {
+            return Err(ReturnError::IncorrectProposalState);
+        }
+        if self.attestations.contains(&reviewer) {
+            return Err(ReturnError::AlreadyExists);
+        }
+        self.attestations.push(reviewer);
+        Ok(())
+    }
+
+    /// Checks the encoded payload against the hash, and marks the payload as revealed.
+    /// Only a payload accepted by the validator can be revealed.
+    pub fn reveal(&mut self, bytes: &[u8], now: TimeNs) -> Result<(), ReturnError> {
+        if self.is_revealed() {
+            return Err(ReturnError::AlreadyExists);
+        }
+        if self.validated != Some(true) {
+            return Err(ReturnError::PayloadNotValidated);
+        }
+        if hash_payload(bytes) != self.embargo.payload_hash {
+            return Err(ReturnError::PayloadHashMismatch);
+        }
+        self.revealed_at = Some(now);
+        Ok(())
+    }
+
+    /// Records the validation of the payload, before it is revealed. The payload is validated once.
+    pub fn validate(&mut self, validated: bool) -> Result<(), ReturnError> {
+        if self.is_revealed() {
+            return Err(ReturnError::IncorrectProposalState);
+        }
+        if self.validated.is_some() {
+            return Err(ReturnError::AlreadyExists);
+        }
+        self.validated = Some(validated);
+        Ok(())
+    }
+
+    /// Fails unless the payload is revealed and validated, so that it can be executed again, e.g. by a later run.
+    pub fn check_released(&self) -> Result<(), ReturnError> {
+        match (self.is_revealed(), self.validated) {
+            (false, _) => Err(ReturnError::PayloadNotRevealed),
+            (true, Some(true)) => Ok(()),
+            (true, _) => Err(ReturnError::PayloadNotValidated),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::ProposalPayload;
+
+    fn payload(depends_on: Vec<Index>) -> ProposalPayload {
+        ProposalPayload {
+            depends_on,
+            depends_on_any: None,
+            conflicts_with: None,
+            batch_depends_on: None,
+            messages: vec![],
+        }
+    }
+
+    fn encode(payload: &ProposalPayload) -> RawBytes {
+        candid::encode_one(payload).unwrap()
+    }
+
+    fn embargoed(payload: &ProposalPayload) -> EmbargoedPayload {
+        EmbargoedPayload::new(Embargo {
+            payload_hash: hash_payload(&encode(payload)),
+            summary: "Security fix".to_string(),
+        })
+    }
+
+    #[test]
+    fn reveal_checks_hash() {
+        let mut e = embargoed(&payload(vec![1]));
+        assert!(e.embargo.is_valid());
+        e.validate(true).unwrap();
+        assert_eq!(
+            e.reveal(&encode(&payload(vec![2])), 10),
+            Err(ReturnError::PayloadHashMismatch)
+        );
+        assert!(!e.is_revealed());
+        assert_eq!(e.reveal(&encode(&payload(vec![1])), 10), Ok(()));
+        assert_eq!(e.revealed_at, Some(10));
+        assert_eq!(
+            e.reveal(&encode(&payload(vec![1])), 11),
+            Err(ReturnError::AlreadyExists)
+        );
+    }
+
+    #[test]
+    fn revealed_payload_is_released_once_validated() {
+        let mut e = embargoed(&payload(vec![]));
+        assert_eq!(
+            e.reveal(&encode(&payload(vec![])), 10),
+            Err(ReturnError::PayloadNotValidated)
+        );
+        e.validate(true).unwrap();
+        assert_eq!(e.validate(false), Err(ReturnError::AlreadyExists));
+        assert_eq!(e.check_released(), Err(ReturnError::PayloadNotRevealed));
+        e.reveal(&encode(&payload(vec![])), 10).unwrap();
+        assert_eq!(e.check_released(), Ok(()));
+
+        let mut rejected = embargoed(&payload(vec![]));
+        rejected.validate(false).unwrap();
+        assert_eq!(
+            rejected.reveal(&encode(&payload(vec![])), 10),
+            Err(ReturnError::PayloadNotValidated)
+        );
+        assert_eq!(
+            rejected.check_released(),
+            Err(ReturnError::PayloadNotRevealed)
+        );
+    }
+
+    #[test]
+    fn attestations_until_reveal() {
+        let reviewer = Principal::from_slice(&[1; 29]);
+        let mut e = embargoed(&payload(vec![]));
+        assert_eq!(e.attest(reviewer), Ok(()));
+        assert_eq!(e.attest(reviewer), Err(ReturnError::AlreadyExists));
+        e.validate(true).unwrap();
+        e.reveal(&encode(&payload(vec![])), 10).unwrap();
+        assert_eq!(
+            e.attest(Principal::from_slice(&[2; 29])),
+            Err(ReturnError::IncorrectProposalState)
+        );
+        assert_eq!(e.attestations, vec![reviewer]);
+    }
+}
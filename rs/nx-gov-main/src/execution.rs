@@ -1,7 +1,9 @@
+use crate::proposal::ProposalState;
 use crate::types::*;
 use crate::validate::Validate;
 
-use candid::{CandidType, Principal};
+use candid::types::value::IDLValue;
+use candid::{CandidType, IDLArgs, Principal};
 use serde::{Deserialize, Serialize};
 
 /// The execution may depend on a pre-validation step by an external canister.
@@ -89,6 +91,20 @@ pub struct ProposalPayload {
     /// If the vector is empty, it means this proposal does not depend on any other proposal.
     /// ForceExecute cannot bypass this dependency.
     pub depends_on: Vec<Index>,
+    /// Ids of alternative proposals that this proposal depends on.
+    /// At least one of them must be executed successfully before this proposal can be executed.
+    /// All the referenced proposal ids must be less than this proposal id.
+    /// If `None` or empty, this relation does not apply. ForceExecute cannot bypass this dependency.
+    pub depends_on_any: Option<Vec<Index>>,
+    /// Ids of competing proposals.
+    /// Once any of them is executed successfully, this proposal can no longer be executed, and fails on the next attempt.
+    /// The ids can refer to proposals submitted later. Ids without a proposal are ignored.
+    pub conflicts_with: Option<Vec<Index>>,
+    /// Dependencies on earlier items of the same `submit_batch` call, by relative position.
+    /// `1` refers to the item right before this one, `2` to the one before that, and so on.
+    /// Resolved into `depends_on` when the batch is written, so stored payloads never have it set.
+    /// Must be `None` or empty for `submit`.
+    pub batch_depends_on: Option<Vec<u64>>,
     /// Messages to be executed.
     pub messages: Vec<CanisterMessage>,
 }
@@ -102,8 +118,93 @@ impl Validate for ProposalPayload {
 }
 
 impl ProposalPayload {
+    /// The highest proposal id that must exist before this proposal is submitted.
+    /// Conflicts are not included, since they can refer to later proposals.
     pub fn max_dependency_index(&self) -> Option<Index> {
-        self.depends_on.iter().max().copied()
+        self.depends_on
+            .iter()
+            .chain(self.depends_on_any.as_deref().unwrap_or_default())
+            .max()
+            .copied()
+    }
+
+    /// Checks the execution dependencies of this payload, given the state of each referenced proposal.
+    ///
+    /// Returns `DependentProposalNotSucceeded` or `ConflictingProposalSucceeded` if the dependencies can never be met,
+    /// and `DependentProposalNotReady` if they may still be met later.
+    pub fn check_dependencies(
+        &self,
+        state_of: impl Fn(Index) -> Result<ProposalState, ReturnError>,
+    ) -> Result<(), ReturnError> {
+        for &id in self.conflicts_with.as_deref().unwrap_or_default() {
+            if state_of(id).is_ok_and(|state| state.has_succeeded()) {
+                return Err(ReturnError::ConflictingProposalSucceeded);
+            }
+        }
+
+        let mut ready = true;
+        for &id in self.depends_on.iter() {
+            let state = state_of(id)?;
+            if state.is_final() && !state.has_succeeded() {
+                return Err(ReturnError::DependentProposalNotSucceeded);
+            }
+            ready &= state.has_succeeded();
+        }
+
+        let any_of = self.depends_on_any.as_deref().unwrap_or_default();
+        if !any_of.is_empty() {
+            let states = any_of
+                .iter()
+                .map(|&id| state_of(id))
+                .collect::<Result<Vec<_>, _>>()?;
+            if !states.iter().any(|state| state.has_succeeded()) {
+                if states.iter().all(|state| state.is_final()) {
+                    return Err(ReturnError::DependentProposalNotSucceeded);
+                }
+                ready = false;
+            }
+        }
+
+        match ready {
+            true => Ok(()),
+            false => Err(ReturnError::DependentProposalNotReady),
+        }
+    }
+
+    pub fn has_batch_dependencies(&self) -> bool {
+        self.batch_depends_on
+            .as_ref()
+            .is_some_and(|d| !d.is_empty())
+    }
+
+    /// Moves the batch dependencies of the item at `position` in a batch into `depends_on`,
+    /// given the ID of the first item of the batch.
+    /// Fails if a dependency does not refer to an earlier item of the batch.
+    pub fn resolve_batch_dependencies(
+        &mut self,
+        position: u64,
+        first_id: Index,
+    ) -> Result<(), ReturnError> {
+        for offset in self.batch_depends_on.take().unwrap_or_default() {
+            if offset == 0 || offset > position {
+                return Err(ReturnError::InputError);
+            }
+            let dependency_id = first_id
+                .checked_add(position - offset)
+                .ok_or(ReturnError::ArithmeticError)?;
+            self.depends_on.push(dependency_id);
+        }
+        Ok(())
+    }
+
+    /// Moves the dependencies resolved for a batch of `len` items starting at ID `from` to the same batch starting at `to`.
+    /// Dependencies outside of the batch are kept.
+    pub fn rebase_batch_dependencies(&mut self, from: Index, to: Index, len: u64) {
+        for id in self.depends_on.iter_mut() {
+            if (from..from.saturating_add(len)).contains(id) {
+                *id = *id - from + to;
+            }
+        }
     }
 }
 
@@ -119,3 +220,274 @@ pub struct ProposalExec {
     /// The execution result of the proposal.
     pub execution_result: Vec<ExecResult>,
 }
+
+/// Comparison between the reply of an activation condition call and its expected value.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ConditionPredicate {
+    /// The reply equals the expected value. Works for any candid values.
+    Equal,
+    /// The reply is greater than the expected value. Only for single integer values.
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+/// A condition that must hold before an accepted proposal can be executed, on top of its activation time.
+///
+/// The condition is evaluated by calling `method` on `canister_id` with `arg`, usually a query endpoint,
+/// and comparing the reply with `expected` using `predicate`. Both are compared as candid values,
+/// so encoding details do not matter.
+/// For example `ready : () -> (bool) query` with `expected = (true)`, or `icrc1_total_supply` with `Greater` than `(N)`.
+///
+/// The condition is evaluated on every execution attempt, including each run of a standing order,
+/// and every change of its outcome is recorded. The canister must be allowed by `Config.condition_canisters`.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ActivationCondition {
+    /// Canister id of the condition endpoint.
+    pub canister_id: Principal,
+    /// Method name of the condition endpoint.
+    pub method: String,
+    /// Candid encoded arguments of the call.
+    pub arg: RawBytes,
+    pub predicate: ConditionPredicate,
+    /// Candid encoded expected value.
+    pub expected: RawBytes,
+}
+
+impl Validate for ActivationCondition {
+    fn is_valid(&self) -> bool {
+        !self.method.is_empty() && IDLArgs::from_bytes(&self.expected).is_ok()
+    }
+}
+
+impl ActivationCondition {
+    /// Whether the reply of the condition call satisfies the condition.
+    /// Returns an error if the reply cannot be compared with the expected value.
+    pub fn is_met_by(&self, reply: &[u8]) -> Result<bool, String> {
+        let expected = IDLArgs::from_bytes(&self.expected).map_err(|e| e.to_string())?;
+        let actual = IDLArgs::from_bytes(reply).map_err(|e| e.to_string())?;
+        if self.predicate == ConditionPredicate::Equal {
+            return Ok(actual == expected);
+        }
+        let (actual, expected) = match (actual.args.as_slice(), expected.args.as_slice()) {
+            ([actual], [expected]) => (to_int(actual), to_int(expected)),
+            _ => (None, None),
+        };
+        let ordering = match (actual, expected) {
+            (Some(actual), Some(expected)) => actual.cmp(&expected),
+            _ => {
+                return Err(String::from(
+                    "ordering predicates require single integer values",
+                ))
+            }
+        };
+        Ok(match self.predicate {
+            ConditionPredicate::Equal => ordering.is_eq(),
+            ConditionPredicate::Greater => ordering.is_gt(),
+            ConditionPredicate::GreaterOrEqual => ordering.is_ge(),
+            ConditionPredicate::Less => ordering.is_lt(),
+            ConditionPredicate::LessOrEqual => ordering.is_le(),
+        })
+    }
+}
+
+fn to_int(value: &IDLValue) -> Option<candid::Int> {
+    match value {
+        IDLValue::Nat(n) => Some(candid::Int::from(n.clone())),
+        IDLValue::Int(i) => Some(i.clone()),
+        IDLValue::Nat8(n) => Some(candid::Int::from(*n)),
+        IDLValue::Nat16(n) => Some(candid::Int::from(*n)),
+        IDLValue::Nat32(n) => Some(candid::Int::from(*n)),
+        IDLValue::Nat64(n) => Some(candid::Int::from(*n)),
+        IDLValue::Int8(i) => Some(candid::Int::from(*i)),
+        IDLValue::Int16(i) => Some(candid::Int::from(*i)),
+        IDLValue::Int32(i) => Some(candid::Int::from(*i)),
+        IDLValue::Int64(i) => Some(candid::Int::from(*i)),
+        _ => None,
+    }
+}
+
+/// Outcome of one evaluation of an activation condition.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ConditionEvaluation {
+    /// Time of the first of the consecutive evaluations with this outcome.
+    pub evaluated_at: TimeNs,
+    /// Whether the condition held, or why it could not be evaluated.
+    pub result: Result<bool, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proposal::ExecutionStep;
+    use candid::{encode_one, Nat};
+
+    fn condition(predicate: ConditionPredicate, expected: RawBytes) -> ActivationCondition {
+        ActivationCondition {
+            canister_id: Principal::anonymous(),
+            method: String::from("ready"),
+            arg: encode_one(()).unwrap(),
+            predicate,
+            expected,
+        }
+    }
+
+    #[test]
+    fn equal_compares_candid_values() {
+        let ready = condition(ConditionPredicate::Equal, encode_one(true).unwrap());
+        assert!(ready.is_valid());
+        assert_eq!(ready.is_met_by(&encode_one(true).unwrap()), Ok(true));
+        assert_eq!(ready.is_met_by(&encode_one(false).unwrap()), Ok(false));
+        assert_eq!(ready.is_met_by(&encode_one("true").unwrap()), Ok(false));
+        assert!(ready.is_met_by(&[0, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn ordering_predicates_compare_integers() {
+        let supply = Nat::from(1_000_000u64);
+        let greater = condition(ConditionPredicate::Greater, encode_one(supply).unwrap());
+        assert_eq!(
+            greater.is_met_by(&encode_one(Nat::from(1_000_001u64)).unwrap()),
+            Ok(true)
+        );
+        assert_eq!(
+            greater.is_met_by(&encode_one(Nat::from(1_000_000u64)).unwrap()),
+            Ok(false)
+        );
+        // Integer types may differ between the reply and the expected value.
+        assert_eq!(
+            greater.is_met_by(&encode_one(2_000_000u64).unwrap()),
+            Ok(true)
+        );
+
+        let at_most = condition(ConditionPredicate::LessOrEqual, encode_one(-5i32).unwrap());
+        assert_eq!(at_most.is_met_by(&encode_one(-5i64).unwrap()), Ok(true));
+        assert_eq!(at_most.is_met_by(&encode_one(-4i64).unwrap()), Ok(false));
+    }
+
+    #[test]
+    fn ordering_predicates_reject_non_integers() {
+        let greater = condition(ConditionPredicate::Greater, encode_one(1u64).unwrap());
+        assert!(greater.is_met_by(&encode_one(true).unwrap()).is_err());
+        assert!(greater
+            .is_met_by(&candid::encode_args((1u64, 2u64)).unwrap())
+            .is_err());
+    }
+
+    fn payload(depends_on: Vec<Index>, batch_depends_on: Vec<u64>) -> ProposalPayload {
+        ProposalPayload {
+            depends_on,
+            depends_on_any: None,
+            conflicts_with: None,
+            batch_depends_on: Some(batch_depends_on),
+            messages: vec![],
+        }
+    }
+
+    /// Proposal `i` is in state `states[i]`.
+    fn check(payload: &ProposalPayload, states: &[ProposalState]) -> Result<(), ReturnError> {
+        payload.check_dependencies(|id| {
+            states
+                .get(id as usize)
+                .cloned()
+                .ok_or(ReturnError::InvalidIndex)
+        })
+    }
+
+    #[test]
+    fn all_of_dependencies() {
+        use ProposalState::*;
+        let p = payload(vec![0, 1], vec![]);
+        assert_eq!(check(&p, &[Succeeded, ForceExecutionSucceeded]), Ok(()));
+        assert_eq!(
+            check(&p, &[Succeeded, Accepted]),
+            Err(ReturnError::DependentProposalNotReady)
+        );
+        assert_eq!(
+            check(&p, &[Accepted, Rejected]),
+            Err(ReturnError::DependentProposalNotSucceeded)
+        );
+        assert_eq!(check(&p, &[Succeeded]), Err(ReturnError::InvalidIndex));
+    }
+
+    #[test]
+    fn any_of_dependencies() {
+        use ProposalState::*;
+        let mut p = payload(vec![], vec![]);
+        p.depends_on_any = Some(vec![0, 1]);
+        assert_eq!(p.max_dependency_index(), Some(1));
+        assert_eq!(check(&p, &[Rejected, Succeeded]), Ok(()));
+        assert_eq!(
+            check(&p, &[Rejected, Open]),
+            Err(ReturnError::DependentProposalNotReady)
+        );
+        assert_eq!(
+            check(&p, &[Rejected, Failed(ExecutionStep::new(0))]),
+            Err(ReturnError::DependentProposalNotSucceeded)
+        );
+        p.depends_on_any = Some(vec![]);
+        assert_eq!(check(&p, &[]), Ok(()));
+    }
+
+    #[test]
+    fn conflicting_proposals() {
+        use ProposalState::*;
+        let mut p = payload(vec![0], vec![]);
+        p.conflicts_with = Some(vec![1, 5]);
+        assert_eq!(p.max_dependency_index(), Some(0));
+        // Conflicts that have not succeeded, or do not exist yet, do not block execution.
+        assert_eq!(check(&p, &[Succeeded, Accepted]), Ok(()));
+        assert_eq!(check(&p, &[Succeeded, Rejected]), Ok(()));
+        assert_eq!(
+            check(&p, &[Accepted, ForceExecutionSucceeded]),
+            Err(ReturnError::ConflictingProposalSucceeded)
+        );
+    }
+
+    #[test]
+    fn batch_dependencies_resolve_to_absolute_ids() {
+        let mut p = payload(vec![3], vec![1, 3]);
+        p.resolve_batch_dependencies(3, 10).unwrap();
+        assert_eq!(p.depends_on, vec![3, 12, 10]);
+        assert!(p.batch_depends_on.is_none());
+        assert_eq!(p.max_dependency_index(), Some(12));
+    }
+
+    #[test]
+    fn batch_dependencies_move_with_the_batch() {
+        let mut p = payload(vec![3], vec![1, 2]);
+        p.resolve_batch_dependencies(2, 10).unwrap();
+        p.rebase_batch_dependencies(10, 15, 3);
+        assert_eq!(p.depends_on, vec![3, 16, 15]);
+    }
+
+    #[test]
+    fn batch_dependencies_must_refer_to_earlier_items() {
+        assert_eq!(
+            payload(vec![], vec![0]).resolve_batch_dependencies(2, 10),
+            Err(ReturnError::InputError)
+        );
+        assert_eq!(
+            payload(vec![], vec![3]).resolve_batch_dependencies(2, 10),
+            Err(ReturnError::InputError)
+        );
+        assert_eq!(
+            payload(vec![], vec![1]).resolve_batch_dependencies(0, 10),
+            Err(ReturnError::InputError)
+        );
+        assert!(payload(vec![], vec![])
+            .resolve_batch_dependencies(0, 10)
+            .is_ok());
+    }
+
+    #[test]
+    fn invalid_conditions() {
+        let mut c = condition(ConditionPredicate::Equal, encode_one(true).unwrap());
+        c.expected = vec![1, 2, 3];
+        assert!(!c.is_valid());
+        let mut c = condition(ConditionPredicate::Equal, encode_one(true).unwrap());
+        c.method = String::new();
+        assert!(!c.is_valid());
+    }
+}
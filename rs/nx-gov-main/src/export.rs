@@ -0,0 +1,94 @@
+//! CSV exports over HTTP, for frontends and audits.
+
+use crate::http::HttpResponse;
+use crate::memory::get_tally_history;
+use crate::proposal::TallyRecord;
+use crate::types::Index;
+
+use serde_bytes::ByteBuf;
+
+/// Returns the tally history of a proposal as CSV, oldest first.
+///
+/// The query string must contain `proposal_id`, and may contain `skip` and `take` for pagination.
+pub fn get_tally_history_csv(query: &str) -> HttpResponse {
+    let proposal_id = match query_param(query, "proposal_id") {
+        Some(proposal_id) => proposal_id,
+        None => {
+            return HttpResponse {
+                status_code: 400,
+                headers: vec![],
+                body: ByteBuf::from(String::from("Missing or invalid proposal_id.")),
+            }
+        }
+    };
+    let skip = query_param(query, "skip").unwrap_or(0);
+    let take = query_param(query, "take").unwrap_or(u64::MAX);
+    let body = tally_history_csv(&get_tally_history(proposal_id, skip, take)).into_bytes();
+    HttpResponse {
+        status_code: 200,
+        headers: vec![
+            ("Content-Type".to_string(), "text/csv".to_string()),
+            ("Content-Length".to_string(), body.len().to_string()),
+        ],
+        body: ByteBuf::from(body),
+    }
+}
+
+/// Parses a numeric parameter of a query string such as `proposal_id=1&skip=10`.
+fn query_param(query: &str, name: &str) -> Option<Index> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+fn tally_history_csv(records: &[TallyRecord]) -> String {
+    let mut csv = String::from(
+        "recorded_at,vote_manager,votes_yes,votes_no,votes_abstain,total_voting_power\n",
+    );
+    for r in records {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            r.recorded_at,
+            r.vote_manager,
+            r.votes_yes,
+            r.votes_no,
+            r.votes_abstain,
+            r.total_voting_power
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    #[test]
+    fn parses_query_params() {
+        let query = "proposal_id=7&skip=x&take=20";
+        assert_eq!(query_param(query, "proposal_id"), Some(7));
+        assert_eq!(query_param(query, "skip"), None);
+        assert_eq!(query_param(query, "take"), Some(20));
+        assert_eq!(query_param("", "proposal_id"), None);
+    }
+
+    #[test]
+    fn formats_one_row_per_record() {
+        let record = TallyRecord {
+            recorded_at: 1_000,
+            vote_manager: Principal::anonymous(),
+            votes_yes: 3,
+            votes_no: 2,
+            votes_abstain: 1,
+            total_voting_power: 10,
+        };
+        assert_eq!(
+            tally_history_csv(&[record]),
+            "recorded_at,vote_manager,votes_yes,votes_no,votes_abstain,total_voting_power\n\
+             1000,2vxsx-fae,3,2,1,10\n"
+        );
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! This file is taken from [ic-eth-wallet](https://github.com/dfinity/ic-eth-wallet) which is licensed under Apache-2.0.
 
+use crate::export::get_tally_history_csv;
 use crate::metrics::get_metrics;
 
 use candid::{CandidType, Deserialize};
@@ -37,6 +38,7 @@ pub fn http_request(request: HttpRequest) -> HttpResponse {
     let parts: Vec<&str> = request.url.split('?').collect();
     match parts[0] {
         "/metrics" => get_metrics(),
+        "/tally_history.csv" => get_tally_history_csv(parts.get(1).unwrap_or(&"")),
         _ => HttpResponse {
             status_code: 404,
             headers: vec![],
@@ -0,0 +1,129 @@
+//! Minimal ICRC-1/ICRC-2 ledger interface used for submission deposits and token sponsorship.
+
+use crate::deposit::{Account, Subaccount};
+use crate::types::ReturnError;
+
+use candid::{CandidType, Deserialize, Nat, Principal};
+use num_traits::cast::ToPrimitive;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TransferArg {
+    pub from_subaccount: Option<Subaccount>,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<serde_bytes::ByteBuf>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum TransferError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TransferFromArgs {
+    pub spender_subaccount: Option<Subaccount>,
+    pub from: Account,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<serde_bytes::ByteBuf>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+/// Returns the transfer fee of the ledger.
+pub async fn icrc1_fee(ledger: Principal) -> Result<u128, ReturnError> {
+    let (fee,): (Nat,) = ic_cdk::call(ledger, "icrc1_fee", ())
+        .await
+        .map_err(|_| ReturnError::InterCanisterCallError)?;
+    fee.0.to_u128().ok_or(ReturnError::ArithmeticError)
+}
+
+/// Returns the balance of an account. Balances that do not fit in `u128` saturate.
+pub async fn icrc1_balance_of(ledger: Principal, account: Account) -> Result<u128, ReturnError> {
+    let (balance,): (Nat,) = ic_cdk::call(ledger, "icrc1_balance_of", (account,))
+        .await
+        .map_err(|_| ReturnError::InterCanisterCallError)?;
+    Ok(balance.0.to_u128().unwrap_or(u128::MAX))
+}
+
+/// Transfers from a subaccount of the governance canister. Returns the block index.
+///
+/// The ledger deduplicates transfers with the same `memo` and `created_at_time`, so a retry of a transfer
+/// that was executed returns the block index of the first one. Returns `Expired` once the retry falls
+/// outside of the deduplication window of the ledger.
+pub async fn icrc1_transfer(
+    ledger: Principal,
+    from_subaccount: Subaccount,
+    to: Account,
+    amount: u128,
+    memo: Vec<u8>,
+    created_at_time: u64,
+) -> Result<u128, ReturnError> {
+    let args = TransferArg {
+        from_subaccount: Some(from_subaccount),
+        to,
+        amount: Nat::from(amount),
+        fee: None,
+        memo: Some(serde_bytes::ByteBuf::from(memo)),
+        created_at_time: Some(created_at_time),
+    };
+    let (res,): (Result<Nat, TransferError>,) = ic_cdk::call(ledger, "icrc1_transfer", (args,))
+        .await
+        .map_err(|_| ReturnError::InterCanisterCallError)?;
+    let block_index = match res {
+        Ok(block_index) => block_index,
+        Err(TransferError::Duplicate { duplicate_of }) => duplicate_of,
+        Err(TransferError::TooOld) => return Err(ReturnError::Expired),
+        Err(_) => return Err(ReturnError::DepositError),
+    };
+    block_index.0.to_u128().ok_or(ReturnError::ArithmeticError)
+}
+
+/// Transfers an approved amount from `from` into a subaccount of the governance canister. Returns the block index.
+pub async fn icrc2_transfer_from(
+    ledger: Principal,
+    from: Account,
+    to_subaccount: Subaccount,
+    amount: u128,
+) -> Result<u128, ReturnError> {
+    let args = TransferFromArgs {
+        spender_subaccount: None,
+        from,
+        to: Account {
+            owner: ic_cdk::api::id(),
+            subaccount: Some(to_subaccount),
+        },
+        amount: Nat::from(amount),
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+    let (res,): (Result<Nat, TransferFromError>,) =
+        ic_cdk::call(ledger, "icrc2_transfer_from", (args,))
+            .await
+            .map_err(|_| ReturnError::InterCanisterCallError)?;
+    let block_index = res.map_err(|_| ReturnError::DepositError)?;
+    block_index.0.to_u128().ok_or(ReturnError::ArithmeticError)
+}
@@ -1,9 +1,21 @@
+mod appeal;
+mod choice;
+mod deposit;
+mod embargo;
 mod execution;
+mod liveness;
 mod proposal;
+mod sponsor;
 mod types;
 mod validate;
 
+pub use appeal::*;
+pub use choice::*;
+pub use deposit::*;
+pub use embargo::*;
 pub use execution::*;
+pub use liveness::*;
 pub use proposal::*;
+pub use sponsor::*;
 pub use types::*;
 pub use validate::*;
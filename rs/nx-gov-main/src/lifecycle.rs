@@ -1,5 +1,6 @@
 use crate::access::*;
 use crate::memory::*;
+use crate::timer::*;
 
 use candid::{encode_one, Principal};
 use ic_cdk_macros::{init, post_upgrade, update};
@@ -41,5 +42,9 @@ pub async fn initialize(
 
 #[post_upgrade]
 fn post_upgrade() {
-    // Restart timer
+    continue_storage_migration();
+
+    // Timers do not survive upgrades
+    restart_auto_executor();
+    restart_liveness_monitor();
 }
@@ -0,0 +1,89 @@
+use crate::types::*;
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Monitoring of vote managers, which must report on every proposal in voting.
+///
+/// A proposal is stale once no vote manager reported on it for `stale_after`.
+/// A timer checks for stale proposals every `check_interval`, and escalates each of them.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LivenessConfig {
+    /// Time without a report after which a proposal in voting is stale, in nano-seconds.
+    pub stale_after: TimeNs,
+    /// Polling interval of the liveness monitor, in nano-seconds. If 0, stale proposals are only listed.
+    pub check_interval: TimeNs,
+    /// Calls `notify_stale_proposal` on every Revoker, once per stale proposal.
+    pub notify_revokers: bool,
+    /// Finalizes stale proposals after their voting end time, with the tally recorded so far.
+    pub auto_finalize: bool,
+}
+
+impl LivenessConfig {
+    pub fn is_stale(&self, liveness: &ProposalLiveness, now: TimeNs) -> bool {
+        now.saturating_sub(liveness.last_activity()) >= self.stale_after
+    }
+}
+
+/// Report times of a proposal in voting.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProposalLiveness {
+    /// Time when the current voting round started.
+    pub opened_at: TimeNs,
+    /// Time of the last report of a vote manager in the current voting round.
+    pub last_report: Option<TimeNs>,
+    /// Time when the Revokers were notified that the proposal is stale.
+    pub escalated_at: Option<TimeNs>,
+}
+
+impl ProposalLiveness {
+    pub fn new(opened_at: TimeNs) -> Self {
+        Self {
+            opened_at,
+            last_report: None,
+            escalated_at: None,
+        }
+    }
+
+    /// The last report, or the start of the voting round if no vote manager reported yet.
+    pub fn last_activity(&self) -> TimeNs {
+        self.last_report.unwrap_or(self.opened_at)
+    }
+
+    /// Records a report. A report also ends a previous escalation.
+    pub fn report(&mut self, now: TimeNs) {
+        self.last_report = Some(now);
+        self.escalated_at = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LivenessConfig {
+        LivenessConfig {
+            stale_after: 100,
+            check_interval: 10,
+            notify_revokers: true,
+            auto_finalize: false,
+        }
+    }
+
+    #[test]
+    fn stale_without_reports() {
+        let liveness = ProposalLiveness::new(1_000);
+        assert!(!config().is_stale(&liveness, 1_099));
+        assert!(config().is_stale(&liveness, 1_100));
+    }
+
+    #[test]
+    fn report_resets_staleness_and_escalation() {
+        let mut liveness = ProposalLiveness::new(1_000);
+        liveness.escalated_at = Some(1_100);
+        liveness.report(1_150);
+        assert_eq!(liveness.escalated_at, None);
+        assert!(!config().is_stale(&liveness, 1_200));
+        assert!(config().is_stale(&liveness, 1_250));
+    }
+}
@@ -3,24 +3,40 @@
 // TODO: governance recovery mechanism
 
 mod access;
+mod appeal;
+mod choice;
+mod deposit;
+mod embargo;
 mod execution;
+mod export;
 pub mod http;
+mod ledger;
 mod lifecycle;
+mod liveness;
 mod memory;
 mod metrics;
 mod proposal;
+mod sponsor;
 mod storage;
-//mod timer;
+mod timer;
 mod types;
 mod validate;
 
 use crate::access::*;
+use crate::appeal::*;
+use crate::choice::*;
+use crate::deposit::*;
+use crate::embargo::*;
 use crate::execution::*;
 #[allow(unused_imports)]
 use crate::http::*;
+use crate::ledger::*;
+use crate::liveness::*;
 use crate::memory::*;
 use crate::proposal::*;
+use crate::sponsor::*;
 use crate::storage::*;
+use crate::timer::*;
 use crate::types::*;
 use crate::validate::Validate;
 
@@ -32,47 +48,321 @@ use ic_cdk::api::{
         CanisterInfoResponse, CanisterStatusResponse, CanisterStatusType,
     },
 };
-use ic_cdk_macros::update;
+use ic_cdk_macros::{query, update};
+use ic_stable_structures::Storable;
 
 use num_traits::cast::ToPrimitive;
 
 // ==== Proposal functions ====
 /// Submit a proposal. Returns the the proposal ID.
+///
+/// `options` is an optional trailing argument, and can be omitted by callers.
+/// If a submission deposit is configured, the caller must have approved the governance canister
+/// to spend the deposit amount plus the ledger fee.
 /// # Panics
 /// Panics if the proposal is invalid.
 #[update]
-pub fn submit(
+pub async fn submit(
     metadata: ProposalMetadata,
     payload: ProposalPayload,
     activates: Schedule,
     expires: Schedule,
     auto_execute: bool,
+    options: Option<SubmitOptions>,
 ) -> Result<Index, ReturnError> {
     require_caller_has_role(UserRole::Proposer);
-    assert!(metadata.is_valid() && payload.is_valid() && expires.is_in_future());
+    let options = options.unwrap_or_default();
+    assert!(
+        metadata.is_valid() && payload.is_valid() && expires.is_in_future() && options.is_valid()
+    );
+    // Proposal IDs only grow, so this also holds for the ID assigned after the deposit is collected.
+    let next_proposal_id = get_next_proposal_id();
+    assert!(std::iter::once(&payload)
+        .chain(options.alternatives())
+        .all(|p| !p.has_batch_dependencies() && p.max_dependency_index() < Some(next_proposal_id)));
+
+    assert!(options.embargo.is_none() || payload.messages.is_empty());
 
     let caller = ic_cdk::api::caller();
-    let payload_id = add_proposal_payload(&payload)?;
-    let metadata_id = add_proposal_metadata(&metadata)?;
+    if options.class == Some(ProposalClass::Emergency) && get_config()?.emergency.is_none() {
+        return Err(ReturnError::InputError);
+    }
+    if let Some(condition) = options.activation_condition.as_ref() {
+        if !get_config()?.is_condition_canister(&condition.canister_id) {
+            return Err(ReturnError::InputError);
+        }
+    }
+    if let Some(supersession) = options.supersedes.as_ref() {
+        check_supersession(caller, supersession)?;
+    }
+    let reservation = check_submission_limits(caller, &[(&metadata, &payload, &options)])?;
+    let deposit = match get_config()?.deposit {
+        Some(deposit_config) => match collect_deposit(caller, deposit_config, 1).await {
+            Ok(deposit) => Some(deposit),
+            Err(e) => {
+                release_submission_limits(reservation, false);
+                return Err(e);
+            }
+        },
+        None => None,
+    };
 
     let proposal = Proposal::from_submit(
-        metadata_id,
-        payload_id,
+        0,
+        0,
         auto_execute,
         activates,
         expires,
+        options.recurrence,
+        options.class,
         &caller,
     );
-    let proposal_id = add_proposal(&proposal)?;
-    assert!(payload.max_dependency_index() < Some(proposal_id));
+    let submission = add_submission(&metadata, &payload, options.choices.as_ref(), proposal);
+    release_submission_limits(reservation, submission.is_ok());
+    let proposal_id = match submission {
+        Ok(proposal_id) => proposal_id,
+        Err(e) => {
+            // Nothing references the deposit, so it is returned right away.
+            if let Some(deposit) = deposit {
+                ic_cdk::spawn(async move {
+                    let _ = return_deposit(
+                        &deposit,
+                        DepositKind::Submission,
+                        None,
+                        DepositOutcome::Refund,
+                    )
+                    .await;
+                });
+            }
+            return Err(e);
+        }
+    };
+    if let Some(deposit) = deposit {
+        set_proposal_deposit(proposal_id, &deposit);
+    }
+    if let Some(condition) = options.activation_condition {
+        set_activation_condition(proposal_id, &condition);
+    }
+    if let Some(supersession) = options.supersedes {
+        set_supersession(proposal_id, &supersession);
+    }
+    if let Some(embargo) = options.embargo {
+        set_embargo(proposal_id, &EmbargoedPayload::new(embargo));
+    }
+    start_sponsorship(proposal_id)?;
 
-    if get_config()?.validator_hook.is_some() {
+    if get_config()?.validator_hook.is_some() && !is_awaiting_sponsorship(proposal_id) {
         push_timer_task(proposal_id)?;
     }
 
     Ok(proposal_id)
 }
 
+/// Submit a batch of proposals. Returns the proposal IDs, in the order of the batch.
+///
+/// Items can depend on earlier items of the batch with `batch_depends_on`,
+/// which is resolved to absolute proposal IDs. Either all proposals are written, or none.
+/// If a submission deposit is configured, one deposit per proposal is collected in a single transfer,
+/// and returned if writing the batch fails.
+/// # Panics
+/// Panics if any proposal is invalid.
+#[update]
+pub async fn submit_batch(
+    batch: Vec<(ProposalMetadata, ProposalPayload, Schedule, Schedule, bool)>,
+) -> Result<Vec<Index>, ReturnError> {
+    require_caller_has_role(UserRole::Proposer);
+    assert!(batch
+        .iter()
+        .all(|(metadata, payload, _, expires, _)| metadata.is_valid()
+            && payload.is_valid()
+            && expires.is_in_future()));
+    if batch.is_empty() {
+        return Err(ReturnError::InputError);
+    }
+    let next_id = get_next_proposal_id();
+    assert!(batch
+        .iter()
+        .all(|(_, payload, ..)| payload.max_dependency_index() < Some(next_id)));
+    // Resolved for the next ID now, and moved after the deposit call if other proposals were submitted meanwhile.
+    let mut batch = batch;
+    for (position, (_, payload, ..)) in batch.iter_mut().enumerate() {
+        payload.resolve_batch_dependencies(position as u64, next_id)?;
+    }
+
+    let caller = ic_cdk::api::caller();
+    let options = SubmitOptions::default();
+    let submissions: Vec<_> = batch
+        .iter()
+        .map(|(metadata, payload, ..)| (metadata, payload, &options))
+        .collect();
+    let reservation = check_submission_limits(caller, &submissions)?;
+    let deposit_config = get_config()?.deposit;
+    let deposit = match deposit_config.clone() {
+        Some(deposit_config) => {
+            match collect_deposit(caller, deposit_config, batch.len() as u128).await {
+                Ok(deposit) => Some(deposit),
+                Err(e) => {
+                    release_submission_limits(reservation, false);
+                    return Err(e);
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Nothing is awaited from here on, so the batch gets consecutive IDs.
+    let first_id = get_next_proposal_id();
+    let len = batch.len() as u64;
+    let mut proposal_ids = Vec::with_capacity(batch.len());
+    for (metadata, mut payload, activates, expires, auto_execute) in batch {
+        payload.rebase_batch_dependencies(next_id, first_id, len);
+        let proposal =
+            Proposal::from_submit(0, 0, auto_execute, activates, expires, None, None, &caller);
+        match add_submission(&metadata, &payload, None, proposal) {
+            Ok(proposal_id) => proposal_ids.push(proposal_id),
+            Err(e) => {
+                // Payloads and metadata written so far are not referenced by any proposal.
+                remove_proposals_from(first_id);
+                release_submission_limits(reservation, false);
+                if let Some(deposit) = deposit {
+                    ic_cdk::spawn(async move {
+                        let _ = return_deposit(
+                            &deposit,
+                            DepositKind::Submission,
+                            None,
+                            DepositOutcome::Refund,
+                        )
+                        .await;
+                    });
+                }
+                return Err(e);
+            }
+        }
+    }
+    release_submission_limits(reservation, true);
+
+    if let (Some(deposit), Some(deposit_config)) = (deposit, deposit_config) {
+        let deposit = ProposalDeposit {
+            amount: deposit_config.amount,
+            ..deposit
+        };
+        for proposal_id in proposal_ids.iter() {
+            set_proposal_deposit(*proposal_id, &deposit);
+        }
+    }
+    for proposal_id in proposal_ids.iter() {
+        start_sponsorship(*proposal_id)?;
+    }
+    if get_config()?.validator_hook.is_some() {
+        for proposal_id in proposal_ids.iter() {
+            if !is_awaiting_sponsorship(*proposal_id) {
+                push_timer_task(*proposal_id)?;
+            }
+        }
+    }
+
+    Ok(proposal_ids)
+}
+
+/// Submission slots taken by `check_submission_limits`, held until the submissions are written or have failed.
+struct SubmissionReservation {
+    proposer: Principal,
+    submissions: u64,
+    /// Start time of the rate limit window that counted the submissions.
+    window_start: TimeNs,
+}
+
+impl SubmissionReservation {
+    /// Releases the reserved pending proposal slots. Failed submissions also give back their rate limit slots.
+    fn release(self, written: bool) {
+        release_pending_proposals(self.submissions);
+        if !written {
+            uncount_submissions(self.proposer, self.window_start, self.submissions);
+        }
+    }
+}
+
+/// Enforces the configured submission limits, and reserves the pending proposal and rate limit slots of the submissions.
+/// Must run before the deposit is collected and before the submissions are written to stable memory.
+/// The returned reservation must be released once the submissions are written or have failed.
+fn check_submission_limits(
+    proposer: Principal,
+    submissions: &[(&ProposalMetadata, &ProposalPayload, &SubmitOptions)],
+) -> Result<Option<SubmissionReservation>, ReturnError> {
+    let limits = match get_config()?.submission_limits {
+        Some(limits) => limits,
+        None => return Ok(None),
+    };
+    for (metadata, payload, options) in submissions {
+        if !limits.is_metadata_size_allowed(metadata.to_bytes().len())
+            || !limits.is_payload_size_allowed(
+                payload.to_bytes().len()
+                    + options
+                        .alternatives()
+                        .iter()
+                        .map(|p| p.to_bytes().len())
+                        .sum::<usize>()
+                    + options
+                        .activation_condition
+                        .as_ref()
+                        .map_or(0, |c| c.to_bytes().len()),
+            )
+        {
+            return Err(ReturnError::SizeLimitExceeded);
+        }
+    }
+    let count = submissions.len() as u64;
+    // Reserved before the deposit call, so that concurrent submissions cannot exceed the limits.
+    let window_start = count_submissions(proposer, &limits, ic_cdk::api::time(), count)?;
+    if let Err(e) = reserve_pending_proposals(limits.max_pending_proposals, count) {
+        uncount_submissions(proposer, window_start, count);
+        return Err(e);
+    }
+    Ok(Some(SubmissionReservation {
+        proposer,
+        submissions: count,
+        window_start,
+    }))
+}
+
+fn release_submission_limits(reservation: Option<SubmissionReservation>, written: bool) {
+    if let Some(reservation) = reservation {
+        reservation.release(written);
+    }
+}
+
+/// Checks that the proposer can supersede the given proposal.
+fn check_supersession(proposer: Principal, supersession: &Supersession) -> Result<(), ReturnError> {
+    let superseded = get_proposal_by_id(supersession.proposal_id)?;
+    if superseded.proposer != proposer {
+        return Err(ReturnError::Unauthorized);
+    }
+    if superseded.state.is_final() {
+        return Err(ReturnError::IncorrectProposalState);
+    }
+    Ok(())
+}
+
+/// Writes the payload, metadata and proposal of a submission to stable memory. Returns the proposal ID.
+/// The alternatives of a multi-option proposal are written after the payload.
+fn add_submission(
+    metadata: &ProposalMetadata,
+    payload: &ProposalPayload,
+    choices: Option<&ChoiceOptions>,
+    mut proposal: Proposal,
+) -> Result<Index, ReturnError> {
+    proposal.payload_id = add_proposal_payload(payload)?;
+    if let Some(choices) = choices {
+        let mut payload_ids = vec![proposal.payload_id];
+        for alternative in &choices.alternatives {
+            payload_ids.push(add_proposal_payload(alternative)?);
+        }
+        proposal.choices = Some(Choices::new(payload_ids, choices.selection.clone()));
+    }
+    proposal.metadata_id = add_proposal_metadata(metadata)?;
+    add_proposal(&proposal)
+}
+
 /// Validate a proposal.
 /// This function is called by the validator canister.
 #[update]
@@ -85,19 +375,27 @@ pub fn validate(
     require_caller_has_role(UserRole::Validator);
 
     let config = get_config()?;
+    let mut proposal = get_proposal_by_id(proposal_id)?;
+
+    // Emergency proposals are held to their own floors, so that the validator cannot lower them.
+    let (min_voting_period, min_passing_threshold) = match proposal.class() {
+        ProposalClass::Standard => (config.min_voting_period, config.min_passing_threshold),
+        ProposalClass::Emergency => {
+            let emergency = config.emergency.ok_or(ReturnError::InputError)?;
+            (emergency.min_voting_period, emergency.min_passing_threshold)
+        }
+    };
     // must set voting_end_time and passing_threshold after this stage if validated
     if validated
         && (!passing_threshold
             .clone()
-            .is_some_and(|x| x.is_valid() && x.all_fields_gte(&config.min_passing_threshold))
-            || !voting_end_time.is_some_and(|x| x > ic_cdk::api::time() + config.min_voting_period))
+            .is_some_and(|x| x.is_valid() && x.all_fields_gte(&min_passing_threshold))
+            || !voting_end_time.is_some_and(|x| x > ic_cdk::api::time() + min_voting_period))
     {
         return Err(ReturnError::InputError);
     }
 
-    let mut proposal = get_proposal_by_id(proposal_id)?;
-
-    if proposal.state != ProposalState::Submitted {
+    if proposal.state != ProposalState::Submitted || is_awaiting_sponsorship(proposal_id) {
         return Err(ReturnError::IncorrectProposalState);
     }
 
@@ -114,16 +412,23 @@ pub fn validate(
     proposal.passing_threshold = passing_threshold;
 
     set_proposal_by_id(proposal_id, &proposal);
+    if validated {
+        set_proposal_liveness(proposal_id, &ProposalLiveness::new(ic_cdk::api::time()));
+    }
 
     if get_config()?.vote_manager_hook.is_some() {
         push_timer_task(proposal_id)?;
     }
 
+    supersede_if_due(proposal_id);
+    settle_deposit_if_final(proposal_id);
     Ok(())
 }
 
 /// Update vote results.
 /// This function is called by the vote manager canister.
+///
+/// For a multi-option proposal, "yes" votes must go through `update_choice_vote_result`.
 #[update]
 pub fn update_vote_result(
     proposal_id: Index,
@@ -136,6 +441,9 @@ pub fn update_vote_result(
     if !proposal.is_voteable() {
         return Err(ReturnError::Expired);
     }
+    if proposal.choices.is_some() && yes_voting_power != 0 {
+        return Err(ReturnError::InputError);
+    }
     proposal.votes_yes += yes_voting_power;
     proposal.votes_no += no_voting_power;
     proposal.votes_abstain += abstain_voting_power;
@@ -155,6 +463,61 @@ pub fn update_vote_result(
     }
 
     set_proposal_by_id(proposal_id, &proposal);
+    add_tally_record(
+        proposal_id,
+        TallyRecord::new(&proposal, ic_cdk::api::caller(), ic_cdk::api::time()),
+    );
+    record_vote_manager_report(proposal_id);
+    supersede_if_due(proposal_id);
+    settle_deposit_if_final(proposal_id);
+    Ok(())
+}
+
+/// Update the per-choice vote results of a multi-option proposal.
+/// This function is called by the vote manager canister.
+///
+/// `choice_voting_power` holds incremental changes for each choice, in choice order.
+/// The votes for all choices make up the "yes" votes of the proposal, while "no" votes reject all choices.
+/// The winning choice is selected when the proposal is finalized, after the voting period.
+#[update]
+pub fn update_choice_vote_result(
+    proposal_id: Index,
+    choice_voting_power: Vec<VotingPower>,
+    no_voting_power: VotingPower,
+    abstain_voting_power: VotingPower,
+) -> Result<(), ReturnError> {
+    require_caller_has_role(UserRole::VoteManager);
+    let mut proposal = get_proposal_by_id(proposal_id)?;
+    if !proposal.is_voteable() {
+        return Err(ReturnError::Expired);
+    }
+    let choices = proposal.choices.as_mut().ok_or(ReturnError::InputError)?;
+    choices.add_votes(&choice_voting_power)?;
+    proposal.votes_yes = choices.total()?;
+    proposal.votes_no += no_voting_power;
+    proposal.votes_abstain += abstain_voting_power;
+    if proposal.votes_no < 0
+        || proposal.votes_abstain < 0
+        || proposal.votes_yes + proposal.votes_no + proposal.votes_abstain
+            >= proposal.total_voting_power
+    {
+        return Err(ReturnError::ArithmeticError);
+    }
+    let config = get_config()?;
+    if config.voting_may_end_early || proposal.voting_end_time.unwrap() < ic_cdk::api::time() {
+        proposal
+            .try_finalize_vote_result()
+            .map_err(|_| ReturnError::StateTransitionError)?;
+    }
+
+    set_proposal_by_id(proposal_id, &proposal);
+    add_tally_record(
+        proposal_id,
+        TallyRecord::new(&proposal, ic_cdk::api::caller(), ic_cdk::api::time()),
+    );
+    record_vote_manager_report(proposal_id);
+    supersede_if_due(proposal_id);
+    settle_deposit_if_final(proposal_id);
     Ok(())
 }
 
@@ -188,6 +551,13 @@ pub fn update_total_voting_power(
             .map_err(|_| ReturnError::StateTransitionError)?;
     }
     set_proposal_by_id(proposal_id, &proposal);
+    add_tally_record(
+        proposal_id,
+        TallyRecord::new(&proposal, ic_cdk::api::caller(), ic_cdk::api::time()),
+    );
+    record_vote_manager_report(proposal_id);
+    supersede_if_due(proposal_id);
+    settle_deposit_if_final(proposal_id);
     Ok(())
 }
 
@@ -206,12 +576,84 @@ pub fn update_vote_result_and_total_voting_power(
 ) -> Result<(), ReturnError> {
     require_caller_has_role(UserRole::VoteManager);
     let mut proposal = get_proposal_by_id(proposal_id)?;
+    add_votes_and_total_voting_power(
+        &mut proposal,
+        yes_voting_power,
+        no_voting_power,
+        abstain_voting_power,
+        total_voting_power,
+    )?;
+    let config = get_config()?;
+    if config.voting_may_end_early || proposal.voting_end_time.unwrap() < ic_cdk::api::time() {
+        proposal
+            .try_finalize_vote_result()
+            .map_err(|_| ReturnError::StateTransitionError)?;
+    }
+    set_proposal_by_id(proposal_id, &proposal);
+    add_tally_record(
+        proposal_id,
+        TallyRecord::new(&proposal, ic_cdk::api::caller(), ic_cdk::api::time()),
+    );
+    record_vote_manager_report(proposal_id);
+    supersede_if_due(proposal_id);
+    settle_deposit_if_final(proposal_id);
+    Ok(())
+}
+
+/// Update results and voting power, and reject the proposal whatever the counts.
+///
+/// This function is called by a vote manager canister that decides votes itself, such as a multisig,
+/// so that its rejection is binding even if the counts would pass the proposal.
+/// The arguments are the same as for `update_vote_result_and_total_voting_power`.
+/// A rejected appeal round ends in `AppealRejected`.
+#[update]
+pub fn reject_vote_result(
+    proposal_id: Index,
+    yes_voting_power: VotingPower,
+    no_voting_power: VotingPower,
+    abstain_voting_power: VotingPower,
+    total_voting_power: VotingPower,
+) -> Result<(), ReturnError> {
+    require_caller_has_role(UserRole::VoteManager);
+    let mut proposal = get_proposal_by_id(proposal_id)?;
+    add_votes_and_total_voting_power(
+        &mut proposal,
+        yes_voting_power,
+        no_voting_power,
+        abstain_voting_power,
+        total_voting_power,
+    )?;
+    proposal
+        .reject_vote_result()
+        .map_err(|_| ReturnError::StateTransitionError)?;
+    set_proposal_by_id(proposal_id, &proposal);
+    add_tally_record(
+        proposal_id,
+        TallyRecord::new(&proposal, ic_cdk::api::caller(), ic_cdk::api::time()),
+    );
+    record_vote_manager_report(proposal_id);
+    supersede_if_due(proposal_id);
+    settle_deposit_if_final(proposal_id);
+    Ok(())
+}
+
+/// Adds incremental votes to a proposal that can be voted on, and sets its total voting power.
+fn add_votes_and_total_voting_power(
+    proposal: &mut Proposal,
+    yes_voting_power: VotingPower,
+    no_voting_power: VotingPower,
+    abstain_voting_power: VotingPower,
+    total_voting_power: VotingPower,
+) -> Result<(), ReturnError> {
     if !proposal.is_voteable() {
         return Err(ReturnError::Expired);
     }
     if total_voting_power < 0 {
         return Err(ReturnError::ArithmeticError);
     }
+    if proposal.choices.is_some() && yes_voting_power != 0 {
+        return Err(ReturnError::InputError);
+    }
     proposal.votes_yes += yes_voting_power;
     proposal.votes_no += no_voting_power;
     proposal.votes_abstain += abstain_voting_power;
@@ -219,13 +661,6 @@ pub fn update_vote_result_and_total_voting_power(
     if proposal.votes_yes < 0 || proposal.votes_no < 0 || proposal.votes_abstain < 0 {
         return Err(ReturnError::ArithmeticError);
     }
-    let config = get_config()?;
-    if config.voting_may_end_early || proposal.voting_end_time.unwrap() < ic_cdk::api::time() {
-        proposal
-            .try_finalize_vote_result()
-            .map_err(|_| ReturnError::StateTransitionError)?;
-    }
-    set_proposal_by_id(proposal_id, &proposal);
     Ok(())
 }
 
@@ -242,17 +677,69 @@ pub fn finalize_vote_result(proposal_id: Index) -> Result<(), ReturnError> {
             .map_err(|_| ReturnError::StateTransitionError)?;
     }
     set_proposal_by_id(proposal_id, &proposal);
+    record_vote_manager_report(proposal_id);
+    supersede_if_due(proposal_id);
+    settle_deposit_if_final(proposal_id);
+    Ok(())
+}
+
+/// Records the report time of the calling vote manager, for the vote manager and for the proposal.
+fn record_vote_manager_report(proposal_id: Index) {
+    let now = ic_cdk::api::time();
+    set_vote_manager_report(ic_cdk::api::caller(), now);
+    let mut liveness =
+        get_proposal_liveness(proposal_id).unwrap_or_else(|| ProposalLiveness::new(now));
+    liveness.report(now);
+    set_proposal_liveness(proposal_id, &liveness);
+}
+
+/// Finalizes a stale proposal after its voting end time, with the tally recorded so far.
+/// Called by the liveness monitor, in place of the vote manager.
+pub(crate) fn finalize_stale_proposal(proposal_id: Index) -> Result<(), ReturnError> {
+    let mut proposal = get_proposal_by_id(proposal_id)?;
+    if !proposal.is_in_voting() || !proposal.is_expired() {
+        return Err(ReturnError::IncorrectProposalState);
+    }
+    proposal
+        .try_finalize_vote_result()
+        .map_err(|_| ReturnError::StateTransitionError)?;
+    set_proposal_by_id(proposal_id, &proposal);
+    supersede_if_due(proposal_id);
+    settle_deposit_if_final(proposal_id);
     Ok(())
 }
 
+/// Notifies every Revoker that a proposal is stale, with `notify_stale_proposal(proposal_id, last_activity)`.
+/// Each proposal is only escalated once, until a vote manager reports on it again.
+pub(crate) fn escalate_stale_proposal(proposal_id: Index, last_activity: TimeNs) {
+    let now = ic_cdk::api::time();
+    let mut liveness =
+        get_proposal_liveness(proposal_id).unwrap_or_else(|| ProposalLiveness::new(now));
+    if liveness.escalated_at.is_some() {
+        return;
+    }
+    liveness.escalated_at = Some(now);
+    set_proposal_liveness(proposal_id, &liveness);
+    for revoker in users_of_role(UserRole::Revoker) {
+        // One-way calls, since Revokers that are not canisters cannot be notified.
+        let _ = ic_cdk::notify(
+            revoker,
+            "notify_stale_proposal",
+            (proposal_id, last_activity),
+        );
+    }
+}
+
 /// Revoke a proposal.
 /// Returns the revoke index
 /// This function is called by the revoker.
+///
+/// A standing order can also be revoked between runs, which stops all of its future runs.
 #[update]
 pub fn revoke(proposal_id: Index, reason: String) -> Result<(), ReturnError> {
     require_caller_has_role(UserRole::Revoker);
     let mut proposal = get_proposal_by_id(proposal_id)?;
-    if proposal.state != ProposalState::Open {
+    if !proposal.is_revocable() {
         return Err(ReturnError::IncorrectProposalState);
     }
     proposal
@@ -264,15 +751,37 @@ pub fn revoke(proposal_id: Index, reason: String) -> Result<(), ReturnError> {
         reason,
         revoked_at: ic_cdk::api::time(),
     })?;
+    settle_deposit_if_final(proposal_id);
     Ok(())
 }
 
 /// Execute a proposal.
 /// This function is called by the executor.
+///
+/// A standing order is executed once per run, and each run produces its own execution record.
 #[update]
 pub async fn execute(proposal_id: Index) -> Result<(), ReturnError> {
     require_caller_has_role(UserRole::Executor);
+    execute_and_settle(proposal_id).await
+}
+
+/// Executes a proposal, then settles its deposit if the proposal reached a final state.
+/// Callers must check the executor role.
+pub(crate) async fn execute_and_settle(proposal_id: Index) -> Result<(), ReturnError> {
+    let res = execute_proposal(proposal_id, None).await;
+    settle_deposit_if_final(proposal_id);
+    res
+}
+
+/// An embargoed payload revealed for execution, checked against its hash.
+struct Reveal {
+    payload: ProposalPayload,
+    embargo: EmbargoedPayload,
+}
 
+/// Executes the payload of a proposal, or the revealed payload of an embargoed proposal.
+/// A revealed payload is only stored once the execution starts.
+async fn execute_proposal(proposal_id: Index, reveal: Option<Reveal>) -> Result<(), ReturnError> {
     let mut proposal = get_proposal_by_id(proposal_id)?;
     if proposal.state == ProposalState::Open {
         proposal
@@ -282,11 +791,29 @@ pub async fn execute(proposal_id: Index) -> Result<(), ReturnError> {
     if !proposal.is_executable() {
         return Err(ReturnError::IncorrectProposalState);
     }
+    let payload_id = proposal
+        .execution_payload_id()
+        .ok_or(ReturnError::IncorrectProposalState)?;
+    if reveal.is_none() {
+        check_payload_released(proposal_id)?;
+    }
+    if let Some(condition) = get_activation_condition(proposal_id) {
+        // Commits the finalized vote result, since the proposal is read again after the call.
+        set_proposal_by_id(proposal_id, &proposal);
+        let is_met = evaluate_activation_condition(proposal_id, &condition).await;
+        proposal = get_proposal_by_id(proposal_id)?;
+        if !is_met {
+            return Err(ReturnError::ActivationConditionNotMet);
+        }
+        if !proposal.is_executable() {
+            return Err(ReturnError::IncorrectProposalState);
+        }
+    }
     let _ = proposal
         .state_transition(ProposalState::Executing(ExecutionStep::new(0)))
         .map_err(|_| ReturnError::StateTransitionError)?;
 
-    if proposal.is_expired() {
+    if proposal.is_execution_expired() {
         let _ = proposal
             .state_transition(ProposalState::Expired)
             .map_err(|_| ReturnError::StateTransitionError)?;
@@ -294,21 +821,33 @@ pub async fn execute(proposal_id: Index) -> Result<(), ReturnError> {
         return Err(ReturnError::Expired);
     }
 
-    let proposal_payload = get_proposal_payload_by_id(proposal.payload_id)?;
+    let proposal_payload = match &reveal {
+        Some(reveal) => reveal.payload.clone(),
+        None => get_proposal_payload_by_id(payload_id)?,
+    };
 
-    match validate_execution_dependency(proposal_payload.depends_on) {
+    match validate_execution_dependency(&proposal_payload) {
         Ok(_) => {}
-        Err(ReturnError::DependentProposalNotSucceeded) => {
+        Err(
+            e @ (ReturnError::DependentProposalNotSucceeded
+            | ReturnError::ConflictingProposalSucceeded),
+        ) => {
             let _ = proposal
                 .state_transition(ProposalState::Failed(ExecutionStep::new(0)))
                 .map_err(|_| ReturnError::StateTransitionError)?;
             set_proposal_by_id(proposal_id, &proposal);
-            return Err(ReturnError::DependentProposalNotSucceeded);
+            return Err(e);
         }
         // If dependent proposal is not ready, caller can retry execute this proposal. The state is not committed (should stay `Accepted`).
         Err(_) => return Err(ReturnError::DependentProposalNotReady),
     }
 
+    // The revealed payload becomes public as its execution starts, and replaces the placeholder payload.
+    if let Some(reveal) = reveal {
+        proposal.payload_id = add_proposal_payload(&reveal.payload)?;
+        set_embargo(proposal_id, &reveal.embargo);
+    }
+
     // Execute
     for (i, message) in proposal_payload.messages.iter().enumerate() {
         proposal
@@ -331,24 +870,89 @@ pub async fn execute(proposal_id: Index) -> Result<(), ReturnError> {
         }
     }
 
+    // Standing orders with runs left are re-armed for their next run.
     let _ = proposal
-        .state_transition(ProposalState::Succeeded)
+        .complete_run()
         .map_err(|_| ReturnError::StateTransitionError)?;
     set_proposal_by_id(proposal_id, &proposal);
 
     Ok(())
 }
 
+/// Time after which an activation condition call still in flight counts as failed, in nano-seconds.
+const CONDITION_CALL_TIMEOUT: TimeNs = 5 * 60 * 1_000_000_000;
+
+/// Evaluates the activation condition of a proposal, and records the outcome.
+///
+/// Only canisters in `Config.condition_canisters` are called, and at most one call per proposal is in flight.
+/// The condition is not met while a call is in flight. Calls cannot be cancelled, so once a call has taken
+/// longer than `CONDITION_CALL_TIMEOUT`, it is recorded as timed out and a new call may start.
+async fn evaluate_activation_condition(
+    proposal_id: Index,
+    condition: &ActivationCondition,
+) -> bool {
+    let now = ic_cdk::api::time();
+    let record = |result: Result<bool, String>| {
+        add_condition_evaluation(
+            proposal_id,
+            ConditionEvaluation {
+                evaluated_at: ic_cdk::api::time(),
+                result,
+            },
+        )
+    };
+    if !get_config().is_ok_and(|config| config.is_condition_canister(&condition.canister_id)) {
+        record(Err(String::from("condition canister is not allowed")));
+        return false;
+    }
+    if let Some(started_at) = get_condition_call(proposal_id) {
+        if now.saturating_sub(started_at) < CONDITION_CALL_TIMEOUT {
+            return false;
+        }
+        record(Err(String::from("condition call timed out")));
+    }
+
+    set_condition_call(proposal_id, now);
+    let reply = ic_cdk::api::call::call_raw128(
+        condition.canister_id,
+        &condition.method,
+        condition.arg.clone(),
+        0,
+    )
+    .await;
+    if !end_condition_call(proposal_id, now) {
+        // Timed out and replaced by a later call, which records its own outcome.
+        return false;
+    }
+    let result = match reply {
+        Ok(reply) => condition.is_met_by(&reply),
+        Err((code, message)) => Err(format!("{:?}: {}", code, message)),
+    };
+    let is_met = result == Ok(true);
+    record(result);
+    is_met
+}
+
 /// Force execute a proposal.
 /// This function is called by the force executor.
 #[update]
 pub async fn force_execute(proposal_id: Index) -> Result<(), ReturnError> {
     require_caller_has_role(UserRole::ForceExecutor);
+    let res = force_execute_proposal(proposal_id).await;
+    settle_deposit_if_final(proposal_id);
+    res
+}
 
+async fn force_execute_proposal(proposal_id: Index) -> Result<(), ReturnError> {
     let mut proposal = get_proposal_by_id(proposal_id)?;
     if !proposal.is_force_executable() {
         return Err(ReturnError::IncorrectProposalState);
     }
+    // A multi-option proposal has no payload to force execute before its winner is selected.
+    let payload_id = proposal
+        .execution_payload_id()
+        .ok_or(ReturnError::IncorrectProposalState)?;
+    check_payload_released(proposal_id)?;
     let _ = proposal
         .state_transition(ProposalState::ForceExecuting(ExecutionStep::new(0)))
         .map_err(|_| ReturnError::StateTransitionError)?;
@@ -361,16 +965,19 @@ pub async fn force_execute(proposal_id: Index) -> Result<(), ReturnError> {
         return Err(ReturnError::Expired);
     }
 
-    let proposal_payload = get_proposal_payload_by_id(proposal.payload_id)?;
+    let proposal_payload = get_proposal_payload_by_id(payload_id)?;
 
-    match validate_execution_dependency(proposal_payload.depends_on) {
+    match validate_execution_dependency(&proposal_payload) {
         Ok(_) => {}
-        Err(ReturnError::DependentProposalNotSucceeded) => {
+        Err(
+            e @ (ReturnError::DependentProposalNotSucceeded
+            | ReturnError::ConflictingProposalSucceeded),
+        ) => {
             let _ = proposal
                 .state_transition(ProposalState::ForceExecutionFailed(ExecutionStep::new(0)))
                 .map_err(|_| ReturnError::StateTransitionError)?;
             set_proposal_by_id(proposal_id, &proposal);
-            return Err(ReturnError::DependentProposalNotSucceeded);
+            return Err(e);
         }
         Err(_) => return Err(ReturnError::DependentProposalNotReady),
     }
@@ -407,31 +1014,20 @@ pub async fn force_execute(proposal_id: Index) -> Result<(), ReturnError> {
     Ok(())
 }
 
-fn validate_execution_dependency(deps: Vec<Index>) -> Result<(), ReturnError> {
-    for &dep in deps.iter() {
-        let dependent_proposal = get_proposal_by_id(dep)?;
-        match dependent_proposal.state {
-            ProposalState::Succeeded | ProposalState::ForceExecutionSucceeded => {}
-            ProposalState::Failed(_)
-            | ProposalState::ForceExecutionFailed(_)
-            | ProposalState::ValidationFailed
-            | ProposalState::Expired
-            | ProposalState::Rejected
-            | ProposalState::Revoked
-            | ProposalState::QuorumNotMet => {
-                return Err(ReturnError::DependentProposalNotSucceeded)
-            }
-            // caller can retry execute this proposal
-            ProposalState::Submitted
-            | ProposalState::Open
-            | ProposalState::Accepted
-            | ProposalState::Executing(_)
-            | ProposalState::ForceExecuting(_) => {
-                return Err(ReturnError::DependentProposalNotReady)
-            }
-        }
-    }
-    Ok(())
+fn validate_execution_dependency(payload: &ProposalPayload) -> Result<(), ReturnError> {
+    payload.check_dependencies(|id| get_proposal_by_id(id).map(|p| p.state))
+}
+
+/// Simulates the dependency checks of `execute` and `force_execute`, without changing the proposal state.
+/// Covers `depends_on`, `depends_on_any` and `conflicts_with`.
+/// Fails with `IncorrectProposalState` for a multi-option proposal without a winning choice.
+#[query]
+pub fn check_execution_dependencies(proposal_id: Index) -> Result<(), ReturnError> {
+    let proposal = get_proposal_by_id(proposal_id)?;
+    let payload_id = proposal
+        .execution_payload_id()
+        .ok_or(ReturnError::IncorrectProposalState)?;
+    validate_execution_dependency(&get_proposal_payload_by_id(payload_id)?)
 }
 
 /// A single `message` is executed, modifying `ExecutionStepState` of the proposal (but not `ProposalState`).
@@ -499,7 +1095,7 @@ async fn execute_message(message: &CanisterMessage, proposal_id: Index) -> Resul
     )
     .await
     .map_err(|(code, message)| (code as i32, message));
-    add_execution_step_result(proposal_id, ExecResult(exec_res.clone()));
+    add_execution_step_result(proposal_id, proposal.runs(), ExecResult(exec_res.clone()));
     match exec_res {
         Ok(_) => {
             let _ = proposal
@@ -572,14 +1168,426 @@ async fn execute_message(message: &CanisterMessage, proposal_id: Index) -> Resul
     Ok(())
 }
 
+// ==== Appeal ====
+
+/// Appeal a proposal that ended in `Rejected` or `QuorumNotMet`, for a second vote under stricter thresholds.
+/// A proposal can be appealed once, within the appeal window after the end of its voting period.
+///
+/// The caller must have the Appellant role, or be the proposer and pay the appeal deposit.
+/// Proposers must have approved the governance canister to spend the deposit amount plus the ledger fee.
+#[update]
+pub async fn appeal(proposal_id: Index) -> Result<(), ReturnError> {
+    let caller = ic_cdk::api::caller();
+    let appeal_config = get_config()?.appeal.ok_or(ReturnError::Unauthorized)?;
+    check_appealable(&get_proposal_by_id(proposal_id)?, &appeal_config)?;
+
+    let deposit = if has_role(UserRole::Appellant, caller) {
+        None
+    } else {
+        let proposal = get_proposal_by_id(proposal_id)?;
+        let deposit_config = appeal_config
+            .deposit
+            .clone()
+            .ok_or(ReturnError::Unauthorized)?;
+        if caller != proposal.proposer || caller == Principal::anonymous() {
+            return Err(ReturnError::Unauthorized);
+        }
+        Some(collect_deposit(caller, deposit_config, 1).await?)
+    };
+
+    // Checked again, since the proposal may have changed during the deposit call.
+    let mut proposal = get_proposal_by_id(proposal_id)?;
+    let reopened = check_appealable(&proposal, &appeal_config).and_then(|_| {
+        let now = ic_cdk::api::time();
+        let voting_period = appeal_config
+            .voting_period
+            .max(get_config()?.min_voting_period);
+        proposal
+            .reopen_for_appeal(
+                caller,
+                now,
+                now.saturating_add(voting_period),
+                &appeal_config.passing_threshold,
+            )
+            .map_err(|_| ReturnError::StateTransitionError)
+    });
+    if let Err(e) = reopened {
+        if let Some(deposit) = deposit {
+            ic_cdk::spawn(async move {
+                let _ = return_deposit(&deposit, DepositKind::Appeal, None, DepositOutcome::Refund)
+                    .await;
+            });
+        }
+        return Err(e);
+    }
+    set_proposal_by_id(proposal_id, &proposal);
+    set_proposal_liveness(proposal_id, &ProposalLiveness::new(ic_cdk::api::time()));
+    if let Some(deposit) = deposit {
+        set_appeal_deposit(proposal_id, &deposit);
+    }
+
+    if get_config()?.vote_manager_hook.is_some() {
+        push_timer_task(proposal_id)?;
+    }
+    Ok(())
+}
+
+fn check_appealable(proposal: &Proposal, appeal_config: &AppealConfig) -> Result<(), ReturnError> {
+    if !matches!(
+        proposal.state,
+        ProposalState::Rejected | ProposalState::QuorumNotMet
+    ) || proposal.appeal.is_some()
+    {
+        return Err(ReturnError::IncorrectProposalState);
+    }
+    if !appeal_config.is_window_open(proposal.voting_end_time, ic_cdk::api::time()) {
+        return Err(ReturnError::Expired);
+    }
+    Ok(())
+}
+
+/// Whether the proposal can still be appealed under the current config.
+fn is_appealable(proposal: &Proposal) -> bool {
+    get_config()
+        .ok()
+        .and_then(|c| c.appeal)
+        .is_some_and(|appeal_config| check_appealable(proposal, &appeal_config).is_ok())
+}
+
+// ==== Embargo ====
+
+/// Attest to the content of an embargoed payload, before it is revealed.
+/// This function is called by reviewers, who received the payload privately.
+#[update]
+pub fn attest(proposal_id: Index) -> Result<(), ReturnError> {
+    require_caller_has_role(UserRole::Reviewer);
+    let mut embargo = get_embargo(proposal_id).ok_or(ReturnError::InvalidIndex)?;
+    if get_proposal_by_id(proposal_id)?.state.is_final() {
+        return Err(ReturnError::IncorrectProposalState);
+    }
+    embargo.attest(ic_cdk::api::caller())?;
+    set_embargo(proposal_id, &embargo);
+    Ok(())
+}
+
+/// Accept or reject the payload of an embargoed proposal, before it is revealed.
+/// This function is called by the validator, which receives the payload privately and checks it against the hash.
+/// A rejected payload is never revealed nor executed.
+#[update]
+pub fn validate_embargoed_payload(proposal_id: Index, validated: bool) -> Result<(), ReturnError> {
+    require_caller_has_role(UserRole::Validator);
+    let mut embargo = get_embargo(proposal_id).ok_or(ReturnError::InvalidIndex)?;
+    if get_proposal_by_id(proposal_id)?.state.is_final() {
+        return Err(ReturnError::IncorrectProposalState);
+    }
+    embargo.validate(validated)?;
+    set_embargo(proposal_id, &embargo);
+    Ok(())
+}
+
+/// Reveal the payload of an accepted embargoed proposal, and execute it.
+/// This function is called by the executor.
+///
+/// The payload is passed candid encoded, exactly as it was hashed for the embargo. It must have been validated with
+/// `validate_embargoed_payload`, and is subject to the payload size limit of submissions.
+/// The payload replaces the placeholder payload and becomes public only once its execution starts. If the checks
+/// or the execution preconditions fail, the payload is not stored.
+/// Later runs of a standing order are executed with `execute`.
+#[update]
+pub async fn reveal_and_execute(proposal_id: Index, payload: RawBytes) -> Result<(), ReturnError> {
+    require_caller_has_role(UserRole::Executor);
+    let mut embargo = get_embargo(proposal_id).ok_or(ReturnError::InvalidIndex)?;
+    if get_config()?
+        .submission_limits
+        .is_some_and(|limits| !limits.is_payload_size_allowed(payload.len()))
+    {
+        return Err(ReturnError::SizeLimitExceeded);
+    }
+    embargo.reveal(&payload, ic_cdk::api::time())?;
+    let payload: ProposalPayload =
+        candid::decode_one(&payload).map_err(|_| ReturnError::InputError)?;
+    if !payload.is_valid()
+        || payload.has_batch_dependencies()
+        || payload.max_dependency_index() >= Some(proposal_id)
+    {
+        return Err(ReturnError::InputError);
+    }
+    let res = execute_proposal(proposal_id, Some(Reveal { payload, embargo })).await;
+    settle_deposit_if_final(proposal_id);
+    res
+}
+
+// ==== Sponsorship ====
+
+/// Starts the sponsorship phase of a new proposal, if configured.
+fn start_sponsorship(proposal_id: Index) -> Result<(), ReturnError> {
+    if let Some(config) = get_config()?.sponsorship {
+        if config.sponsors_required > 0 {
+            set_sponsorship(proposal_id, &Sponsorship::new(&config, ic_cdk::api::time()));
+        }
+    }
+    Ok(())
+}
+
+/// Sponsor a proposal in its sponsorship phase.
+/// Once enough sponsors are collected, the proposal goes to validation.
+///
+/// The caller must have the Sponsor role, or hold enough of the configured sponsorship token.
+/// Proposers cannot sponsor their own proposals.
+/// The token balance is read at call time and not locked, see `TokenSponsorship`.
+#[update]
+pub async fn sponsor(proposal_id: Index) -> Result<(), ReturnError> {
+    let caller = ic_cdk::api::caller();
+    let proposal = get_proposal_awaiting_sponsorship(proposal_id)?;
+    if caller == proposal.proposer || caller == Principal::anonymous() {
+        return Err(ReturnError::Unauthorized);
+    }
+
+    if !has_role(UserRole::Sponsor, caller) {
+        let token = get_config()?
+            .sponsorship
+            .and_then(|config| config.token)
+            .ok_or(ReturnError::Unauthorized)?;
+        if icrc1_balance_of(token.ledger, Account::from(caller)).await? < token.min_balance {
+            return Err(ReturnError::Unauthorized);
+        }
+    }
+
+    // Read again, since the proposal may have changed during the balance call.
+    get_proposal_awaiting_sponsorship(proposal_id)?;
+    let mut sponsorship = get_sponsorship(proposal_id).ok_or(ReturnError::InvalidIndex)?;
+    let now = ic_cdk::api::time();
+    if sponsorship.is_expired(now) {
+        expire_sponsorship(proposal_id)?;
+        return Err(ReturnError::Expired);
+    }
+    let reached = sponsorship.add_sponsor(caller, now)?;
+    set_sponsorship(proposal_id, &sponsorship);
+
+    if reached && get_config()?.validator_hook.is_some() {
+        push_timer_task(proposal_id)?;
+    }
+    Ok(())
+}
+
+fn get_proposal_awaiting_sponsorship(proposal_id: Index) -> Result<Proposal, ReturnError> {
+    let proposal = get_proposal_by_id(proposal_id)?;
+    if proposal.state != ProposalState::Submitted || !is_awaiting_sponsorship(proposal_id) {
+        return Err(ReturnError::IncorrectProposalState);
+    }
+    Ok(proposal)
+}
+
+/// Moves a proposal whose sponsorship deadline passed without enough sponsors to `Expired`.
+/// Anyone can call this function.
+#[update]
+pub fn expire_sponsorship(proposal_id: Index) -> Result<(), ReturnError> {
+    let mut proposal = get_proposal_by_id(proposal_id)?;
+    let sponsorship = get_sponsorship(proposal_id).ok_or(ReturnError::InvalidIndex)?;
+    if proposal.state != ProposalState::Submitted || !sponsorship.is_expired(ic_cdk::api::time()) {
+        return Err(ReturnError::IncorrectProposalState);
+    }
+    let _ = proposal
+        .state_transition(ProposalState::Expired)
+        .map_err(|_| ReturnError::StateTransitionError)?;
+    set_proposal_by_id(proposal_id, &proposal);
+
+    settle_deposit_if_final(proposal_id);
+    Ok(())
+}
+
+// ==== Supersession ====
+
+/// Moves the proposal superseded by `proposal_id` to `Superseded`, once the supersession is due.
+/// Does nothing if the superseded proposal is executing or already in a final state.
+fn supersede_if_due(proposal_id: Index) {
+    let supersession = match get_supersession(proposal_id) {
+        Some(supersession) => supersession,
+        None => return,
+    };
+    if !get_proposal_by_id(proposal_id).is_ok_and(|p| supersession.is_due(&p.state)) {
+        return;
+    }
+    let superseded_id = supersession.proposal_id;
+    let mut superseded = match get_proposal_by_id(superseded_id) {
+        Ok(superseded) => superseded,
+        Err(_) => return,
+    };
+    if superseded
+        .state_transition(ProposalState::Superseded)
+        .is_ok()
+    {
+        set_proposal_by_id(superseded_id, &superseded);
+        set_superseded_by(superseded_id, proposal_id);
+        settle_deposit_if_final(superseded_id);
+    }
+}
+
+// ==== Deposits ====
+
+/// Pulls the submission deposits of `proposals` proposals from the proposer into the escrow subaccount,
+/// in a single transfer. The returned deposit holds the total amount.
+async fn collect_deposit(
+    proposer: Principal,
+    deposit_config: DepositConfig,
+    proposals: u128,
+) -> Result<ProposalDeposit, ReturnError> {
+    let depositor = Account::from(proposer);
+    let amount = deposit_config
+        .amount
+        .checked_mul(proposals)
+        .ok_or(ReturnError::ArithmeticError)?;
+    let block_index = icrc2_transfer_from(
+        deposit_config.ledger,
+        depositor.clone(),
+        DEPOSIT_SUBACCOUNT,
+        amount,
+    )
+    .await?;
+    Ok(ProposalDeposit {
+        ledger: deposit_config.ledger,
+        depositor,
+        amount,
+        reject_slash_threshold: deposit_config.reject_slash_threshold,
+        slash_destination: deposit_config
+            .slash_destination
+            .unwrap_or_else(|| Account::from(ic_cdk::api::id())),
+        block_index,
+        status: DepositStatus::Held,
+        settled_at: None,
+    })
+}
+
+/// Transfers the deposit out of escrow, to the depositor or to the slash destination.
+/// The ledger fee is deducted from the transferred amount.
+/// The transfer is created at `deposit.settled_at` if set, so that the ledger deduplicates retries.
+/// Returns the block index, or `None` if the deposit does not cover the fee.
+async fn return_deposit(
+    deposit: &ProposalDeposit,
+    kind: DepositKind,
+    proposal_id: Option<Index>,
+    outcome: DepositOutcome,
+) -> Result<Option<u128>, ReturnError> {
+    let fee = icrc1_fee(deposit.ledger).await?;
+    if deposit.amount <= fee {
+        return Ok(None);
+    }
+    let memo = deposit.settlement_memo(kind, proposal_id, &outcome);
+    let to = match outcome {
+        DepositOutcome::Refund => deposit.depositor.clone(),
+        DepositOutcome::Slash => deposit.slash_destination.clone(),
+    };
+    let created_at_time = deposit.settled_at.unwrap_or_else(ic_cdk::api::time);
+    icrc1_transfer(
+        deposit.ledger,
+        DEPOSIT_SUBACCOUNT,
+        to,
+        deposit.amount - fee,
+        memo,
+        created_at_time,
+    )
+    .await
+    .map(Some)
+}
+
+/// Settles the deposits of a proposal in the background, once their outcome is known.
+fn settle_deposit_if_final(proposal_id: Index) {
+    let proposal = match get_proposal_by_id(proposal_id) {
+        Ok(proposal) => proposal,
+        Err(_) => return,
+    };
+    let appealable = is_appealable(&proposal);
+    for kind in [DepositKind::Submission, DepositKind::Appeal] {
+        let is_due = get_deposit(kind, proposal_id).is_some_and(|d| {
+            d.status == DepositStatus::Held && d.outcome_for(kind, &proposal, appealable).is_some()
+        });
+        if is_due {
+            ic_cdk::spawn(async move {
+                let _ = settle(kind, proposal_id).await;
+            });
+        }
+    }
+}
+
+fn get_deposit(kind: DepositKind, proposal_id: Index) -> Option<ProposalDeposit> {
+    match kind {
+        DepositKind::Submission => get_proposal_deposit(proposal_id),
+        DepositKind::Appeal => get_appeal_deposit(proposal_id),
+    }
+}
+
+fn set_deposit(kind: DepositKind, proposal_id: Index, deposit: &ProposalDeposit) {
+    match kind {
+        DepositKind::Submission => set_proposal_deposit(proposal_id, deposit),
+        DepositKind::Appeal => set_appeal_deposit(proposal_id, deposit),
+    }
+}
+
+/// Refunds or slashes the deposit of a proposal in a final state.
+/// Anyone can call this function, to retry a settlement that failed,
+/// or to settle the deposit of a rejected proposal once its appeal window has closed.
+#[update]
+pub async fn settle_deposit(proposal_id: Index) -> Result<DepositStatus, ReturnError> {
+    settle(DepositKind::Submission, proposal_id).await
+}
+
+/// Refunds or slashes the appeal deposit of a proposal whose appeal round has ended.
+/// Anyone can call this function, to retry a settlement that failed.
+#[update]
+pub async fn settle_appeal_deposit(proposal_id: Index) -> Result<DepositStatus, ReturnError> {
+    settle(DepositKind::Appeal, proposal_id).await
+}
+
+async fn settle(kind: DepositKind, proposal_id: Index) -> Result<DepositStatus, ReturnError> {
+    let mut deposit = get_deposit(kind, proposal_id).ok_or(ReturnError::InvalidIndex)?;
+    if deposit.status != DepositStatus::Held {
+        return Ok(deposit.status);
+    }
+    let proposal = get_proposal_by_id(proposal_id)?;
+    let outcome = deposit
+        .outcome_for(kind, &proposal, is_appealable(&proposal))
+        .ok_or(ReturnError::IncorrectProposalState)?;
+
+    // Prevents concurrent settlements while the transfer is in flight.
+    deposit.status = DepositStatus::Settling;
+    deposit.settled_at.get_or_insert_with(ic_cdk::api::time);
+    set_deposit(kind, proposal_id, &deposit);
+
+    match return_deposit(&deposit, kind, Some(proposal_id), outcome.clone()).await {
+        Ok(block_index) => {
+            deposit.status = match outcome {
+                DepositOutcome::Refund => DepositStatus::Refunded { block_index },
+                DepositOutcome::Slash => DepositStatus::Slashed { block_index },
+            };
+            set_deposit(kind, proposal_id, &deposit);
+            Ok(deposit.status)
+        }
+        Err(e) => {
+            deposit.status = DepositStatus::Held;
+            if e == ReturnError::Expired {
+                // The ledger no longer deduplicates against the first attempt, which did not go through
+                // within its window. The next attempt starts a new window.
+                deposit.settled_at = None;
+            }
+            set_deposit(kind, proposal_id, &deposit);
+            Err(e)
+        }
+    }
+}
+
 // ==== Settings ====
 
 #[update]
 pub fn update_config(config: Config) -> Result<(), ReturnError> {
     require_caller_has_role(UserRole::Admin);
+    if !config.is_emergency_config_valid() {
+        return Err(ReturnError::InputError);
+    }
     CONFIG
         .with(|c| c.borrow_mut().set(Cbor(Some(config))))
         .map_err(|_| ReturnError::MemoryError)?;
+    restart_auto_executor();
+    restart_liveness_monitor();
     Ok(())
 }
 
@@ -1,12 +1,18 @@
+use crate::deposit::*;
+use crate::embargo::*;
 use crate::execution::*;
+use crate::liveness::*;
 use crate::proposal::*;
+use crate::sponsor::*;
 use crate::storage::*;
 use crate::types::*;
 
+use candid::Principal;
 use ic_cdk_macros::query;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, StableLog, StableVec};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
 
 const CONFIG_PAGE_SIZE: u64 = 1;
 
@@ -23,9 +29,31 @@ const REVOKER_ROLES_MEM_ID: MemoryId = MemoryId::new(3);
 const EXECUTOR_ROLES_MEM_ID: MemoryId = MemoryId::new(4);
 const FORCE_EXECUTOR_ROLES_MEM_ID: MemoryId = MemoryId::new(5);
 const VALIDATOR_ROLES_MEM_ID: MemoryId = MemoryId::new(6);
-const PROPOSALS_MEM_ID: MemoryId = MemoryId::new(7);
-const PROPOSAL_EXEC_MEM_ID: MemoryId = MemoryId::new(8);
+const PROPOSALS_V0_MEM_ID: MemoryId = MemoryId::new(7);
+const PROPOSAL_EXEC_V0_MEM_ID: MemoryId = MemoryId::new(8);
 const TIMER_TASKS_MEM_ID: MemoryId = MemoryId::new(9);
+const PROPOSAL_DEPOSITS_MEM_ID: MemoryId = MemoryId::new(10);
+const SUBMISSION_WINDOWS_MEM_ID: MemoryId = MemoryId::new(11);
+const PENDING_PROPOSALS_MEM_ID: MemoryId = MemoryId::new(12);
+const ACTIVATION_CONDITIONS_MEM_ID: MemoryId = MemoryId::new(13);
+const CONDITION_EVALUATIONS_MEM_ID: MemoryId = MemoryId::new(14);
+const SUPERSESSIONS_MEM_ID: MemoryId = MemoryId::new(15);
+const SUPERSEDED_BY_MEM_ID: MemoryId = MemoryId::new(16);
+const SPONSOR_ROLES_MEM_ID: MemoryId = MemoryId::new(17);
+const SPONSORSHIPS_MEM_ID: MemoryId = MemoryId::new(18);
+const APPELLANT_ROLES_MEM_ID: MemoryId = MemoryId::new(19);
+const APPEAL_DEPOSITS_MEM_ID: MemoryId = MemoryId::new(20);
+const REVIEWER_ROLES_MEM_ID: MemoryId = MemoryId::new(21);
+const EMBARGOES_MEM_ID: MemoryId = MemoryId::new(22);
+const TALLY_HISTORY_MEM_ID: MemoryId = MemoryId::new(23);
+const VOTE_MANAGER_REPORTS_MEM_ID: MemoryId = MemoryId::new(24);
+const PROPOSAL_LIVENESS_MEM_ID: MemoryId = MemoryId::new(25);
+const PROPOSALS_MEM_ID: MemoryId = MemoryId::new(26);
+const PROPOSAL_EXEC_MEM_ID: MemoryId = MemoryId::new(27);
+const STORAGE_VERSION_MEM_ID: MemoryId = MemoryId::new(28);
+
+/// Version of the stable memory layout. Bumped by each migration in `migrate_storage`.
+const STORAGE_VERSION: u32 = 1;
 
 const PROPOSAL_METADATA_LOG_INDEX_MEM_ID: MemoryId = MemoryId::new(60);
 const PROPOSAL_METADATA_LOG_DATA_MEM_ID: MemoryId = MemoryId::new(61);
@@ -48,10 +76,25 @@ thread_local! {
                 voting_may_end_early: true,
                 validator_hook: None,
                 vote_manager_hook: None,
+                deposit: None,
+                submission_limits: None,
+                auto_execute_interval: None,
+                sponsorship: None,
+                appeal: None,
+                emergency: None,
+                liveness: None,
+                condition_canisters: None,
             })),
         ).expect("Failed to initialize config")
     );
 
+    // Pending proposal slots of submissions waiting for their deposit.
+    // Kept on the heap, since no call is in flight across an upgrade.
+    static PENDING_RESERVATIONS: Cell<u64> = Cell::new(0);
+
+    // Start times of the activation condition calls in flight, by proposal index. Kept on the heap, like the reservations.
+    static CONDITION_CALLS: RefCell<BTreeMap<Index, TimeNs>> = RefCell::new(BTreeMap::new());
+
     // Managed stable memory
     pub static MEMORY_MANAGER: RefCell<MemoryManager<RM>> = RefCell::new(
         MemoryManager::init(RM::new(DefaultMemoryImpl::default(), MM_PAGE_START..u64::MAX/65536-1))
@@ -99,12 +142,43 @@ thread_local! {
                 mm.borrow().get(VALIDATOR_ROLES_MEM_ID)).expect("init failed"))
     });
 
+    pub static SPONSOR_ROLES: RefCell<StableVec<StablePrincipal, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableVec::init(
+                mm.borrow().get(SPONSOR_ROLES_MEM_ID)).expect("init failed"))
+    });
+
+    pub static APPELLANT_ROLES: RefCell<StableVec<StablePrincipal, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableVec::init(
+                mm.borrow().get(APPELLANT_ROLES_MEM_ID)).expect("init failed"))
+    });
+
+    pub static REVIEWER_ROLES: RefCell<StableVec<StablePrincipal, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableVec::init(
+                mm.borrow().get(REVIEWER_ROLES_MEM_ID)).expect("init failed"))
+    });
+
+    pub static STORAGE_VERSION_CELL: RefCell<StableCell<u32, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableCell::init(
+                mm.borrow().get(STORAGE_VERSION_MEM_ID), 0).expect("init failed"))
+    });
+
     pub static PROPOSALS: RefCell<StableVec<Proposal, VM>> =
         MEMORY_MANAGER.with(|mm| {
             RefCell::new(StableVec::init(
                 mm.borrow().get(PROPOSALS_MEM_ID)).expect("init failed"))
     });
 
+    // Proposals in the original layout. Copied by the migration to storage version 1, and left in place.
+    pub static PROPOSALS_V0: RefCell<StableVec<ProposalV0, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableVec::init(
+                mm.borrow().get(PROPOSALS_V0_MEM_ID)).expect("init failed"))
+    });
+
     pub static PROPOSAL_METADATA: RefCell<StableLog<ProposalMetadata, VM, VM>> =
         MEMORY_MANAGER.with(|mm| {
             RefCell::new(StableLog::init(
@@ -119,12 +193,20 @@ thread_local! {
                 mm.borrow().get(PROPOSAL_PAYLOAD_LOG_DATA_MEM_ID)).expect("init failed"))
     });
 
-    pub static PROPOSAL_EXEC: RefCell<StableBTreeMap<Index, ProposalExec, VM>> =
+    // map of (proposal index, run) to the execution result of that run
+    pub static PROPOSAL_EXEC: RefCell<StableBTreeMap<(Index, u64), ProposalExec, VM>> =
         MEMORY_MANAGER.with(|mm| {
             RefCell::new(StableBTreeMap::init(
                 mm.borrow().get(PROPOSAL_EXEC_MEM_ID)))
     });
 
+    // Execution results keyed by proposal index only. Emptied by the migration to storage version 1.
+    pub static PROPOSAL_EXEC_V0: RefCell<StableBTreeMap<Index, ProposalExec, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(PROPOSAL_EXEC_V0_MEM_ID)))
+    });
+
     pub static PROPOSAL_REVOKE: RefCell<StableLog<ProposalRevoke, VM, VM>> =
         MEMORY_MANAGER.with(|mm| {
             RefCell::new(StableLog::init(
@@ -132,6 +214,97 @@ thread_local! {
                 mm.borrow().get(PROPOSAL_REVOKE_LOG_DATA_MEM_ID)).expect("init failed"))
     });
 
+    // map of proposal index to its submission deposit
+    pub static PROPOSAL_DEPOSITS: RefCell<StableBTreeMap<Index, ProposalDeposit, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(PROPOSAL_DEPOSITS_MEM_ID)))
+    });
+
+    // map of proposal index to its appeal deposit
+    pub static APPEAL_DEPOSITS: RefCell<StableBTreeMap<Index, ProposalDeposit, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(APPEAL_DEPOSITS_MEM_ID)))
+    });
+
+    // map of proposer to its submission rate limit window
+    pub static SUBMISSION_WINDOWS: RefCell<StableBTreeMap<StablePrincipal, SubmissionWindow, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(SUBMISSION_WINDOWS_MEM_ID)))
+    });
+
+    // set of proposal indexes that have not reached a final state
+    pub static PENDING_PROPOSALS: RefCell<StableBTreeMap<Index, (), VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(PENDING_PROPOSALS_MEM_ID)))
+    });
+
+    // map of proposal index to its activation condition
+    pub static ACTIVATION_CONDITIONS: RefCell<StableBTreeMap<Index, ActivationCondition, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(ACTIVATION_CONDITIONS_MEM_ID)))
+    });
+
+    // map of (proposal index, evaluation number) to the activation condition evaluation outcome
+    pub static CONDITION_EVALUATIONS: RefCell<StableBTreeMap<(Index, u64), ConditionEvaluation, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(CONDITION_EVALUATIONS_MEM_ID)))
+    });
+
+    // map of proposal index to the earlier proposal it supersedes
+    pub static SUPERSESSIONS: RefCell<StableBTreeMap<Index, Supersession, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(SUPERSESSIONS_MEM_ID)))
+    });
+
+    // map of superseded proposal index to the proposal that superseded it
+    pub static SUPERSEDED_BY: RefCell<StableBTreeMap<Index, Index, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(SUPERSEDED_BY_MEM_ID)))
+    });
+
+    // map of proposal index to its sponsorship
+    pub static SPONSORSHIPS: RefCell<StableBTreeMap<Index, Sponsorship, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(SPONSORSHIPS_MEM_ID)))
+    });
+
+    // map of (proposal index, update number) to the running totals after that tally update
+    pub static TALLY_HISTORY: RefCell<StableBTreeMap<(Index, u64), TallyRecord, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(TALLY_HISTORY_MEM_ID)))
+    });
+
+    // map of vote manager to the time of its last report
+    pub static VOTE_MANAGER_REPORTS: RefCell<StableBTreeMap<StablePrincipal, TimeNs, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(VOTE_MANAGER_REPORTS_MEM_ID)))
+    });
+
+    // map of proposal index to the report times of its current voting round
+    pub static PROPOSAL_LIVENESS: RefCell<StableBTreeMap<Index, ProposalLiveness, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(PROPOSAL_LIVENESS_MEM_ID)))
+    });
+
+    // map of proposal index to its embargoed payload commitment
+    pub static EMBARGOES: RefCell<StableBTreeMap<Index, EmbargoedPayload, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(
+                mm.borrow().get(EMBARGOES_MEM_ID)))
+    });
+
     // Proposal IDs that have push notifications. LIFO.
     pub static TIMER_TASKS: RefCell<StableVec<Index, VM>> =
         MEMORY_MANAGER.with(|mm| {
@@ -159,16 +332,70 @@ pub(crate) fn config_is_initialized() -> bool {
     CONFIG.with(|c| c.borrow().get().0.clone().unwrap().initialized)
 }
 
+// ==== Migrations ====
+/// Number of proposals migrated per message.
+const MIGRATION_BATCH_SIZE: u64 = 1_000;
+
+/// Migrates the stable memory from the layout of an older version, by one batch of proposals.
+/// Returns whether the migration is complete. Runs on every upgrade, then in later messages until complete.
+pub(crate) fn migrate_storage() -> bool {
+    let version = STORAGE_VERSION_CELL.with(|v| *v.borrow().get());
+    if version < 1 && !migrate_to_v1(MIGRATION_BATCH_SIZE) {
+        return false;
+    }
+    if version < STORAGE_VERSION {
+        STORAGE_VERSION_CELL.with(|v| v.borrow_mut().set(STORAGE_VERSION).expect("set failed"));
+    }
+    true
+}
+
+/// Moves up to `limit` proposals and their execution results out of the original layouts,
+/// which cannot hold the added proposal fields or more than one run per proposal.
+/// The original execution result of a proposal becomes its first run.
+/// Proposals keep their IDs, so the migrated proposals are the cursor of the migration. The original proposals
+/// are left in place, since removing them would not be bounded. Returns whether all proposals are migrated.
+fn migrate_to_v1(limit: u64) -> bool {
+    let migrated = PROPOSALS.with(|p| p.borrow().len());
+    let count = PROPOSALS_V0.with(|p| p.borrow().len());
+    for proposal_id in migrated..count.min(migrated.saturating_add(limit)) {
+        let proposal = PROPOSALS_V0.with(|p| p.borrow().get(proposal_id).unwrap().decode());
+        let new_id = push_proposal(&proposal).expect("proposal migration failed");
+        assert_eq!(new_id, proposal_id, "proposal migration out of order");
+        if let Some(exec) = PROPOSAL_EXEC_V0.with(|p| p.borrow_mut().remove(&proposal_id)) {
+            PROPOSAL_EXEC.with(|p| p.borrow_mut().insert((proposal_id, 0), exec));
+        }
+    }
+    PROPOSALS.with(|p| p.borrow().len()) >= count
+}
+
+/// Whether proposals in the original layout remain to be migrated.
+/// Until they are, they cannot be changed and no proposal can be added.
+fn is_migrating() -> bool {
+    STORAGE_VERSION_CELL.with(|v| *v.borrow().get()) < 1
+        && PROPOSALS.with(|p| p.borrow().len()) < PROPOSALS_V0.with(|p| p.borrow().len())
+}
+
 // ==== Proposals ====
+/// Returns the IDs and voting end times of the proposals in a voting round, including appeal rounds.
 #[query]
 pub fn get_all_open_proposal_ids_with_expiration() -> Vec<(Index, TimeNs)> {
+    get_all_open_proposal_ids_with_expiration_and_class()
+        .into_iter()
+        .map(|(id, voting_end_time, _)| (id, voting_end_time))
+        .collect()
+}
+
+/// Returns the IDs, voting end times and classes of the proposals in a voting round, including appeal rounds.
+#[query]
+pub fn get_all_open_proposal_ids_with_expiration_and_class() -> Vec<(Index, TimeNs, ProposalClass)>
+{
     PROPOSALS.with(|p| {
         p.borrow()
             .iter()
             .enumerate()
             .filter_map(|(i, p)| {
-                if p.state == ProposalState::Open {
-                    Some((i as Index, p.voting_end_time.unwrap()))
+                if p.is_in_voting() {
+                    Some((i as Index, p.voting_end_time.unwrap(), p.class()))
                 } else {
                     None
                 }
@@ -177,15 +404,27 @@ pub fn get_all_open_proposal_ids_with_expiration() -> Vec<(Index, TimeNs)> {
     })
 }
 
+/// Returns the IDs of the submitted proposals that are ready for validation.
+/// Proposals still collecting sponsors are not included.
 #[query]
 pub fn get_all_submitted_proposal_ids() -> Vec<Index> {
+    get_all_submitted_proposal_ids_with_class()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// Returns the IDs and classes of the submitted proposals that are ready for validation.
+/// Proposals still collecting sponsors are not included.
+#[query]
+pub fn get_all_submitted_proposal_ids_with_class() -> Vec<(Index, ProposalClass)> {
     PROPOSALS.with(|p| {
         p.borrow()
             .iter()
             .enumerate()
             .filter_map(|(i, p)| {
-                if p.state == ProposalState::Submitted {
-                    Some(i as Index)
+                if p.state == ProposalState::Submitted && !is_awaiting_sponsorship(i as Index) {
+                    Some((i as Index, p.class()))
                 } else {
                     None
                 }
@@ -196,40 +435,163 @@ pub fn get_all_submitted_proposal_ids() -> Vec<Index> {
 
 #[query]
 pub fn get_proposal_states(skip: Index, take: Index) -> Vec<ProposalState> {
+    get_proposal_states_with_class(skip, take)
+        .into_iter()
+        .map(|(state, _)| state)
+        .collect()
+}
+
+/// Returns the states and classes of a range of proposals.
+#[query]
+pub fn get_proposal_states_with_class(
+    skip: Index,
+    take: Index,
+) -> Vec<(ProposalState, ProposalClass)> {
     PROPOSALS.with(|p| {
         p.borrow()
             .iter()
             .skip(skip as usize)
             .take(take as usize)
-            .map(|p| p.state.clone())
+            .map(|p| (p.state.clone(), p.class()))
             .collect()
     })
 }
 
+/// Proposals that are not migrated yet count as well, see `migrate_storage`.
 #[query]
 pub fn get_next_proposal_id() -> Index {
-    PROPOSALS.with(|p| p.borrow().len() as Index)
+    match is_migrating() {
+        true => PROPOSALS_V0.with(|p| p.borrow().len()),
+        false => PROPOSALS.with(|p| p.borrow().len()),
+    }
 }
 
+/// Proposals that are not migrated yet are read from the original layout.
 #[query]
 pub fn get_proposal(proposal_id: Index) -> Option<Proposal> {
-    PROPOSALS.with(|p| p.borrow().get(proposal_id))
+    PROPOSALS
+        .with(|p| p.borrow().get(proposal_id))
+        .or_else(|| match is_migrating() {
+            true => PROPOSALS_V0.with(|p| p.borrow().get(proposal_id).map(|p| p.decode())),
+            false => None,
+        })
 }
 
+/// Fails with `MigrationInProgress` for proposals that are not migrated yet.
 pub(crate) fn get_proposal_by_id(proposal_id: Index) -> Result<Proposal, ReturnError> {
-    PROPOSALS.with(|p| p.borrow().get(proposal_id).ok_or(ReturnError::InvalidIndex))
+    PROPOSALS
+        .with(|p| p.borrow().get(proposal_id))
+        .ok_or_else(|| match proposal_id < get_next_proposal_id() {
+            true => ReturnError::MigrationInProgress,
+            false => ReturnError::InvalidIndex,
+        })
 }
 
+/// Fails with `MigrationInProgress` until all proposals are migrated, since the new proposal would take the ID
+/// of a proposal that is not migrated yet.
 pub(crate) fn add_proposal(proposal: &Proposal) -> Result<u64, ReturnError> {
-    PROPOSALS.with(|p| {
+    if is_migrating() {
+        return Err(ReturnError::MigrationInProgress);
+    }
+    push_proposal(proposal)
+}
+
+fn push_proposal(proposal: &Proposal) -> Result<u64, ReturnError> {
+    let proposal_id = PROPOSALS.with(|p| {
         let p = p.borrow_mut();
         p.push(proposal).map_err(|_| ReturnError::MemoryError)?;
         Ok(p.len() - 1)
-    })
+    })?;
+    update_pending_proposal(proposal_id, proposal);
+    Ok(proposal_id)
+}
+
+/// Removes the last proposals, from `proposal_id` on. Only for proposals written in the current message,
+/// before anything else refers to them.
+pub(crate) fn remove_proposals_from(proposal_id: Index) {
+    PROPOSALS.with(|p| {
+        let p = p.borrow_mut();
+        while p.len() > proposal_id {
+            p.pop();
+            PENDING_PROPOSALS.with(|pending| pending.borrow_mut().remove(&p.len()));
+        }
+    });
 }
 
 pub(crate) fn set_proposal_by_id(proposal_id: Index, proposal: &Proposal) {
-    PROPOSALS.with(|p| p.borrow_mut().set(proposal_id, proposal))
+    PROPOSALS.with(|p| p.borrow_mut().set(proposal_id, proposal));
+    update_pending_proposal(proposal_id, proposal);
+}
+
+// ==== PendingProposals ====
+fn update_pending_proposal(proposal_id: Index, proposal: &Proposal) {
+    PENDING_PROPOSALS.with(|p| match proposal.state.is_final() {
+        true => p.borrow_mut().remove(&proposal_id),
+        false => p.borrow_mut().insert(proposal_id, ()),
+    });
+}
+
+#[query]
+pub fn get_pending_proposal_count() -> u64 {
+    PENDING_PROPOSALS.with(|p| p.borrow().len())
+}
+
+pub(crate) fn get_pending_proposal_ids() -> Vec<Index> {
+    PENDING_PROPOSALS.with(|p| p.borrow().iter().map(|(id, _)| id).collect())
+}
+
+// ==== SubmissionWindows ====
+/// Counts submissions towards the proposer's rate limit. Fails without writing if the limit is reached.
+/// Returns the start time of the window the submissions were counted in.
+pub(crate) fn count_submissions(
+    proposer: Principal,
+    limits: &SubmissionLimits,
+    now: TimeNs,
+    submissions: u64,
+) -> Result<TimeNs, ReturnError> {
+    SUBMISSION_WINDOWS.with(|w| {
+        let key = StablePrincipal::from(proposer);
+        let window = limits.count_submissions(w.borrow().get(&key), now, submissions)?;
+        w.borrow_mut().insert(key, window);
+        Ok(window.0)
+    })
+}
+
+/// Takes back submissions counted by `count_submissions` that were not written.
+/// Nothing changes if the window that counted them has already been replaced.
+pub(crate) fn uncount_submissions(proposer: Principal, window_start: TimeNs, submissions: u64) {
+    SUBMISSION_WINDOWS.with(|w| {
+        let key = StablePrincipal::from(proposer);
+        let window = w.borrow().get(&key);
+        if let Some((start, count)) = window.filter(|(start, _)| *start == window_start) {
+            w.borrow_mut()
+                .insert(key, (start, count.saturating_sub(submissions)));
+        }
+    })
+}
+
+// ==== PendingReservations ====
+/// Reserves pending proposal slots for submissions that are not written yet.
+/// Fails if the written and reserved pending proposals would exceed `max_pending_proposals`.
+pub(crate) fn reserve_pending_proposals(
+    max_pending_proposals: Option<u64>,
+    submissions: u64,
+) -> Result<(), ReturnError> {
+    PENDING_RESERVATIONS.with(|r| {
+        let reserved = r.get().saturating_add(submissions);
+        if max_pending_proposals
+            .is_some_and(|max| get_pending_proposal_count().saturating_add(reserved) > max)
+        {
+            return Err(ReturnError::PendingProposalLimitExceeded);
+        }
+        r.set(reserved);
+        Ok(())
+    })
+}
+
+/// Releases slots reserved by `reserve_pending_proposals`, once the submissions are written or have failed.
+pub(crate) fn release_pending_proposals(submissions: u64) {
+    PENDING_RESERVATIONS.with(|r| r.set(r.get().saturating_sub(submissions)));
 }
 
 // ==== ProposalMetadata ====
@@ -255,9 +617,30 @@ pub(crate) fn add_proposal_payload(payload: &ProposalPayload) -> Result<u64, Ret
     })
 }
 
+/// Returns the submitted payload of a proposal, which is the first choice of a multi-option proposal.
 #[query]
 pub fn get_proposal_payload(proposal_id: Index) -> Option<ProposalPayload> {
-    PROPOSAL_PAYLOAD.with(|p| p.borrow().get(proposal_id))
+    // Multi-option proposals store several payloads, so payload ids differ from proposal ids.
+    let proposal = get_proposal_by_id(proposal_id).ok()?;
+    PROPOSAL_PAYLOAD.with(|p| p.borrow().get(proposal.payload_id))
+}
+
+/// Returns the candidate payloads of a proposal, in choice order.
+/// A proposal with a single payload has one choice.
+#[query]
+pub fn get_proposal_choice_payloads(proposal_id: Index) -> Vec<ProposalPayload> {
+    let proposal = match get_proposal_by_id(proposal_id) {
+        Ok(proposal) => proposal,
+        Err(_) => return vec![],
+    };
+    let payload_ids = match proposal.choices {
+        Some(choices) => choices.payload_ids,
+        None => vec![proposal.payload_id],
+    };
+    PROPOSAL_PAYLOAD.with(|p| {
+        let p = p.borrow();
+        payload_ids.iter().filter_map(|id| p.get(*id)).collect()
+    })
 }
 
 pub(crate) fn get_proposal_payload_by_id(
@@ -290,30 +673,255 @@ pub fn get_proposal_revoke(revoke_id: Index) -> Result<ProposalRevoke, ReturnErr
     })
 }
 
+/// Returns the last entry of `id` in a map keyed by `(id, sequence number)`.
+/// Seeks from the end of the range instead of iterating it, which is linear in the number of entries.
+fn last_entry_of<V: ic_stable_structures::Storable>(
+    map: &StableBTreeMap<(Index, u64), V, VM>,
+    id: Index,
+) -> Option<(u64, V)> {
+    map.iter_upper_bound(&(id + 1, 0))
+        .next()
+        .filter(|((i, _), _)| *i == id)
+        .map(|((_, n), v)| (n, v))
+}
+
 // ==== ProposalExec ====
 #[allow(dead_code)]
-pub(crate) fn set_execution_result(id: Index, proposal_exe_result: ProposalExec) {
-    PROPOSAL_EXEC.with(|p| p.borrow_mut().insert(id, proposal_exe_result));
+pub(crate) fn set_execution_result(id: Index, run: u64, proposal_exe_result: ProposalExec) {
+    PROPOSAL_EXEC.with(|p| p.borrow_mut().insert((id, run), proposal_exe_result));
 }
 
-pub(crate) fn add_execution_step_result(id: Index, step_result: ExecResult) {
+pub(crate) fn add_execution_step_result(id: Index, run: u64, step_result: ExecResult) {
     PROPOSAL_EXEC.with(|p| {
-        let mut proposal_exe_result = p.borrow_mut().get(&id).unwrap_or_default();
+        let mut proposal_exe_result = p.borrow_mut().get(&(id, run)).unwrap_or_default();
         proposal_exe_result.execution_result.push(step_result);
-        p.borrow_mut().insert(id, proposal_exe_result);
+        p.borrow_mut().insert((id, run), proposal_exe_result);
     });
 }
 
+/// Returns the execution result of the latest run of a proposal.
 #[query]
 pub fn get_proposal_execution_result(id: Index) -> Result<ProposalExec, ReturnError> {
     PROPOSAL_EXEC.with(|p| {
-        p.borrow()
-            .get(&id)
+        last_entry_of(&p.borrow(), id)
+            .map(|(_, exec)| exec)
             .ok_or(ReturnError::InvalidIndex)
-            .map(|p| p.clone())
     })
 }
 
+/// Returns the execution results of all runs of a proposal, ordered by run.
+/// Only standing orders have more than one run.
+#[query]
+pub fn get_proposal_execution_results(id: Index) -> Vec<ProposalExec> {
+    PROPOSAL_EXEC.with(|p| {
+        p.borrow()
+            .range((id, 0)..=(id, u64::MAX))
+            .map(|(_, exec)| exec)
+            .collect()
+    })
+}
+
+// ==== ActivationCondition ====
+pub(crate) fn set_activation_condition(proposal_id: Index, condition: &ActivationCondition) {
+    ACTIVATION_CONDITIONS.with(|c| c.borrow_mut().insert(proposal_id, condition.clone()));
+}
+
+#[query]
+pub fn get_activation_condition(proposal_id: Index) -> Option<ActivationCondition> {
+    ACTIVATION_CONDITIONS.with(|c| c.borrow().get(&proposal_id))
+}
+
+/// Records an evaluation outcome, unless it repeats the previous outcome of the proposal.
+/// The log therefore only grows when the outcome changes, however often the condition is polled.
+pub(crate) fn add_condition_evaluation(proposal_id: Index, evaluation: ConditionEvaluation) {
+    CONDITION_EVALUATIONS.with(|e| {
+        let last = last_entry_of(&e.borrow(), proposal_id);
+        let next = match last {
+            Some((_, previous)) if previous.result == evaluation.result => return,
+            Some((n, _)) => n + 1,
+            None => 0,
+        };
+        e.borrow_mut().insert((proposal_id, next), evaluation);
+    });
+}
+
+pub(crate) fn get_condition_call(proposal_id: Index) -> Option<TimeNs> {
+    CONDITION_CALLS.with(|c| c.borrow().get(&proposal_id).copied())
+}
+
+pub(crate) fn set_condition_call(proposal_id: Index, started_at: TimeNs) {
+    CONDITION_CALLS.with(|c| c.borrow_mut().insert(proposal_id, started_at));
+}
+
+/// Ends the condition call that started at `started_at`.
+/// Returns false if a later call has replaced it, after it timed out.
+pub(crate) fn end_condition_call(proposal_id: Index, started_at: TimeNs) -> bool {
+    CONDITION_CALLS.with(|c| {
+        let mut calls = c.borrow_mut();
+        let is_current = calls.get(&proposal_id) == Some(&started_at);
+        if is_current {
+            calls.remove(&proposal_id);
+        }
+        is_current
+    })
+}
+
+/// Returns a range of the evaluation outcomes of the activation condition of a proposal, oldest first.
+/// Consecutive identical outcomes are recorded once, with the time they were first seen.
+#[query]
+pub fn get_activation_condition_evaluations(
+    proposal_id: Index,
+    skip: u64,
+    take: u64,
+) -> Vec<ConditionEvaluation> {
+    CONDITION_EVALUATIONS.with(|e| {
+        e.borrow()
+            .range((proposal_id, skip)..=(proposal_id, u64::MAX))
+            .take(take as usize)
+            .map(|(_, evaluation)| evaluation)
+            .collect()
+    })
+}
+
+// ==== TallyHistory ====
+pub(crate) fn add_tally_record(proposal_id: Index, record: TallyRecord) {
+    TALLY_HISTORY.with(|h| {
+        let next = last_entry_of(&h.borrow(), proposal_id).map_or(0, |(n, _)| n + 1);
+        h.borrow_mut().insert((proposal_id, next), record);
+    });
+}
+
+/// Returns a range of the tally updates of a proposal, oldest first.
+#[query]
+pub fn get_tally_history(proposal_id: Index, skip: u64, take: u64) -> Vec<TallyRecord> {
+    TALLY_HISTORY.with(|h| {
+        h.borrow()
+            .range((proposal_id, skip)..=(proposal_id, u64::MAX))
+            .take(take as usize)
+            .map(|(_, record)| record)
+            .collect()
+    })
+}
+
+// ==== Liveness ====
+pub(crate) fn set_vote_manager_report(vote_manager: Principal, now: TimeNs) {
+    VOTE_MANAGER_REPORTS.with(|r| {
+        r.borrow_mut()
+            .insert(StablePrincipal::from(vote_manager), now)
+    });
+}
+
+/// Returns the time of the last report of every vote manager that reported at least once.
+#[query]
+pub fn get_vote_manager_reports() -> Vec<(Principal, TimeNs)> {
+    VOTE_MANAGER_REPORTS.with(|r| r.borrow().iter().map(|(p, t)| (p.into(), t)).collect())
+}
+
+pub(crate) fn set_proposal_liveness(proposal_id: Index, liveness: &ProposalLiveness) {
+    PROPOSAL_LIVENESS.with(|l| l.borrow_mut().insert(proposal_id, liveness.clone()));
+}
+
+/// Returns the report times of the current voting round of a proposal.
+/// Proposals that opened before liveness tracking was added have none.
+#[query]
+pub fn get_proposal_liveness(proposal_id: Index) -> Option<ProposalLiveness> {
+    PROPOSAL_LIVENESS.with(|l| l.borrow().get(&proposal_id))
+}
+
+/// Returns the IDs and last activity times of the proposals in voting that no vote manager reported on recently.
+/// Empty if `Config.liveness` is not set.
+#[query]
+pub fn get_stale_proposals() -> Vec<(Index, TimeNs)> {
+    let config = match get_config().ok().and_then(|c| c.liveness) {
+        Some(config) => config,
+        None => return vec![],
+    };
+    let now = ic_cdk::api::time();
+    get_pending_proposal_ids()
+        .into_iter()
+        .filter_map(|id| {
+            let proposal = get_proposal_by_id(id).ok().filter(|p| p.is_in_voting())?;
+            let liveness = get_proposal_liveness(id)
+                .unwrap_or_else(|| ProposalLiveness::new(proposal.created_at));
+            config
+                .is_stale(&liveness, now)
+                .then_some((id, liveness.last_activity()))
+        })
+        .collect()
+}
+
+// ==== ProposalDeposit ====
+pub(crate) fn set_proposal_deposit(proposal_id: Index, deposit: &ProposalDeposit) {
+    PROPOSAL_DEPOSITS.with(|p| p.borrow_mut().insert(proposal_id, deposit.clone()));
+}
+
+#[query]
+pub fn get_proposal_deposit(proposal_id: Index) -> Option<ProposalDeposit> {
+    PROPOSAL_DEPOSITS.with(|p| p.borrow().get(&proposal_id))
+}
+
+// ==== Supersession ====
+pub(crate) fn set_supersession(proposal_id: Index, supersession: &Supersession) {
+    SUPERSESSIONS.with(|s| s.borrow_mut().insert(proposal_id, supersession.clone()));
+}
+
+/// Returns the earlier proposal that a proposal supersedes, or is set to supersede.
+#[query]
+pub fn get_supersession(proposal_id: Index) -> Option<Supersession> {
+    SUPERSESSIONS.with(|s| s.borrow().get(&proposal_id))
+}
+
+pub(crate) fn set_superseded_by(proposal_id: Index, superseded_by: Index) {
+    SUPERSEDED_BY.with(|s| s.borrow_mut().insert(proposal_id, superseded_by));
+}
+
+/// Returns the proposal that superseded a proposal in the `Superseded` state.
+#[query]
+pub fn get_superseded_by(proposal_id: Index) -> Option<Index> {
+    SUPERSEDED_BY.with(|s| s.borrow().get(&proposal_id))
+}
+
+// ==== Sponsorship ====
+pub(crate) fn set_sponsorship(proposal_id: Index, sponsorship: &Sponsorship) {
+    SPONSORSHIPS.with(|s| s.borrow_mut().insert(proposal_id, sponsorship.clone()));
+}
+
+/// Returns the sponsorship of a proposal, including its sponsors.
+#[query]
+pub fn get_sponsorship(proposal_id: Index) -> Option<Sponsorship> {
+    SPONSORSHIPS.with(|s| s.borrow().get(&proposal_id))
+}
+
+pub(crate) fn is_awaiting_sponsorship(proposal_id: Index) -> bool {
+    get_sponsorship(proposal_id).is_some_and(|s| !s.is_reached())
+}
+
+// ==== Embargo ====
+pub(crate) fn set_embargo(proposal_id: Index, embargo: &EmbargoedPayload) {
+    EMBARGOES.with(|e| e.borrow_mut().insert(proposal_id, embargo.clone()));
+}
+
+/// Returns the embargo of a proposal, including the payload hash, the summary and the attestations.
+#[query]
+pub fn get_embargo(proposal_id: Index) -> Option<EmbargoedPayload> {
+    EMBARGOES.with(|e| e.borrow().get(&proposal_id))
+}
+
+/// Fails if the payload of the proposal is embargoed, and has not been revealed and validated yet.
+pub(crate) fn check_payload_released(proposal_id: Index) -> Result<(), ReturnError> {
+    get_embargo(proposal_id).map_or(Ok(()), |e| e.check_released())
+}
+
+// ==== AppealDeposit ====
+pub(crate) fn set_appeal_deposit(proposal_id: Index, deposit: &ProposalDeposit) {
+    APPEAL_DEPOSITS.with(|p| p.borrow_mut().insert(proposal_id, deposit.clone()));
+}
+
+#[query]
+pub fn get_appeal_deposit(proposal_id: Index) -> Option<ProposalDeposit> {
+    APPEAL_DEPOSITS.with(|p| p.borrow().get(&proposal_id))
+}
+
 // ==== TimerTasks ====
 pub(crate) fn push_timer_task(proposal_id: Index) -> Result<(), ReturnError> {
     TIMER_TASKS.with(|t| {
@@ -327,3 +935,188 @@ pub(crate) fn push_timer_task(proposal_id: Index) -> Result<(), ReturnError> {
 pub(crate) fn pop_timer_task() -> Option<Index> {
     TIMER_TASKS.with(|t| t.borrow_mut().pop())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::legacy_proposal;
+
+    #[test]
+    fn migrate_to_v1_moves_proposals_and_execution_results() {
+        PROPOSALS_V0.with(|p| {
+            let p = p.borrow_mut();
+            p.push(&legacy_proposal(true)).unwrap();
+            p.push(&legacy_proposal(false)).unwrap();
+        });
+        let exec = ProposalExec {
+            execution_result: vec![ExecResult(Ok(vec![1]))],
+        };
+        PROPOSAL_EXEC_V0.with(|p| p.borrow_mut().insert(0, exec));
+
+        // Proposals that are not migrated yet are readable, but cannot be changed or followed by new ones.
+        assert!(!migrate_to_v1(1));
+        assert_eq!(get_next_proposal_id(), 2);
+        assert_eq!(
+            get_proposal_by_id(0).unwrap().state,
+            ProposalState::Accepted
+        );
+        assert_eq!(
+            get_proposal_by_id(1).err(),
+            Some(ReturnError::MigrationInProgress)
+        );
+        assert_eq!(get_proposal_by_id(2).err(), Some(ReturnError::InvalidIndex));
+        assert_eq!(get_proposal(1).unwrap().state, ProposalState::Submitted);
+        let proposal = get_proposal(1).unwrap();
+        assert_eq!(
+            add_proposal(&proposal).err(),
+            Some(ReturnError::MigrationInProgress)
+        );
+
+        assert!(migrate_storage());
+        assert_eq!(
+            STORAGE_VERSION_CELL.with(|v| *v.borrow().get()),
+            STORAGE_VERSION
+        );
+        assert_eq!(get_next_proposal_id(), 2);
+        assert_eq!(get_proposal(1).unwrap().state, ProposalState::Submitted);
+        assert_eq!(get_pending_proposal_count(), 2);
+        let results = get_proposal_execution_results(0);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].execution_result[0].0, Ok(vec![1]));
+        assert!(PROPOSAL_EXEC_V0.with(|p| p.borrow().is_empty()));
+        assert_eq!(add_proposal(&proposal), Ok(2));
+
+        // Later upgrades do not migrate again.
+        assert!(migrate_storage());
+        assert_eq!(get_next_proposal_id(), 3);
+    }
+
+    #[test]
+    fn latest_execution_result_is_the_last_run_of_the_proposal() {
+        for run in 0..3 {
+            add_execution_step_result(5, run, ExecResult(Ok(vec![run as u8])));
+        }
+        add_execution_step_result(6, 0, ExecResult(Ok(vec![9])));
+
+        let latest = get_proposal_execution_result(5).unwrap();
+        assert_eq!(latest.execution_result[0].0, Ok(vec![2]));
+        assert_eq!(
+            get_proposal_execution_result(6).unwrap().execution_result[0].0,
+            Ok(vec![9])
+        );
+        assert_eq!(
+            get_proposal_execution_result(4).err(),
+            Some(ReturnError::InvalidIndex)
+        );
+    }
+
+    #[test]
+    fn removed_proposals_free_their_ids_and_pending_slots() {
+        let proposal = legacy_proposal(false).decode();
+        add_proposal(&proposal).unwrap();
+        let first_id = get_next_proposal_id();
+        add_proposal(&proposal).unwrap();
+        add_proposal(&proposal).unwrap();
+        assert_eq!(get_pending_proposal_count(), 3);
+
+        remove_proposals_from(first_id);
+        assert_eq!(get_next_proposal_id(), first_id);
+        assert_eq!(get_pending_proposal_ids(), vec![0]);
+    }
+
+    #[test]
+    fn reserved_pending_proposals_count_towards_the_limit() {
+        reserve_pending_proposals(Some(3), 2).unwrap();
+        assert_eq!(
+            reserve_pending_proposals(Some(3), 2),
+            Err(ReturnError::PendingProposalLimitExceeded)
+        );
+        reserve_pending_proposals(Some(3), 1).unwrap();
+        release_pending_proposals(2);
+        reserve_pending_proposals(Some(3), 2).unwrap();
+    }
+
+    #[test]
+    fn timed_out_condition_calls_are_replaced() {
+        set_condition_call(7, 100);
+        // A later call replaces the timed out one, which then cannot end it.
+        set_condition_call(7, 200);
+        assert!(!end_condition_call(7, 100));
+        assert_eq!(get_condition_call(7), Some(200));
+        assert!(end_condition_call(7, 200));
+        assert_eq!(get_condition_call(7), None);
+    }
+
+    #[test]
+    fn condition_evaluations_are_kept_in_order() {
+        for (evaluated_at, ready) in [(1, false), (2, false), (3, true), (4, false)] {
+            add_condition_evaluation(
+                7,
+                ConditionEvaluation {
+                    evaluated_at,
+                    result: Ok(ready),
+                },
+            );
+        }
+        add_condition_evaluation(
+            8,
+            ConditionEvaluation {
+                evaluated_at: 4,
+                result: Err(String::from("unreachable")),
+            },
+        );
+
+        // The repeated outcome at time 2 is not recorded.
+        let evaluations = get_activation_condition_evaluations(7, 1, 5);
+        assert_eq!(evaluations.len(), 2);
+        assert_eq!(evaluations[0].evaluated_at, 3);
+        assert_eq!(evaluations[0].result, Ok(true));
+        assert_eq!(evaluations[1].result, Ok(false));
+        assert_eq!(
+            get_activation_condition_evaluations(7, 0, 1)[0].evaluated_at,
+            1
+        );
+        assert_eq!(get_activation_condition_evaluations(8, 0, 5).len(), 1);
+    }
+
+    #[test]
+    fn tally_records_are_appended_per_proposal() {
+        let record = |recorded_at| TallyRecord {
+            recorded_at,
+            vote_manager: Principal::anonymous(),
+            votes_yes: 1,
+            votes_no: 0,
+            votes_abstain: 0,
+            total_voting_power: 10,
+        };
+        add_tally_record(3, record(1));
+        add_tally_record(4, record(2));
+        add_tally_record(3, record(3));
+
+        let history = get_tally_history(3, 0, 5);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].recorded_at, 3);
+        assert_eq!(get_tally_history(3, 1, 5)[0].recorded_at, 3);
+        assert_eq!(get_tally_history(4, 0, 5).len(), 1);
+    }
+
+    #[test]
+    fn uncounted_submissions_free_their_rate_limit_slots() {
+        let proposer = Principal::anonymous();
+        let limits = SubmissionLimits {
+            max_submissions_per_window: Some(2),
+            rate_limit_window: 100,
+            ..Default::default()
+        };
+        let start = count_submissions(proposer, &limits, 10, 2).unwrap();
+        assert!(count_submissions(proposer, &limits, 20, 1).is_err());
+        uncount_submissions(proposer, start, 1);
+        assert_eq!(count_submissions(proposer, &limits, 20, 1), Ok(start));
+
+        // Submissions of an earlier window are not taken back from a later one.
+        let later = count_submissions(proposer, &limits, 200, 2).unwrap();
+        uncount_submissions(proposer, start, 2);
+        assert!(count_submissions(proposer, &limits, 210, 1).is_err());
+        assert_eq!(later, 200);
+    }
+}
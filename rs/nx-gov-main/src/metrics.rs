@@ -3,6 +3,7 @@
 //! This file is taken from [ic-eth-wallet](https://github.com/dfinity/ic-eth-wallet) which is licensed under Apache-2.0.
 
 use crate::http::HttpResponse;
+use crate::memory::{get_stale_proposals, get_vote_manager_reports};
 
 use ic_metrics_encoder::MetricsEncoder;
 use serde_bytes::ByteBuf;
@@ -56,6 +57,21 @@ fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
         cycles_balance() as f64,
         "Amount of wasm memory used by this canister, in GiB",
     )?;
+    w.encode_gauge(
+        "nx_gov_stale_proposals",
+        get_stale_proposals().len() as f64,
+        "Number of proposals in voting without a recent vote manager report",
+    )?;
+    let mut reports = w.gauge_vec(
+        "nx_gov_vote_manager_last_report_timestamp_seconds",
+        "Time of the last report of each vote manager, in seconds since UNIX Epoch",
+    )?;
+    for (vote_manager, reported_at) in get_vote_manager_reports() {
+        reports = reports.value(
+            &[("vote_manager", &vote_manager.to_text())],
+            reported_at as f64 / 1e9,
+        )?;
+    }
     Ok(())
 }
 
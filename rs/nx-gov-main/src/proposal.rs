@@ -1,3 +1,7 @@
+use crate::appeal::{Appeal, VotingRound};
+use crate::choice::{ChoiceOptions, Choices};
+use crate::embargo::Embargo;
+use crate::execution::{ActivationCondition, ProposalPayload};
 use crate::types::*;
 use crate::validate::Validate;
 
@@ -26,6 +30,12 @@ pub struct Proposal {
     /// Conversion to absolute time happens when the proposal voting is finalized.
     /// If created with relative time, the proposal cannot be force executed.
     pub expires: Schedule,
+    /// Recurrence of a standing-order proposal.
+    ///
+    /// If set, the payload is executed once per interval after the proposal is accepted.
+    pub recurrence: Option<Recurrence>,
+    /// Class of the proposal, which decides the validation floors. `None` is `Standard`.
+    pub class: Option<ProposalClass>,
 
     // ---- Generated at creation time ----
     /// Time when the proposal was created.
@@ -55,6 +65,19 @@ pub struct Proposal {
     pub votes_abstain: VotingPower,
     /// Total voting power valid for the current proposal.
     pub total_voting_power: VotingPower,
+    /// Choices of a multi-option proposal, with the tally of each. `None` for a single payload.
+    /// The candidate payloads are fixed at creation time.
+    pub choices: Option<Choices>,
+
+    // ---- Set by execution ----
+    /// Number of successfully completed execution runs. Only standing orders run more than once.
+    /// `None` for proposals stored before standing orders, which is zero runs.
+    #[serde(default)]
+    pub runs: Option<u64>,
+
+    // ---- Set by appeal ----
+    /// The appeal of the proposal, including the first voting round.
+    pub appeal: Option<Appeal>,
 }
 
 #[allow(clippy::enum_variant_names)]
@@ -66,12 +89,15 @@ pub enum ProposalError {
 }
 
 impl Proposal {
+    #[allow(clippy::too_many_arguments)]
     pub fn from_submit(
         metadata_id: Index,
         payload_id: Index,
         auto_execute: bool,
         activates: Schedule,
         expires: Schedule,
+        recurrence: Option<Recurrence>,
+        class: Option<ProposalClass>,
         proposer: &Principal,
     ) -> Self {
         Self {
@@ -80,6 +106,8 @@ impl Proposal {
             auto_execute,
             activates,
             expires,
+            recurrence,
+            class,
             created_at: ic_cdk::api::time(),
             proposer: proposer.to_owned(),
             validated: None,
@@ -90,11 +118,36 @@ impl Proposal {
             votes_no: 0,
             votes_abstain: 0,
             total_voting_power: 0,
+            choices: None,
+            runs: None,
+            appeal: None,
+        }
+    }
+
+    pub fn class(&self) -> ProposalClass {
+        self.class.clone().unwrap_or_default()
+    }
+
+    pub fn runs(&self) -> u64 {
+        self.runs.unwrap_or_default()
+    }
+
+    /// The payload to execute. For a multi-option proposal, this is the payload of the winning choice,
+    /// which is `None` until the proposal is accepted.
+    pub fn execution_payload_id(&self) -> Option<Index> {
+        match &self.choices {
+            Some(choices) => choices.winning_payload_id(),
+            None => Some(self.payload_id),
         }
     }
 
     pub fn is_voteable(&self) -> bool {
-        self.state == ProposalState::Open && self.voting_end_time > Some(ic_cdk::api::time())
+        self.is_in_voting() && self.voting_end_time > Some(ic_cdk::api::time())
+    }
+
+    /// Whether the proposal is in a voting round, either the first one or an appeal.
+    pub fn is_in_voting(&self) -> bool {
+        self.state == ProposalState::Open || self.state == ProposalState::Appealed
     }
 
     pub fn is_expired(&self) -> bool {
@@ -109,6 +162,22 @@ impl Proposal {
             && self.expires.to_timestamp() > Schedule::At(ic_cdk::api::time()).to_timestamp()
     }
 
+    /// Expiration is only known once it has been converted to absolute time.
+    pub fn is_execution_expired(&self) -> bool {
+        self.expires.is_absolute()
+            && self.expires.to_timestamp() <= Schedule::At(ic_cdk::api::time()).to_timestamp()
+    }
+
+    pub fn is_recurring(&self) -> bool {
+        self.recurrence.is_some()
+    }
+
+    /// Proposals can be revoked during voting.
+    /// Standing orders can also be revoked between runs, which stops all future runs.
+    pub fn is_revocable(&self) -> bool {
+        self.is_in_voting() || (self.state == ProposalState::Accepted && self.is_recurring())
+    }
+
     pub fn is_force_executable(&self) -> bool {
         self.state == ProposalState::Open
             && self.activates.is_absolute()
@@ -117,6 +186,50 @@ impl Proposal {
             && self.expires.to_timestamp() > Schedule::At(ic_cdk::api::time()).to_timestamp()
     }
 
+    /// Reopens a rejected proposal, or one that did not meet quorum, for a second voting round.
+    ///
+    /// The first round is kept in `appeal`, and the votes are reset for the second round.
+    /// The thresholds of the second round are raised to at least `floor`.
+    /// Relative activation and expiration times stay relative until the second round ends.
+    /// A proposal can only be appealed once.
+    pub fn reopen_for_appeal(
+        &mut self,
+        appellant: Principal,
+        now: TimeNs,
+        voting_end_time: TimeNs,
+        floor: &ProposalPassingThreshold,
+    ) -> Result<(), ProposalError> {
+        if self.appeal.is_some() {
+            return Err(ProposalError::StateTransitionError);
+        }
+        let outcome = self.state_transition(ProposalState::Appealed)?;
+        self.appeal = Some(Appeal {
+            appellant,
+            appealed_at: now,
+            first_round: VotingRound {
+                outcome,
+                voting_end_time: self.voting_end_time,
+                passing_threshold: self.passing_threshold.clone(),
+                votes_yes: self.votes_yes,
+                votes_no: self.votes_no,
+                votes_abstain: self.votes_abstain,
+                total_voting_power: self.total_voting_power,
+            },
+        });
+        self.voting_end_time = Some(voting_end_time);
+        self.passing_threshold = Some(match &self.passing_threshold {
+            Some(threshold) => threshold.raised_to(floor),
+            None => floor.clone(),
+        });
+        self.votes_yes = 0;
+        self.votes_no = 0;
+        self.votes_abstain = 0;
+        if let Some(choices) = self.choices.as_mut() {
+            choices.reset();
+        }
+        Ok(())
+    }
+
     /// Finalize the activation time.
     pub fn finalize_activation(&mut self) {
         self.activates.convert_to_absolute();
@@ -127,6 +240,46 @@ impl Proposal {
         self.expires.convert_to_absolute();
     }
 
+    /// Activation time of the next run of a standing order.
+    /// Returns `None` if the proposal is not recurring or has no run left.
+    ///
+    /// Runs are spaced by `interval` from the previous activation time rather than from the time
+    /// the previous run completed, so a late run does not shift the rest of the schedule.
+    pub fn next_run_time(&self) -> Option<TimeNs> {
+        let recurrence = self.recurrence.as_ref()?;
+        if recurrence
+            .max_runs
+            .is_some_and(|max_runs| self.runs() >= max_runs)
+        {
+            return None;
+        }
+        let next = self
+            .activates
+            .to_timestamp()?
+            .checked_add(recurrence.interval)?;
+        (Some(next) < self.expires.to_timestamp()).then_some(next)
+    }
+
+    /// Completes a successful execution run.
+    /// A standing order with runs left moves back to `Accepted` and activates at its next run time,
+    /// otherwise the proposal moves to `Succeeded`.
+    /// Returns previous state if transition successful.
+    pub fn complete_run(&mut self) -> Result<ProposalState, ProposalError> {
+        self.runs = Some(
+            self.runs()
+                .checked_add(1)
+                .ok_or(ProposalError::ArithmeticError)?,
+        );
+        match self.next_run_time() {
+            Some(next) => {
+                let prev_state = self.state_transition(ProposalState::Accepted)?;
+                self.activates = Schedule::At(next);
+                Ok(prev_state)
+            }
+            None => self.state_transition(ProposalState::Succeeded),
+        }
+    }
+
     /// Ensures proper state transitions of the state machine.
     /// Returns previous state if transition successful.
     pub fn state_transition(
@@ -135,7 +288,10 @@ impl Proposal {
     ) -> Result<ProposalState, ProposalError> {
         match self.state {
             ProposalState::Submitted => match next_state {
-                ProposalState::Open | ProposalState::ValidationFailed => {
+                ProposalState::Open
+                | ProposalState::ValidationFailed
+                | ProposalState::Expired
+                | ProposalState::Superseded => {
                     self.state = next_state;
                     Ok(ProposalState::Submitted)
                 }
@@ -146,6 +302,7 @@ impl Proposal {
                 | ProposalState::Rejected
                 | ProposalState::Revoked
                 | ProposalState::QuorumNotMet
+                | ProposalState::Superseded
                 | ProposalState::ForceExecuting(_) => {
                     self.state = next_state;
                     Ok(ProposalState::Open)
@@ -153,7 +310,11 @@ impl Proposal {
                 _ => Err(ProposalError::StateTransitionError),
             },
             ProposalState::Accepted => match next_state {
-                ProposalState::Executing(_) => {
+                ProposalState::Executing(_) | ProposalState::Superseded => {
+                    self.state = next_state;
+                    Ok(ProposalState::Accepted)
+                }
+                ProposalState::Revoked if self.is_recurring() => {
                     self.state = next_state;
                     Ok(ProposalState::Accepted)
                 }
@@ -168,6 +329,28 @@ impl Proposal {
                     self.state = next_state;
                     Ok(prev_state)
                 }
+                ProposalState::Accepted if self.is_recurring() => {
+                    let prev_state = self.state.clone();
+                    self.state = next_state;
+                    Ok(prev_state)
+                }
+                _ => Err(ProposalError::StateTransitionError),
+            },
+            ProposalState::Rejected | ProposalState::QuorumNotMet => match next_state {
+                ProposalState::Appealed => {
+                    let prev_state = self.state.clone();
+                    self.state = next_state;
+                    Ok(prev_state)
+                }
+                _ => Err(ProposalError::StateTransitionError),
+            },
+            ProposalState::Appealed => match next_state {
+                ProposalState::Accepted
+                | ProposalState::AppealRejected
+                | ProposalState::Revoked => {
+                    self.state = next_state;
+                    Ok(ProposalState::Appealed)
+                }
                 _ => Err(ProposalError::StateTransitionError),
             },
             ProposalState::ForceExecuting(_) => match next_state {
@@ -208,75 +391,293 @@ impl Proposal {
         }
     }
 
-    /// Votes of `yes`, `no`, and `abstain` all count towards the quorum.
-    pub fn current_participation_rate(&self) -> Percentage<PercentagePrecision> {
-        let total_votes = self.votes_yes as f64 + self.votes_no as f64 + self.votes_abstain as f64;
-        (total_votes / self.total_voting_power as f64).into()
-    }
-
-    /// Only votes of `yes` and `no` count towards the pass rate.
-    pub fn current_yes_rate(&self) -> Percentage<PercentagePrecision> {
-        let effective_votes = self.votes_yes as f64 + self.votes_no as f64;
-        (self.votes_yes as f64 / effective_votes).into()
+    /// Whether no/(yes+no) reaches `threshold`. Never reached if the tally is invalid.
+    pub fn no_share_reaches(&self, threshold: &Percentage<PercentagePrecision>) -> bool {
+        self.tally().no_share_reaches(threshold)
     }
 
-    pub fn absolute_majority_reached(&self) -> bool {
-        self.votes_yes * 2 > self.total_voting_power
+    pub fn tally(&self) -> Tally {
+        Tally {
+            yes: self.votes_yes,
+            no: self.votes_no,
+            abstain: self.votes_abstain,
+            total: self.total_voting_power,
+        }
     }
 
     /// Try to finalize the vote result.
-    /// Returns true if is finalized.
+    /// Returns true if is finalized, or `ArithmeticError` if the tally is invalid.
+    ///
+    /// A multi-option proposal is only finalized once the voting period has ended, since the winner can still change.
+    /// If it is accepted but no choice wins, it is rejected.
     /// # Panics
     /// Panics if voting_end_time or passing_threshold is None(should never happen).
     pub fn try_finalize_vote_result(&mut self) -> Result<bool, ProposalError> {
         #![allow(clippy::unwrap_used)]
-        match self.is_expired() {
-            // Voting not finished, try finalize
-            false => {
-                if self.current_participation_rate()
-                    >= self.passing_threshold.clone().unwrap().quorum
-                    && self.absolute_majority_reached()
-                {
-                    self.state_transition(ProposalState::Accepted)?;
-                    self.finalize_activation();
-                    self.finalize_expiration();
-                    Ok(true)
-                } else {
-                    Ok(false)
+        let threshold = self.passing_threshold.clone().unwrap();
+        let appealed = self.state == ProposalState::Appealed;
+        let voting_ended = self.is_expired();
+        if self.choices.is_some() && !voting_ended {
+            return Ok(false);
+        }
+        let outcome = match (
+            decide_vote(&threshold, &self.tally(), voting_ended)?,
+            &mut self.choices,
+        ) {
+            (None, _) => return Ok(false),
+            (Some(VoteOutcome::Accepted), Some(choices)) => {
+                choices.winner = choices.select_winner();
+                match choices.winner {
+                    Some(_) => VoteOutcome::Accepted,
+                    None => VoteOutcome::Rejected,
                 }
             }
-            // Voting finished, must finalize
-            true => {
-                if self.current_participation_rate()
-                    < self.passing_threshold.clone().unwrap().quorum
-                {
-                    self.state_transition(ProposalState::QuorumNotMet)?;
-                } else if self.current_yes_rate()
-                    < self.passing_threshold.clone().unwrap().passing_threshold
-                {
-                    self.state_transition(ProposalState::Rejected)?;
-                } else {
-                    self.state_transition(ProposalState::Accepted)?;
-                }
-                self.finalize_activation();
-                self.finalize_expiration();
-                Ok(true)
+            (Some(outcome), _) => outcome,
+        };
+        let state = match outcome {
+            VoteOutcome::Accepted => ProposalState::Accepted,
+            VoteOutcome::Rejected | VoteOutcome::QuorumNotMet if appealed => {
+                ProposalState::AppealRejected
             }
+            VoteOutcome::Rejected => ProposalState::Rejected,
+            VoteOutcome::QuorumNotMet => ProposalState::QuorumNotMet,
+        };
+        self.state_transition(state)?;
+        // Relative times of a proposal that can still be appealed are converted when the appeal round ends.
+        if !matches!(
+            self.state,
+            ProposalState::Rejected | ProposalState::QuorumNotMet
+        ) {
+            self.finalize_activation();
+            self.finalize_expiration();
+        }
+        Ok(true)
+    }
+
+    /// Ends the voting round as rejected, whatever the tally.
+    /// For vote managers that decide votes themselves, whose rejection must not be overturned by the counts.
+    pub fn reject_vote_result(&mut self) -> Result<(), ProposalError> {
+        let state = match self.state {
+            ProposalState::Appealed => ProposalState::AppealRejected,
+            _ => ProposalState::Rejected,
+        };
+        self.state_transition(state)?;
+        // Relative times of a proposal that can still be appealed are converted when the appeal round ends.
+        if self.state == ProposalState::AppealRejected {
+            self.finalize_activation();
+            self.finalize_expiration();
         }
+        Ok(())
     }
 }
 
+/// The running totals of a proposal after a tally update by a vote manager.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct TallyRecord {
+    /// Time of the update.
+    pub recorded_at: TimeNs,
+    /// The vote manager that sent the update.
+    pub vote_manager: Principal,
+    pub votes_yes: VotingPower,
+    pub votes_no: VotingPower,
+    pub votes_abstain: VotingPower,
+    pub total_voting_power: VotingPower,
+}
+
+impl TallyRecord {
+    pub fn new(proposal: &Proposal, vote_manager: Principal, now: TimeNs) -> Self {
+        Self {
+            recorded_at: now,
+            vote_manager,
+            votes_yes: proposal.votes_yes,
+            votes_no: proposal.votes_no,
+            votes_abstain: proposal.votes_abstain,
+            total_voting_power: proposal.total_voting_power,
+        }
+    }
+}
+
+/// Voting power cast on a proposal, and the total voting power.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tally {
+    pub yes: VotingPower,
+    pub no: VotingPower,
+    pub abstain: VotingPower,
+    pub total: VotingPower,
+}
+
+impl Tally {
+    /// Whether no/(yes+no) reaches `threshold`. Never reached if the tally is invalid.
+    pub(crate) fn no_share_reaches(&self, threshold: &Percentage<PercentagePrecision>) -> bool {
+        self.counts()
+            .is_ok_and(|c| ratio_reaches(c.no, c.yes + c.no, threshold))
+    }
+
+    /// Fails with `ArithmeticError` on negative counts, or if the cast voting power overflows `u128`.
+    fn counts(&self) -> Result<Counts, ProposalError> {
+        let count = |v: VotingPower| u128::try_from(v).map_err(|_| ProposalError::ArithmeticError);
+        let (yes, no, abstain, total) = (
+            count(self.yes)?,
+            count(self.no)?,
+            count(self.abstain)?,
+            count(self.total)?,
+        );
+        let cast = yes
+            .checked_add(no)
+            .and_then(|v| v.checked_add(abstain))
+            .ok_or(ProposalError::ArithmeticError)?;
+        Ok(Counts {
+            yes,
+            no,
+            total,
+            cast,
+        })
+    }
+}
+
+/// Validated tally, with `cast = yes + no + abstain`.
+/// Every count is below 2^127, so the sum of a count and `cast` cannot overflow.
+#[derive(Clone, Debug)]
+struct Counts {
+    yes: u128,
+    no: u128,
+    total: u128,
+    cast: u128,
+}
+
+impl Counts {
+    fn uncast(&self) -> u128 {
+        self.total.saturating_sub(self.cast)
+    }
+
+    /// The counts if all uncast voting power votes "no".
+    fn with_uncast_no(&self) -> Self {
+        Self {
+            no: self.no + self.uncast(),
+            cast: self.cast + self.uncast(),
+            ..self.clone()
+        }
+    }
+
+    /// The counts if all uncast voting power votes "yes".
+    fn with_uncast_yes(&self) -> Self {
+        Self {
+            yes: self.yes + self.uncast(),
+            cast: self.cast + self.uncast(),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoteOutcome {
+    Accepted,
+    Rejected,
+    QuorumNotMet,
+}
+
+/// Decides the vote of a proposal. Returns `None` while the vote is undecided.
+///
+/// Once the voting period has ended, the vote is always decided.
+/// Before that, a vote with a passing mode is decided early once the quorum is met and the result cannot change anymore:
+/// it is accepted if it passes even when all uncast voting power votes "no",
+/// and rejected if it fails even when all uncast voting power votes "yes".
+/// A vote without a passing mode is only accepted early, once the quorum is met and "yes" is an absolute majority.
+///
+/// All comparisons are exact. A ratio with a zero denominator never reaches any threshold,
+/// so a zero total voting power never meets the quorum.
+/// Fails with `ArithmeticError` on negative counts, or if the cast voting power overflows `u128`.
+pub fn decide_vote(
+    threshold: &ProposalPassingThreshold,
+    tally: &Tally,
+    voting_ended: bool,
+) -> Result<Option<VoteOutcome>, ProposalError> {
+    let counts = tally.counts()?;
+    let quorum_met = ratio_reaches(counts.cast, counts.total, &threshold.quorum);
+    if voting_ended {
+        return Ok(Some(match (quorum_met, passes(threshold, &counts)) {
+            (false, _) => VoteOutcome::QuorumNotMet,
+            (true, true) => VoteOutcome::Accepted,
+            (true, false) => VoteOutcome::Rejected,
+        }));
+    }
+    if !quorum_met {
+        return Ok(None);
+    }
+    let accepted_anyway = passes(threshold, &counts.with_uncast_no());
+    Ok(if threshold.mode.is_none() {
+        // Without a passing mode, the original rule applies: an absolute majority accepts early,
+        // even if later "no" votes could still fail the passing threshold.
+        ratio_cmp(counts.yes, counts.total, 1, 2)
+            .is_gt()
+            .then_some(VoteOutcome::Accepted)
+    } else if accepted_anyway {
+        Some(VoteOutcome::Accepted)
+    } else if !passes(threshold, &counts.with_uncast_yes()) {
+        Some(VoteOutcome::Rejected)
+    } else {
+        None
+    })
+}
+
+/// Whether the "yes" votes pass, according to the passing mode. Ignores the quorum.
+fn passes(threshold: &ProposalPassingThreshold, counts: &Counts) -> bool {
+    let yes_no = counts.yes + counts.no;
+    let reaches =
+        |denominator| ratio_reaches(counts.yes, denominator, &threshold.passing_threshold);
+    let majority_of = |denominator| ratio_cmp(counts.yes, denominator, 1, 2).is_gt();
+    match threshold.mode {
+        None => reaches(yes_no),
+        Some(PassingMode::SimpleMajority) => majority_of(yes_no) && reaches(yes_no),
+        Some(PassingMode::AbsoluteMajority) => majority_of(counts.total) && reaches(counts.total),
+        Some(PassingMode::TwoThirds) => {
+            yes_no > 0 && ratio_cmp(counts.yes, yes_no, 2, 3).is_ge() && reaches(yes_no)
+        }
+        Some(PassingMode::Unanimity) => counts.no == 0 && counts.yes > 0 && reaches(yes_no),
+        Some(PassingMode::AbstainInDenominator) => majority_of(counts.cast) && reaches(counts.cast),
+    }
+}
+
+/// Whether `numerator / denominator` reaches `threshold`. Never reached with a zero denominator.
+pub(crate) fn ratio_reaches(
+    numerator: u128,
+    denominator: u128,
+    threshold: &Percentage<PercentagePrecision>,
+) -> bool {
+    let (n, d) = threshold.as_fraction();
+    denominator > 0 && ratio_cmp(numerator, denominator, n, d).is_ge()
+}
+
+/// Compares `a / b` with `c / d` by cross-multiplication, i.e. `a * d` with `c * b`.
+fn ratio_cmp(a: u128, b: u128, c: u128, d: u128) -> std::cmp::Ordering {
+    wide_mul(a, d).cmp(&wide_mul(c, b))
+}
+
+/// Exact product of two `u128` as (high, low) halves, which order like the products.
+fn wide_mul(a: u128, b: u128) -> (u128, u128) {
+    const LOW: u128 = u64::MAX as u128;
+    let (a_high, a_low) = (a >> 64, a & LOW);
+    let (b_high, b_low) = (b >> 64, b & LOW);
+    let low = a_low * b_low;
+    let cross_1 = a_low * b_high;
+    let cross_2 = a_high * b_low;
+    let mid = (low >> 64) + (cross_1 & LOW) + (cross_2 & LOW);
+    let high = a_high * b_high + (cross_1 >> 64) + (cross_2 >> 64) + (mid >> 64);
+    (high, (mid << 64) | (low & LOW))
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize, PartialEq, Serialize)]
 pub enum ProposalState {
-    /// The proposal is submitted and waiting for validation. Next states: Open, ValidationFailed.
+    /// The proposal is submitted and waiting for sponsorship or validation.
+    /// Next states: Open, ValidationFailed, Expired (sponsorship not reached), Superseded.
     Submitted,
     /// The proposal failed validation. END.
     ValidationFailed,
-    /// The proposal is validated and open for voting. Next states: Accepted, Rejected, Revoked, QuorumNotMet, ForceExecuting.
+    /// The proposal is validated and open for voting. Next states: Accepted, Rejected, Revoked, QuorumNotMet, Superseded, ForceExecuting.
     Open,
-    /// Enough "yes" votes have been cast to accept the proposal, and it will soon be executed. Next states: Executing.
+    /// Enough "yes" votes have been cast to accept the proposal, and it will soon be executed. Next states: Executing, Superseded, Revoked (standing orders only).
     Accepted,
-    /// The proposal is currently being executed. Next states: Succeeded, Failed, Expired.
+    /// The proposal is currently being executed. Next states: Succeeded, Failed, Expired, Accepted (standing orders with runs left).
     Executing(ExecutionStep),
     /// The proposal has been successfully executed. END.
     Succeeded,
@@ -284,11 +685,12 @@ pub enum ProposalState {
     Failed(ExecutionStep),
     /// The proposal has expired without being executed. END.
     Expired,
-    /// Enough "no" votes have been cast to reject the proposal, and it will not be executed. END.
+    /// Enough "no" votes have been cast to reject the proposal, and it will not be executed.
+    /// END, unless appealed. Next states: Appealed.
     Rejected,
-    /// Revoked during voting process. END.
+    /// Revoked during voting process, or a standing order stopped between runs. END.
     Revoked,
-    /// The quorum was not met during the voting period. END.
+    /// The quorum was not met during the voting period. END, unless appealed. Next states: Appealed.
     QuorumNotMet,
     /// The proposal was force executed. Next states: ForceExecutionSucceeded, ForceExecutionFailed.
     ForceExecuting(ExecutionStep),
@@ -296,6 +698,135 @@ pub enum ProposalState {
     ForceExecutionSucceeded,
     /// A failure occurred while force executing the proposal. END.
     ForceExecutionFailed(ExecutionStep),
+    /// A proposal that supersedes this one was accepted, or validated if requested. END.
+    Superseded,
+    /// The proposal was appealed and is open for a second vote. Next states: Accepted, AppealRejected, Revoked.
+    Appealed,
+    /// The second vote of an appealed proposal did not accept it. END.
+    AppealRejected,
+}
+
+impl ProposalState {
+    /// Whether the proposal has been executed successfully, normally or forcibly.
+    pub fn has_succeeded(&self) -> bool {
+        matches!(
+            self,
+            ProposalState::Succeeded | ProposalState::ForceExecutionSucceeded
+        )
+    }
+
+    /// Whether the state is an END state, with no further transitions.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            ProposalState::ValidationFailed
+                | ProposalState::Succeeded
+                | ProposalState::Failed(_)
+                | ProposalState::Expired
+                | ProposalState::Rejected
+                | ProposalState::Revoked
+                | ProposalState::QuorumNotMet
+                | ProposalState::ForceExecutionSucceeded
+                | ProposalState::ForceExecutionFailed(_)
+                | ProposalState::Superseded
+                | ProposalState::AppealRejected
+        )
+    }
+}
+
+/// Recurrence of a standing-order proposal.
+///
+/// The first run happens at the activation time. Runs continue once per `interval`
+/// until `max_runs` runs have completed, or until the next run would not happen before the expiration time.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Recurrence {
+    /// Time between the activation of two consecutive runs, in nano-seconds.
+    pub interval: TimeNs,
+    /// Maximum number of runs. If `None`, runs continue until the proposal expires.
+    pub max_runs: Option<u64>,
+}
+
+impl Validate for Recurrence {
+    fn is_valid(&self) -> bool {
+        self.interval > 0 && self.max_runs != Some(0)
+    }
+}
+
+/// Optional parameters of a submission.
+/// All fields are optional, so that new parameters can be added without breaking existing callers.
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize)]
+pub struct SubmitOptions {
+    /// Turns the proposal into a standing order, whose payload executes repeatedly once accepted.
+    pub recurrence: Option<Recurrence>,
+    /// Condition that must hold before the proposal can be executed, on top of the activation time.
+    /// Its canister must be in `Config.condition_canisters`.
+    pub activation_condition: Option<ActivationCondition>,
+    /// Earlier proposal that this proposal replaces.
+    pub supersedes: Option<Supersession>,
+    /// Class of the proposal. Defaults to `Standard`.
+    pub class: Option<ProposalClass>,
+    /// Turns the proposal into a multi-option proposal, with the submitted payload as the first choice.
+    pub choices: Option<ChoiceOptions>,
+    /// Keeps the payload secret until execution. The submitted payload is a public placeholder without messages,
+    /// which is replaced when the payload is revealed. Cannot be combined with `choices`.
+    pub embargo: Option<Embargo>,
+}
+
+/// Class of a proposal, declared at submission time.
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ProposalClass {
+    /// Validated against `Config.min_voting_period` and `Config.min_passing_threshold`.
+    #[default]
+    Standard,
+    /// Fast-track class, for example for security patches.
+    /// Validated against the floors in `Config.emergency`, which allow a shorter voting period
+    /// but require a higher quorum and passing threshold.
+    Emergency,
+}
+
+/// Replacement of an earlier proposal by a corrected version.
+///
+/// Once the new proposal is accepted, or validated if `on_validation` is set,
+/// the earlier proposal moves to `Superseded`, unless it is executing or already in a final state.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Supersession {
+    /// The proposal being replaced. Must have the same proposer, and must not be in a final state.
+    pub proposal_id: Index,
+    /// Supersede as soon as the new proposal is validated, instead of when it is accepted.
+    pub on_validation: bool,
+}
+
+impl Supersession {
+    /// Whether the earlier proposal should be superseded, given the state of the new proposal.
+    pub fn is_due(&self, state: &ProposalState) -> bool {
+        match state {
+            ProposalState::Accepted => true,
+            ProposalState::Open => self.on_validation,
+            _ => false,
+        }
+    }
+}
+
+impl SubmitOptions {
+    /// The alternative payloads of a multi-option proposal, or none.
+    pub fn alternatives(&self) -> &[ProposalPayload] {
+        self.choices.as_ref().map_or(&[], |c| &c.alternatives)
+    }
+}
+
+impl Validate for SubmitOptions {
+    fn is_valid(&self) -> bool {
+        self.recurrence.as_ref().map_or(true, |r| r.is_valid())
+            && self
+                .activation_condition
+                .as_ref()
+                .map_or(true, |c| c.is_valid())
+            && self.choices.as_ref().map_or(true, |c| c.is_valid())
+            && self
+                .embargo
+                .as_ref()
+                .map_or(true, |e| e.is_valid() && self.choices.is_none())
+    }
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
@@ -437,3 +968,702 @@ impl ExecutionStep {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const INTERVAL: TimeNs = 7 * 86400 * 1_000_000_000;
+
+    pub(crate) fn executing_proposal(recurrence: Option<Recurrence>, expires: TimeNs) -> Proposal {
+        Proposal {
+            metadata_id: 0,
+            payload_id: 0,
+            auto_execute: false,
+            activates: Schedule::At(1_000),
+            expires: Schedule::At(expires),
+            recurrence,
+            class: None,
+            created_at: 0,
+            proposer: Principal::anonymous(),
+            validated: Some(true),
+            voting_end_time: Some(500),
+            passing_threshold: Some(ProposalPassingThreshold::default()),
+            state: ProposalState::Executing(ExecutionStep::new(0)),
+            votes_yes: 0,
+            votes_no: 0,
+            votes_abstain: 0,
+            total_voting_power: 0,
+            choices: None,
+            runs: None,
+            appeal: None,
+        }
+    }
+
+    #[test]
+    fn rejected_vote_result_ends_the_voting_round() {
+        let mut proposal = executing_proposal(None, u64::MAX);
+        proposal.state = ProposalState::Open;
+        // The counts would accept the proposal.
+        proposal.votes_yes = 9;
+        proposal.total_voting_power = 10;
+        proposal.reject_vote_result().unwrap();
+        assert_eq!(proposal.state, ProposalState::Rejected);
+
+        proposal
+            .reopen_for_appeal(Principal::anonymous(), 600, 2_000, &Default::default())
+            .unwrap();
+        proposal.reject_vote_result().unwrap();
+        assert_eq!(proposal.state, ProposalState::AppealRejected);
+
+        // Only a proposal in a voting round can be rejected.
+        assert!(proposal.reject_vote_result().is_err());
+        proposal.state = ProposalState::Accepted;
+        assert!(proposal.reject_vote_result().is_err());
+    }
+
+    #[test]
+    fn one_shot_proposal_succeeds_after_first_run() {
+        let mut proposal = executing_proposal(None, u64::MAX);
+        assert_eq!(proposal.next_run_time(), None);
+        proposal.complete_run().unwrap();
+        assert_eq!(proposal.state, ProposalState::Succeeded);
+        assert_eq!(proposal.runs(), 1);
+    }
+
+    #[test]
+    fn standing_order_stops_after_max_runs() {
+        let recurrence = Recurrence {
+            interval: INTERVAL,
+            max_runs: Some(2),
+        };
+        let mut proposal = executing_proposal(Some(recurrence), u64::MAX);
+
+        proposal.complete_run().unwrap();
+        assert_eq!(proposal.state, ProposalState::Accepted);
+        assert_eq!(proposal.activates, Schedule::At(1_000 + INTERVAL));
+        assert_eq!(proposal.runs(), 1);
+
+        proposal
+            .state_transition(ProposalState::Executing(ExecutionStep::new(0)))
+            .unwrap();
+        proposal.complete_run().unwrap();
+        assert_eq!(proposal.state, ProposalState::Succeeded);
+        assert_eq!(proposal.runs(), 2);
+    }
+
+    #[test]
+    fn standing_order_stops_at_expiration() {
+        let recurrence = Recurrence {
+            interval: INTERVAL,
+            max_runs: None,
+        };
+        let mut proposal = executing_proposal(Some(recurrence), 1_000 + 2 * INTERVAL);
+
+        proposal.complete_run().unwrap();
+        assert_eq!(proposal.state, ProposalState::Accepted);
+        proposal
+            .state_transition(ProposalState::Executing(ExecutionStep::new(0)))
+            .unwrap();
+        // The third run would activate exactly at the expiration time.
+        proposal.complete_run().unwrap();
+        assert_eq!(proposal.state, ProposalState::Succeeded);
+        assert_eq!(proposal.runs(), 2);
+    }
+
+    #[test]
+    fn only_standing_orders_can_be_revoked_between_runs() {
+        let mut proposal = executing_proposal(None, u64::MAX);
+        proposal.state = ProposalState::Accepted;
+        assert!(!proposal.is_revocable());
+        assert!(proposal.state_transition(ProposalState::Revoked).is_err());
+
+        let recurrence = Recurrence {
+            interval: INTERVAL,
+            max_runs: None,
+        };
+        let mut proposal = executing_proposal(Some(recurrence), u64::MAX);
+        proposal.complete_run().unwrap();
+        assert!(proposal.is_revocable());
+        proposal.state_transition(ProposalState::Revoked).unwrap();
+        assert_eq!(proposal.state, ProposalState::Revoked);
+    }
+
+    #[test]
+    fn superseded_only_before_execution() {
+        let mut proposal = executing_proposal(None, 1_000_000);
+        for state in [
+            ProposalState::Submitted,
+            ProposalState::Open,
+            ProposalState::Accepted,
+        ] {
+            proposal.state = state;
+            proposal
+                .state_transition(ProposalState::Superseded)
+                .unwrap();
+            assert!(proposal.state.is_final());
+        }
+        for state in [
+            ProposalState::Executing(ExecutionStep::new(0)),
+            ProposalState::ForceExecuting(ExecutionStep::new(0)),
+            ProposalState::Rejected,
+            ProposalState::Succeeded,
+            ProposalState::Superseded,
+        ] {
+            proposal.state = state;
+            assert!(proposal
+                .state_transition(ProposalState::Superseded)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn appeal_keeps_first_round() {
+        let mut proposal = executing_proposal(None, 1_000_000);
+        proposal.state = ProposalState::Rejected;
+        proposal.votes_yes = 10;
+        proposal.votes_no = 30;
+        proposal.votes_abstain = 5;
+        proposal.total_voting_power = 100;
+        let floor = ProposalPassingThreshold {
+            quorum: Percentage::<PercentagePrecision>::from_percent(50),
+            ..Default::default()
+        };
+        proposal
+            .reopen_for_appeal(Principal::anonymous(), 600, 2_000, &floor)
+            .unwrap();
+
+        assert_eq!(proposal.state, ProposalState::Appealed);
+        assert!(proposal.is_in_voting() && proposal.is_revocable());
+        assert_eq!(proposal.voting_end_time, Some(2_000));
+        assert_eq!(
+            proposal.passing_threshold.as_ref().unwrap().quorum,
+            floor.quorum
+        );
+        assert_eq!(
+            (
+                proposal.votes_yes,
+                proposal.votes_no,
+                proposal.votes_abstain
+            ),
+            (0, 0, 0)
+        );
+        assert_eq!(proposal.total_voting_power, 100);
+        let first_round = proposal.appeal.as_ref().unwrap().first_round.clone();
+        assert_eq!(first_round.outcome, ProposalState::Rejected);
+        assert_eq!(first_round.voting_end_time, Some(500));
+        assert_eq!(
+            first_round.passing_threshold,
+            Some(ProposalPassingThreshold::default())
+        );
+        assert_eq!(
+            (
+                first_round.votes_yes,
+                first_round.votes_no,
+                first_round.votes_abstain
+            ),
+            (10, 30, 5)
+        );
+
+        // Only once, even from another appealable state.
+        proposal
+            .state_transition(ProposalState::AppealRejected)
+            .unwrap();
+        assert!(proposal.state.is_final());
+        proposal.state = ProposalState::QuorumNotMet;
+        assert!(proposal
+            .reopen_for_appeal(Principal::anonymous(), 700, 3_000, &floor)
+            .is_err());
+    }
+
+    #[test]
+    fn appeal_transitions() {
+        let floor = ProposalPassingThreshold::default();
+        let mut proposal = executing_proposal(None, 1_000_000);
+        proposal.state = ProposalState::QuorumNotMet;
+        proposal
+            .reopen_for_appeal(Principal::anonymous(), 600, 2_000, &floor)
+            .unwrap();
+        assert!(proposal.state_transition(ProposalState::Rejected).is_err());
+        proposal.state_transition(ProposalState::Accepted).unwrap();
+
+        for state in [
+            ProposalState::Open,
+            ProposalState::Accepted,
+            ProposalState::Succeeded,
+            ProposalState::Expired,
+        ] {
+            let mut proposal = executing_proposal(None, 1_000_000);
+            proposal.state = state;
+            assert!(proposal
+                .reopen_for_appeal(Principal::anonymous(), 600, 2_000, &floor)
+                .is_err());
+            assert!(proposal.appeal.is_none());
+        }
+    }
+
+    #[test]
+    fn supersession_trigger() {
+        let on_acceptance = Supersession {
+            proposal_id: 0,
+            on_validation: false,
+        };
+        let on_validation = Supersession {
+            on_validation: true,
+            ..on_acceptance.clone()
+        };
+        assert!(!on_acceptance.is_due(&ProposalState::Submitted));
+        assert!(!on_acceptance.is_due(&ProposalState::Open));
+        assert!(on_acceptance.is_due(&ProposalState::Accepted));
+        assert!(!on_acceptance.is_due(&ProposalState::Rejected));
+        assert!(!on_validation.is_due(&ProposalState::Submitted));
+        assert!(on_validation.is_due(&ProposalState::Open));
+        assert!(on_validation.is_due(&ProposalState::Accepted));
+        assert!(!on_validation.is_due(&ProposalState::ValidationFailed));
+    }
+
+    #[test]
+    fn recurrence_validation() {
+        let valid = Recurrence {
+            interval: INTERVAL,
+            max_runs: Some(4),
+        };
+        assert!(valid.is_valid());
+        assert!(!Recurrence {
+            interval: 0,
+            max_runs: None
+        }
+        .is_valid());
+        assert!(!Recurrence {
+            interval: INTERVAL,
+            max_runs: Some(0)
+        }
+        .is_valid());
+    }
+
+    const MODES: [Option<PassingMode>; 6] = [
+        None,
+        Some(PassingMode::SimpleMajority),
+        Some(PassingMode::AbsoluteMajority),
+        Some(PassingMode::TwoThirds),
+        Some(PassingMode::Unanimity),
+        Some(PassingMode::AbstainInDenominator),
+    ];
+
+    fn threshold(mode: Option<PassingMode>, quorum: u8, passing: u8) -> ProposalPassingThreshold {
+        ProposalPassingThreshold {
+            quorum: Percentage::<PercentagePrecision>::from_percent(quorum),
+            passing_threshold: Percentage::<PercentagePrecision>::from_percent(passing),
+            mode,
+        }
+    }
+
+    fn tally(yes: VotingPower, no: VotingPower, abstain: VotingPower, total: VotingPower) -> Tally {
+        Tally {
+            yes,
+            no,
+            abstain,
+            total,
+        }
+    }
+
+    #[test]
+    fn final_outcome_of_each_mode() {
+        use VoteOutcome::*;
+        // (tally, outcome per mode in MODES order), with a 50% quorum and a 0% passing threshold
+        let cases = [
+            (
+                tally(6, 4, 0, 20),
+                [Accepted, Accepted, Rejected, Rejected, Rejected, Accepted],
+            ),
+            (
+                tally(11, 4, 0, 20),
+                [Accepted, Accepted, Accepted, Accepted, Rejected, Accepted],
+            ),
+            (
+                tally(10, 5, 0, 20),
+                [Accepted, Accepted, Rejected, Accepted, Rejected, Accepted],
+            ),
+            (
+                tally(5, 0, 5, 20),
+                [Accepted, Accepted, Rejected, Accepted, Accepted, Rejected],
+            ),
+            (
+                tally(6, 0, 5, 20),
+                [Accepted, Accepted, Rejected, Accepted, Accepted, Accepted],
+            ),
+            (
+                tally(5, 5, 0, 20),
+                [Accepted, Rejected, Rejected, Rejected, Rejected, Rejected],
+            ),
+            (
+                tally(0, 0, 10, 20),
+                [Rejected, Rejected, Rejected, Rejected, Rejected, Rejected],
+            ),
+            (tally(9, 0, 0, 20), [QuorumNotMet; 6]),
+            (tally(0, 0, 0, 0), [QuorumNotMet; 6]),
+        ];
+        for (tally, outcomes) in cases {
+            for (mode, outcome) in MODES.into_iter().zip(outcomes) {
+                assert_eq!(
+                    decide_vote(&threshold(mode.clone(), 50, 0), &tally, true),
+                    Ok(Some(outcome)),
+                    "{:?} {:?}",
+                    mode,
+                    tally
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn early_outcome_of_each_mode() {
+        use VoteOutcome::*;
+        // (tally, outcome per mode in MODES order), with a 20% quorum and a 0% passing threshold
+        let cases = [
+            // quorum not met yet
+            (tally(3, 0, 0, 20), [None; 6]),
+            // remaining votes can still swing the result
+            (
+                tally(8, 2, 0, 20),
+                [None, None, None, None, Some(Rejected), None],
+            ),
+            // passes even if all remaining votes are "no"
+            (
+                tally(11, 0, 0, 20),
+                [
+                    Some(Accepted),
+                    Some(Accepted),
+                    Some(Accepted),
+                    None,
+                    None,
+                    Some(Accepted),
+                ],
+            ),
+            (
+                tally(14, 1, 0, 20),
+                [
+                    Some(Accepted),
+                    Some(Accepted),
+                    Some(Accepted),
+                    Some(Accepted),
+                    Some(Rejected),
+                    Some(Accepted),
+                ],
+            ),
+            // fails even if all remaining votes are "yes"
+            (
+                tally(0, 11, 0, 20),
+                [
+                    None,
+                    Some(Rejected),
+                    Some(Rejected),
+                    Some(Rejected),
+                    Some(Rejected),
+                    Some(Rejected),
+                ],
+            ),
+            (
+                tally(2, 1, 1, 20),
+                [None, None, None, None, Some(Rejected), None],
+            ),
+            (
+                tally(0, 0, 10, 20),
+                [None, None, Some(Rejected), None, None, Some(Rejected)],
+            ),
+            // all votes cast
+            (
+                tally(20, 0, 0, 20),
+                [
+                    Some(Accepted),
+                    Some(Accepted),
+                    Some(Accepted),
+                    Some(Accepted),
+                    Some(Accepted),
+                    Some(Accepted),
+                ],
+            ),
+        ];
+        for (tally, outcomes) in cases {
+            for (mode, outcome) in MODES.into_iter().zip(outcomes) {
+                assert_eq!(
+                    decide_vote(&threshold(mode.clone(), 20, 0), &tally, false),
+                    Ok(outcome),
+                    "{:?} {:?}",
+                    mode,
+                    tally
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn absolute_majority_accepts_early_without_a_mode() {
+        // 11 of 20 is an absolute majority, although 9 more "no" votes would fail a 60% passing threshold.
+        assert_eq!(
+            decide_vote(&threshold(None, 20, 60), &tally(11, 0, 0, 20), false),
+            Ok(Some(VoteOutcome::Accepted))
+        );
+        assert_eq!(
+            decide_vote(&threshold(None, 20, 60), &tally(10, 0, 0, 20), false),
+            Ok(None)
+        );
+        // The quorum still applies.
+        assert_eq!(
+            decide_vote(&threshold(None, 60, 0), &tally(11, 0, 0, 20), false),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn passing_threshold_below_a_majority_is_accepted_when_voting_ends_without_a_mode() {
+        // 2 of 5 reaches a 40% passing threshold, as a multisig pass would report it, but is no absolute majority.
+        assert_eq!(
+            decide_vote(&threshold(None, 20, 40), &tally(2, 0, 0, 5), false),
+            Ok(None)
+        );
+        assert_eq!(
+            decide_vote(&threshold(None, 20, 40), &tally(2, 0, 0, 5), true),
+            Ok(Some(VoteOutcome::Accepted))
+        );
+    }
+
+    #[test]
+    fn passing_threshold_bounds_every_mode() {
+        // 55% of yes+no, 44% of yes+no+abstain, 55% of the total.
+        let split = tally(11, 9, 5, 20);
+        for mode in MODES {
+            let outcome = decide_vote(&threshold(mode.clone(), 20, 60), &split, true);
+            assert_eq!(outcome, Ok(Some(VoteOutcome::Rejected)), "{:?}", mode);
+        }
+        let unanimous = tally(11, 0, 0, 20);
+        for mode in MODES {
+            let outcome = decide_vote(&threshold(mode.clone(), 20, 60), &unanimous, true);
+            assert_ne!(outcome, Ok(Some(VoteOutcome::QuorumNotMet)), "{:?}", mode);
+        }
+    }
+
+    /// Early decisions must agree with the final decision, however the remaining voting power is cast.
+    /// Without a passing mode, the original early rule is kept, which does not guarantee this.
+    #[test]
+    fn early_outcome_is_final_for_every_completion() {
+        const TOTAL: VotingPower = 6;
+        for mode in MODES.into_iter().skip(1) {
+            for (quorum, passing) in [(0, 0), (50, 0), (20, 60), (100, 100)] {
+                let threshold = threshold(mode.clone(), quorum, passing);
+                for yes in 0..=TOTAL {
+                    for no in 0..=TOTAL - yes {
+                        for abstain in 0..=TOTAL - yes - no {
+                            let current = tally(yes, no, abstain, TOTAL);
+                            let early = match decide_vote(&threshold, &current, false) {
+                                Ok(Some(outcome)) => outcome,
+                                _ => continue,
+                            };
+                            let uncast = TOTAL - yes - no - abstain;
+                            for more_yes in 0..=uncast {
+                                for more_no in 0..=uncast - more_yes {
+                                    for more_abstain in 0..=uncast - more_yes - more_no {
+                                        let last = tally(
+                                            yes + more_yes,
+                                            no + more_no,
+                                            abstain + more_abstain,
+                                            TOTAL,
+                                        );
+                                        assert_eq!(
+                                            decide_vote(&threshold, &last, true),
+                                            Ok(Some(early)),
+                                            "{:?} {:?} -> {:?}",
+                                            threshold,
+                                            current,
+                                            last
+                                        );
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_tallies() {
+        for mode in MODES {
+            let threshold = threshold(mode.clone(), 0, 0);
+            // No voting power at all: the quorum is never met, even a zero quorum.
+            assert_eq!(decide_vote(&threshold, &tally(0, 0, 0, 0), false), Ok(None));
+            assert_eq!(
+                decide_vote(&threshold, &tally(0, 0, 0, 0), true),
+                Ok(Some(VoteOutcome::QuorumNotMet))
+            );
+            // Only abstentions: no mode passes.
+            assert_eq!(
+                decide_vote(&threshold, &tally(0, 0, 5, 5), true),
+                Ok(Some(VoteOutcome::Rejected))
+            );
+            // Negative counts are invalid.
+            for invalid in [
+                tally(-1, 0, 0, 5),
+                tally(0, -1, 0, 5),
+                tally(0, 0, -1, 5),
+                tally(0, 0, 0, -1),
+            ] {
+                assert_eq!(
+                    decide_vote(&threshold, &invalid, true),
+                    Err(ProposalError::ArithmeticError)
+                );
+            }
+            // The cast voting power does not fit in u128.
+            let max = VotingPower::MAX;
+            assert_eq!(
+                decide_vote(&threshold, &tally(max, max, max, max), true),
+                Err(ProposalError::ArithmeticError)
+            );
+        }
+        // yes/(yes+no) is one unit above 1/2, invisible in floating point.
+        let huge: VotingPower = 1 << 100;
+        let simple = threshold(Some(PassingMode::SimpleMajority), 0, 0);
+        assert_eq!(
+            decide_vote(&simple, &tally(huge + 1, huge, 0, 2 * huge + 1), true),
+            Ok(Some(VoteOutcome::Accepted))
+        );
+        assert_eq!(
+            decide_vote(&simple, &tally(huge, huge, 0, 2 * huge), true),
+            Ok(Some(VoteOutcome::Rejected))
+        );
+    }
+
+    mod model {
+        use super::*;
+        use num_bigint::{BigInt, BigUint};
+        use proptest::prelude::*;
+
+        /// Reference implementation of `decide_vote`, on unbounded integers.
+        fn model_decide_vote(
+            threshold: &ProposalPassingThreshold,
+            tally: &Tally,
+            voting_ended: bool,
+        ) -> Result<Option<VoteOutcome>, ProposalError> {
+            let [yes, no, abstain, total] =
+                [tally.yes, tally.no, tally.abstain, tally.total].map(BigInt::from);
+            let zero = BigInt::from(0);
+            if [&yes, &no, &abstain, &total].iter().any(|v| **v < zero) {
+                return Err(ProposalError::ArithmeticError);
+            }
+            let cast = &yes + &no + &abstain;
+            if cast > BigInt::from(u128::MAX) {
+                return Err(ProposalError::ArithmeticError);
+            }
+            let reaches = |num: &BigInt, den: &BigInt, pct: &Percentage<PercentagePrecision>| {
+                let (n, d) = pct.as_fraction();
+                *den > zero && num * BigInt::from(d) >= BigInt::from(n) * den
+            };
+            let passes = |yes: &BigInt, no: &BigInt, cast: &BigInt| {
+                let yes_no = yes + no;
+                let pt = &threshold.passing_threshold;
+                match threshold.mode {
+                    None => reaches(yes, &yes_no, pt),
+                    Some(PassingMode::SimpleMajority) => {
+                        yes * 2 > yes_no && reaches(yes, &yes_no, pt)
+                    }
+                    Some(PassingMode::AbsoluteMajority) => {
+                        yes * 2 > total && reaches(yes, &total, pt)
+                    }
+                    Some(PassingMode::TwoThirds) => {
+                        yes_no > zero && yes * 3 >= &yes_no * 2 && reaches(yes, &yes_no, pt)
+                    }
+                    Some(PassingMode::Unanimity) => {
+                        *no == zero && *yes > zero && reaches(yes, &yes_no, pt)
+                    }
+                    Some(PassingMode::AbstainInDenominator) => {
+                        yes * 2 > *cast && reaches(yes, cast, pt)
+                    }
+                }
+            };
+            let quorum_met = reaches(&cast, &total, &threshold.quorum);
+            if voting_ended {
+                return Ok(Some(match (quorum_met, passes(&yes, &no, &cast)) {
+                    (false, _) => VoteOutcome::QuorumNotMet,
+                    (true, true) => VoteOutcome::Accepted,
+                    (true, false) => VoteOutcome::Rejected,
+                }));
+            }
+            if !quorum_met {
+                return Ok(None);
+            }
+            let uncast = (&total - &cast).max(zero.clone());
+            let all_cast = &cast + &uncast;
+            let accepted_anyway = passes(&yes, &(&no + &uncast), &all_cast);
+            let rejected_anyway = !passes(&(&yes + &uncast), &no, &all_cast);
+            Ok(match threshold.mode {
+                None => (&yes * 2 > total).then_some(VoteOutcome::Accepted),
+                Some(_) if accepted_anyway => Some(VoteOutcome::Accepted),
+                Some(_) if rejected_anyway => Some(VoteOutcome::Rejected),
+                Some(_) => None,
+            })
+        }
+
+        fn voting_power() -> impl Strategy<Value = VotingPower> {
+            prop_oneof![
+                0..100 as VotingPower,
+                (VotingPower::MAX - 100)..=VotingPower::MAX,
+                0..=VotingPower::MAX,
+                any::<VotingPower>(),
+            ]
+        }
+
+        fn passing_threshold() -> impl Strategy<Value = ProposalPassingThreshold> {
+            (0..=10_000u16, 0..=10_000u16, 0..MODES.len()).prop_map(|(quorum, passing, mode)| {
+                ProposalPassingThreshold {
+                    quorum: Percentage::<PercentagePrecision>::from_basis_points(quorum),
+                    passing_threshold: Percentage::<PercentagePrecision>::from_basis_points(
+                        passing,
+                    ),
+                    mode: MODES[mode].clone(),
+                }
+            })
+        }
+
+        proptest! {
+            #[test]
+            fn wide_mul_is_exact(a in any::<u128>(), b in any::<u128>()) {
+                let (high, low) = wide_mul(a, b);
+                let product = (BigUint::from(high) << 128) + BigUint::from(low);
+                prop_assert_eq!(product, BigUint::from(a) * BigUint::from(b));
+            }
+
+            #[test]
+            fn decide_vote_matches_model(
+                threshold in passing_threshold(),
+                yes in voting_power(),
+                no in voting_power(),
+                abstain in voting_power(),
+                total in voting_power(),
+                voting_ended in any::<bool>(),
+            ) {
+                let tally = tally(yes, no, abstain, total);
+                prop_assert_eq!(
+                    decide_vote(&threshold, &tally, voting_ended),
+                    model_decide_vote(&threshold, &tally, voting_ended)
+                );
+            }
+
+            #[test]
+            fn decide_vote_matches_model_on_small_tallies(
+                threshold in passing_threshold(),
+                yes in 0..50 as VotingPower,
+                no in 0..50 as VotingPower,
+                abstain in 0..50 as VotingPower,
+                total in 0..200 as VotingPower,
+                voting_ended in any::<bool>(),
+            ) {
+                let tally = tally(yes, no, abstain, total);
+                prop_assert_eq!(
+                    decide_vote(&threshold, &tally, voting_ended),
+                    model_decide_vote(&threshold, &tally, voting_ended)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::types::*;
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+/// Sponsorship phase between submission and validation.
+///
+/// A proposal only goes to validation after enough distinct sponsors called `sponsor`.
+/// Members of the Sponsor role can always sponsor. If `token` is set, token holders above a balance can too.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SponsorshipConfig {
+    /// Number of distinct sponsors required. If 0, proposals go to validation right away.
+    pub sponsors_required: u64,
+    /// Time after submission during which sponsors can be collected, in nano-seconds.
+    pub sponsorship_period: TimeNs,
+    /// Lets token holders sponsor, on top of the Sponsor role.
+    pub token: Option<TokenSponsorship>,
+}
+
+/// Lets holders of a token sponsor proposals.
+///
+/// The balance is only checked when `sponsor` is called and is neither locked nor snapshotted, so
+/// one holder can move the same tokens through several principals and count as several sponsors.
+/// Token sponsorship is therefore a spam filter, not a measure of distinct support: it costs
+/// `min_balance` to hold, not `min_balance` per sponsor. Use the Sponsor role where that matters.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TokenSponsorship {
+    /// The ICRC-1 ledger of the token.
+    pub ledger: Principal,
+    /// Minimum balance of the default account of a sponsor.
+    pub min_balance: u128,
+}
+
+/// The sponsorship of a proposal. Parameters are fixed at submission time.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Sponsorship {
+    pub sponsors_required: u64,
+    /// Time after which the proposal expires, unless it has enough sponsors.
+    pub deadline: TimeNs,
+    /// Sponsors in the order they sponsored.
+    pub sponsors: Vec<Principal>,
+}
+
+impl Sponsorship {
+    pub fn new(config: &SponsorshipConfig, now: TimeNs) -> Self {
+        Self {
+            sponsors_required: config.sponsors_required,
+            deadline: now.saturating_add(config.sponsorship_period),
+            sponsors: vec![],
+        }
+    }
+
+    pub fn is_reached(&self) -> bool {
+        self.sponsors.len() as u64 >= self.sponsors_required
+    }
+
+    pub fn is_expired(&self, now: TimeNs) -> bool {
+        !self.is_reached() && now >= self.deadline
+    }
+
+    /// Adds a sponsor. Returns whether the sponsorship is now reached.
+    pub fn add_sponsor(&mut self, sponsor: Principal, now: TimeNs) -> Result<bool, ReturnError> {
+        if self.is_reached() {
+            return Err(ReturnError::IncorrectProposalState);
+        }
+        if self.is_expired(now) {
+            return Err(ReturnError::Expired);
+        }
+        if self.sponsors.contains(&sponsor) {
+            return Err(ReturnError::AlreadyExists);
+        }
+        self.sponsors.push(sponsor);
+        Ok(self.is_reached())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(i: u8) -> Principal {
+        Principal::from_slice(&[i; 29])
+    }
+
+    fn sponsorship(sponsors_required: u64) -> Sponsorship {
+        let config = SponsorshipConfig {
+            sponsors_required,
+            sponsorship_period: 100,
+            token: None,
+        };
+        Sponsorship::new(&config, 1_000)
+    }
+
+    #[test]
+    fn reached_with_distinct_sponsors() {
+        let mut s = sponsorship(2);
+        assert_eq!(s.deadline, 1_100);
+        assert_eq!(s.add_sponsor(principal(1), 1_000), Ok(false));
+        assert_eq!(
+            s.add_sponsor(principal(1), 1_010),
+            Err(ReturnError::AlreadyExists)
+        );
+        assert_eq!(s.add_sponsor(principal(2), 1_099), Ok(true));
+        assert_eq!(
+            s.add_sponsor(principal(3), 1_099),
+            Err(ReturnError::IncorrectProposalState)
+        );
+        // A reached sponsorship never expires.
+        assert!(!s.is_expired(u64::MAX));
+    }
+
+    #[test]
+    fn expires_at_deadline() {
+        let mut s = sponsorship(2);
+        assert_eq!(s.add_sponsor(principal(1), 1_000), Ok(false));
+        assert!(!s.is_expired(1_099));
+        assert!(s.is_expired(1_100));
+        assert_eq!(
+            s.add_sponsor(principal(2), 1_100),
+            Err(ReturnError::Expired)
+        );
+        assert_eq!(s.sponsors, vec![principal(1)]);
+    }
+}
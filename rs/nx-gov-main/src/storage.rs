@@ -1,6 +1,9 @@
+use crate::deposit::*;
+use crate::embargo::*;
 use crate::execution::*;
+use crate::liveness::*;
 use crate::proposal::*;
-use crate::types::*;
+use crate::sponsor::*;
 
 use candid::{Decode, Encode, Principal};
 use ic_stable_structures::{
@@ -9,9 +12,12 @@ use ic_stable_structures::{
 };
 use std::borrow::Cow;
 
+/// The bound of a stored proposal cannot change once `PROPOSALS` holds data, so it leaves room
+/// for fields added later. `proposal_bound_covers_the_largest_proposal` checks it still covers
+/// the largest proposal.
 impl Storable for Proposal {
     const BOUND: Bound = Bound::Bounded {
-        max_size: 379,
+        max_size: 1536,
         is_fixed_size: false,
     };
 
@@ -24,18 +30,31 @@ impl Storable for Proposal {
     }
 }
 
-impl Storable for ProposalPassingThreshold {
+/// A proposal stored in the original layout, before the optional fields added for standing orders,
+/// proposal classes, choices, and appeals. Only read when migrating to the current layout.
+///
+/// The bound must stay as it was, otherwise the vector with the stored proposals cannot be opened.
+pub struct ProposalV0(pub Vec<u8>);
+
+impl ProposalV0 {
+    /// Decodes into the current layout. The added fields are all optional, so they decode as `None`.
+    pub fn decode(&self) -> Proposal {
+        Decode!(&self.0, Proposal).unwrap()
+    }
+}
+
+impl Storable for ProposalV0 {
     const BOUND: Bound = Bound::Bounded {
-        max_size: 25,
-        is_fixed_size: true,
+        max_size: 379,
+        is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+        Cow::Borrowed(&self.0)
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        Self(bytes.into_owned())
     }
 }
 
@@ -99,6 +118,102 @@ impl Storable for ProposalExec {
     }
 }
 
+impl Storable for ProposalDeposit {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl Storable for ActivationCondition {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl Storable for ConditionEvaluation {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl Storable for Supersession {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl Storable for Sponsorship {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl Storable for EmbargoedPayload {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl Storable for TallyRecord {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl Storable for ProposalLiveness {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
 impl Storable for ProposalRevoke {
     const BOUND: Bound = Bound::Unbounded;
 
@@ -194,3 +309,203 @@ impl Storable for StablePrincipal {
         Self(Blob::try_from(bytes.as_ref()).unwrap())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::appeal::*;
+    use crate::choice::*;
+    use crate::types::*;
+    use candid::CandidType;
+    use serde::Deserialize;
+
+    /// The proposal layout before storage version 1, with a subset of the proposal states.
+    #[derive(CandidType, Deserialize)]
+    struct LegacyProposal {
+        metadata_id: Index,
+        payload_id: Index,
+        auto_execute: bool,
+        activates: Schedule,
+        expires: Schedule,
+        created_at: TimeNs,
+        proposer: Principal,
+        validated: Option<bool>,
+        voting_end_time: Option<TimeNs>,
+        passing_threshold: Option<LegacyPassingThreshold>,
+        state: LegacyProposalState,
+        votes_yes: VotingPower,
+        votes_no: VotingPower,
+        votes_abstain: VotingPower,
+        total_voting_power: VotingPower,
+    }
+
+    #[derive(CandidType, Deserialize)]
+    struct LegacyPassingThreshold {
+        quorum: Percentage<PercentagePrecision>,
+        passing_threshold: Percentage<PercentagePrecision>,
+    }
+
+    #[derive(CandidType, Deserialize)]
+    enum LegacyProposalState {
+        Submitted,
+        Accepted,
+    }
+
+    /// Encodes a proposal in the original layout, accepted or not.
+    pub(crate) fn legacy_proposal(accepted: bool) -> ProposalV0 {
+        let legacy = LegacyProposal {
+            metadata_id: 3,
+            payload_id: 4,
+            auto_execute: true,
+            activates: Schedule::At(10),
+            expires: Schedule::In(20),
+            created_at: 5,
+            proposer: Principal::anonymous(),
+            validated: Some(true),
+            voting_end_time: Some(30),
+            passing_threshold: Some(LegacyPassingThreshold {
+                quorum: Percentage::<PercentagePrecision>::from_percent(20),
+                passing_threshold: Percentage::<PercentagePrecision>::from_percent(50),
+            }),
+            state: match accepted {
+                true => LegacyProposalState::Accepted,
+                false => LegacyProposalState::Submitted,
+            },
+            votes_yes: 7,
+            votes_no: 1,
+            votes_abstain: 2,
+            total_voting_power: 10,
+        };
+        ProposalV0(Encode!(&legacy).unwrap())
+    }
+
+    #[test]
+    fn legacy_proposal_decodes_into_current_layout() {
+        let legacy = legacy_proposal(true);
+        match ProposalV0::BOUND {
+            Bound::Bounded { max_size, .. } => assert!(legacy.0.len() <= max_size as usize),
+            Bound::Unbounded => unreachable!(),
+        }
+
+        let proposal = legacy.decode();
+        assert_eq!(proposal.payload_id, 4);
+        assert_eq!(proposal.expires, Schedule::In(20));
+        assert_eq!(proposal.state, ProposalState::Accepted);
+        assert_eq!(proposal.votes_yes, 7);
+        let threshold = proposal.passing_threshold.clone().unwrap();
+        assert_eq!(
+            threshold.passing_threshold,
+            Percentage::<PercentagePrecision>::from_percent(50)
+        );
+        assert_eq!(threshold.mode, None);
+        assert_eq!(proposal.runs, None);
+        assert_eq!(proposal.runs(), 0);
+        assert!(proposal.recurrence.is_none());
+        assert!(proposal.class.is_none());
+        assert!(proposal.choices.is_none());
+        assert!(proposal.appeal.is_none());
+    }
+
+    /// Every proposal state, with the largest step for those that carry one.
+    fn all_proposal_states() -> Vec<ProposalState> {
+        let step = || ExecutionStep {
+            step: u8::MAX,
+            state: ExecutionStepState::Succeeded,
+        };
+        vec![
+            ProposalState::Submitted,
+            ProposalState::ValidationFailed,
+            ProposalState::Open,
+            ProposalState::Accepted,
+            ProposalState::Executing(step()),
+            ProposalState::Succeeded,
+            ProposalState::Failed(step()),
+            ProposalState::Expired,
+            ProposalState::Rejected,
+            ProposalState::Revoked,
+            ProposalState::QuorumNotMet,
+            ProposalState::ForceExecuting(step()),
+            ProposalState::ForceExecutionSucceeded,
+            ProposalState::ForceExecutionFailed(step()),
+            ProposalState::Superseded,
+            ProposalState::Appealed,
+            ProposalState::AppealRejected,
+        ]
+    }
+
+    /// A proposal in `state` with every optional field set to its largest encoding.
+    fn largest_proposal(state: ProposalState, outcome: ProposalState) -> Proposal {
+        let principal = Principal::from_slice(&[u8::MAX; 29]);
+        let threshold = ProposalPassingThreshold {
+            quorum: Percentage::<PercentagePrecision>::from_percent(100),
+            passing_threshold: Percentage::<PercentagePrecision>::from_percent(100),
+            mode: Some(PassingMode::AbstainInDenominator),
+        };
+        // Candid encodes voting power as a variable-length `int`, longest at the minimum.
+        let power = VotingPower::MIN;
+        Proposal {
+            metadata_id: Index::MAX,
+            payload_id: Index::MAX,
+            auto_execute: true,
+            activates: Schedule::At(TimeNs::MAX),
+            expires: Schedule::At(TimeNs::MAX),
+            recurrence: Some(Recurrence {
+                interval: TimeNs::MAX,
+                max_runs: Some(u64::MAX),
+            }),
+            class: Some(ProposalClass::Emergency),
+            created_at: TimeNs::MAX,
+            proposer: principal,
+            validated: Some(true),
+            voting_end_time: Some(TimeNs::MAX),
+            passing_threshold: Some(threshold.clone()),
+            state,
+            votes_yes: power,
+            votes_no: power,
+            votes_abstain: power,
+            total_voting_power: power,
+            choices: Some(Choices {
+                payload_ids: vec![Index::MAX; MAX_CHOICES],
+                tallies: vec![power; MAX_CHOICES],
+                selection: ChoiceSelection::Threshold(
+                    Percentage::<PercentagePrecision>::from_percent(100),
+                ),
+                winner: Some(u8::MAX),
+            }),
+            runs: Some(u64::MAX),
+            appeal: Some(Appeal {
+                appellant: principal,
+                appealed_at: TimeNs::MAX,
+                first_round: VotingRound {
+                    outcome,
+                    voting_end_time: Some(TimeNs::MAX),
+                    passing_threshold: Some(threshold),
+                    votes_yes: power,
+                    votes_no: power,
+                    votes_abstain: power,
+                    total_voting_power: power,
+                },
+            }),
+        }
+    }
+
+    #[test]
+    fn proposal_bound_covers_the_largest_proposal() {
+        let Bound::Bounded { max_size, .. } = Proposal::BOUND else {
+            unreachable!()
+        };
+        for state in all_proposal_states() {
+            for outcome in all_proposal_states() {
+                let proposal = largest_proposal(state.clone(), outcome);
+                let len = proposal.to_bytes().len();
+                assert!(
+                    len <= max_size as usize,
+                    "{:?} encodes to {} bytes, over the bound of {}",
+                    state,
+                    len,
+                    max_size
+                );
+            }
+        }
+    }
+}
@@ -1,3 +1,88 @@
-//! Not implemented because ic_cdk_timers does not allow async closures.
-//!
+//! Auto-executor for proposals with `auto_execute` set, liveness monitor of vote managers, and storage migration.
 //!
+//! `ic_cdk_timers` does not allow async closures, so each tick spawns one execution per due proposal.
+
+use crate::access::*;
+use crate::memory::*;
+use crate::proposal::*;
+use crate::{escalate_stale_proposal, execute_and_settle, finalize_stale_proposal};
+
+use ic_cdk_timers::TimerId;
+use std::cell::Cell;
+use std::time::Duration;
+
+thread_local! {
+    static AUTO_EXECUTOR: Cell<Option<TimerId>> = Cell::new(None);
+    static LIVENESS_MONITOR: Cell<Option<TimerId>> = Cell::new(None);
+}
+
+/// Continues the storage migration, one batch per message, until it is complete.
+pub(crate) fn continue_storage_migration() {
+    if !migrate_storage() {
+        ic_cdk_timers::set_timer(Duration::ZERO, continue_storage_migration);
+    }
+}
+
+/// Restarts the auto-executor with the configured interval. The auto-executor stays stopped if no interval is set.
+pub(crate) fn restart_auto_executor() {
+    if let Some(timer_id) = AUTO_EXECUTOR.with(|t| t.take()) {
+        ic_cdk_timers::clear_timer(timer_id);
+    }
+    let interval = get_config().ok().and_then(|c| c.auto_execute_interval);
+    if let Some(interval) = interval.filter(|i| *i > 0) {
+        let timer_id =
+            ic_cdk_timers::set_timer_interval(Duration::from_nanos(interval), auto_execute);
+        AUTO_EXECUTOR.with(|t| t.set(Some(timer_id)));
+    }
+}
+
+/// Executes every pending `auto_execute` proposal that may be due.
+/// Proposals that are not executable yet, including those whose activation condition does not hold, are retried on the next tick.
+fn auto_execute() {
+    if !has_role(UserRole::Executor, ic_cdk::api::id()) {
+        return;
+    }
+    for proposal_id in get_pending_proposal_ids() {
+        let is_candidate = get_proposal_by_id(proposal_id).is_ok_and(|p| {
+            p.auto_execute && matches!(p.state, ProposalState::Open | ProposalState::Accepted)
+        });
+        if is_candidate {
+            ic_cdk::spawn(async move {
+                let _ = execute_and_settle(proposal_id).await;
+            });
+        }
+    }
+}
+
+/// Restarts the liveness monitor with the configured interval. The monitor stays stopped if liveness is not configured.
+pub(crate) fn restart_liveness_monitor() {
+    if let Some(timer_id) = LIVENESS_MONITOR.with(|t| t.take()) {
+        ic_cdk_timers::clear_timer(timer_id);
+    }
+    let interval = get_config()
+        .ok()
+        .and_then(|c| c.liveness)
+        .map(|l| l.check_interval);
+    if let Some(interval) = interval.filter(|i| *i > 0) {
+        let timer_id =
+            ic_cdk_timers::set_timer_interval(Duration::from_nanos(interval), check_liveness);
+        LIVENESS_MONITOR.with(|t| t.set(Some(timer_id)));
+    }
+}
+
+/// Escalates every stale proposal as configured.
+/// Proposals that can be finalized are not escalated to the Revokers.
+fn check_liveness() {
+    let config = match get_config().ok().and_then(|c| c.liveness) {
+        Some(config) => config,
+        None => return,
+    };
+    for (proposal_id, last_activity) in get_stale_proposals() {
+        if config.auto_finalize && finalize_stale_proposal(proposal_id).is_ok() {
+            continue;
+        }
+        if config.notify_revokers {
+            escalate_stale_proposal(proposal_id, last_activity);
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use crate::appeal::AppealConfig;
+use crate::deposit::DepositConfig;
+use crate::liveness::LivenessConfig;
+use crate::sponsor::SponsorshipConfig;
 use crate::validate::Validate;
 
 use candid::{CandidType, Principal};
@@ -25,6 +29,16 @@ pub enum ReturnError {
     PreValidateFailed,
     PostValidateFailed,
     ExecutionFailed,
+    DepositError,
+    RateLimitExceeded,
+    PendingProposalLimitExceeded,
+    SizeLimitExceeded,
+    ActivationConditionNotMet,
+    ConflictingProposalSucceeded,
+    PayloadNotRevealed,
+    PayloadHashMismatch,
+    PayloadNotValidated,
+    MigrationInProgress,
 }
 
 /// nano seconds since UNIX Epoch.
@@ -49,6 +63,106 @@ pub struct Config {
     pub validator_hook: Option<Principal>,
     /// Vote manager notification hook. If set then a timer is used to notify the vote manager.
     pub vote_manager_hook: Option<Principal>,
+    /// Submission deposit. If set, proposers must pay a deposit in ICRC-1 tokens when submitting.
+    pub deposit: Option<DepositConfig>,
+    /// Submission limits. If set, `submit` rejects submissions that exceed any of the limits.
+    pub submission_limits: Option<SubmissionLimits>,
+    /// Polling interval of the auto-executor, in nano-seconds.
+    /// If set, a timer executes due `auto_execute` proposals. The canister must have the executor role.
+    pub auto_execute_interval: Option<TimeNs>,
+    /// Sponsorship phase. If set, proposals only go to validation once enough sponsors have called `sponsor`.
+    pub sponsorship: Option<SponsorshipConfig>,
+    /// Appeals of rejected proposals. If `None`, proposals cannot be appealed.
+    pub appeal: Option<AppealConfig>,
+    /// Validation floors of emergency proposals. If `None`, emergency proposals cannot be submitted.
+    pub emergency: Option<EmergencyConfig>,
+    /// Monitoring of vote managers. If `None`, stale proposals are neither listed nor escalated.
+    pub liveness: Option<LivenessConfig>,
+    /// Canisters that activation conditions may call. If `None`, proposals cannot have an activation condition.
+    /// Conditions of existing proposals whose canister is removed from the list are no longer met.
+    pub condition_canisters: Option<Vec<Principal>>,
+}
+
+impl Config {
+    pub fn is_condition_canister(&self, canister_id: &Principal) -> bool {
+        self.condition_canisters
+            .as_ref()
+            .is_some_and(|canisters| canisters.contains(canister_id))
+    }
+}
+
+/// Validation floors of emergency proposals, used instead of `Config.min_voting_period` and `Config.min_passing_threshold`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct EmergencyConfig {
+    /// Minimum voting period, in nano-seconds. May be shorter than `Config.min_voting_period`.
+    pub min_voting_period: TimeNs,
+    /// Minimum passing threshold. Must be at least `Config.min_passing_threshold`.
+    pub min_passing_threshold: ProposalPassingThreshold,
+}
+
+impl Config {
+    /// Whether the emergency floors are at least as strict as the standard thresholds.
+    pub fn is_emergency_config_valid(&self) -> bool {
+        self.emergency.as_ref().map_or(true, |e| {
+            e.min_passing_threshold.is_valid()
+                && e.min_passing_threshold
+                    .all_fields_gte(&self.min_passing_threshold)
+        })
+    }
+}
+
+/// Limits on proposal submissions, checked before anything is written to stable memory.
+/// Each limit is disabled when `None`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct SubmissionLimits {
+    /// Maximum number of submissions per proposer within a rate limit window.
+    pub max_submissions_per_window: Option<u64>,
+    /// Length of the rate limit window, in nano-seconds.
+    pub rate_limit_window: TimeNs,
+    /// Maximum number of proposals that have not reached a final state.
+    pub max_pending_proposals: Option<u64>,
+    /// Maximum encoded size of the proposal metadata, in bytes.
+    pub max_metadata_bytes: Option<u64>,
+    /// Maximum encoded size of the proposal payload, in bytes.
+    pub max_payload_bytes: Option<u64>,
+}
+
+/// Rate limit window of a proposer: (window start time, submissions in the window).
+pub type SubmissionWindow = (TimeNs, u64);
+
+impl SubmissionLimits {
+    /// Counts `submissions` new submissions at `now` towards the proposer's rate limit window.
+    /// Returns the updated window, or an error if they do not all fit in the window.
+    pub fn count_submissions(
+        &self,
+        window: Option<SubmissionWindow>,
+        now: TimeNs,
+        submissions: u64,
+    ) -> Result<SubmissionWindow, ReturnError> {
+        let (start, count) = match window {
+            Some((start, count)) if now < start.saturating_add(self.rate_limit_window) => {
+                (start, count)
+            }
+            _ => (now, 0),
+        };
+        if self
+            .max_submissions_per_window
+            .is_some_and(|max| count.saturating_add(submissions) > max)
+        {
+            return Err(ReturnError::RateLimitExceeded);
+        }
+        Ok((start, count.saturating_add(submissions)))
+    }
+
+    pub fn is_metadata_size_allowed(&self, size: usize) -> bool {
+        self.max_metadata_bytes
+            .map_or(true, |max| size as u64 <= max)
+    }
+
+    pub fn is_payload_size_allowed(&self, size: usize) -> bool {
+        self.max_payload_bytes
+            .map_or(true, |max| size as u64 <= max)
+    }
 }
 
 pub type VotingPower = i128; // A negative value nullifies a prior vote.
@@ -61,7 +175,29 @@ pub struct ProposalPassingThreshold {
     /// The percentage of the vote that need to do a vote action for a proposal to pass or reject.
     pub quorum: Percentage<PercentagePrecision>,
     /// The percentage of yes/(yes+no) for a proposal to pass.
+    /// Modes that count votes in another denominator apply it to that denominator.
     pub passing_threshold: Percentage<PercentagePrecision>,
+    /// How the votes are counted.
+    /// If `None`, yes/(yes+no) must reach `passing_threshold`, and the vote only ends early on an absolute majority.
+    pub mode: Option<PassingMode>,
+}
+
+/// How the votes of a proposal are counted. Chosen by the validator.
+///
+/// Each mode sets a floor on top of `passing_threshold`, so a mode can never be weaker than the
+/// `quorum` and `passing_threshold` bounded by `Config.min_passing_threshold`.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PassingMode {
+    /// More than half of yes+no.
+    SimpleMajority,
+    /// More than half of the total voting power.
+    AbsoluteMajority,
+    /// At least two thirds of yes+no.
+    TwoThirds,
+    /// At least one "yes" vote and no "no" votes.
+    Unanimity,
+    /// More than half of yes+no+abstain.
+    AbstainInDenominator,
 }
 
 impl Default for ProposalPassingThreshold {
@@ -69,6 +205,7 @@ impl Default for ProposalPassingThreshold {
         Self {
             quorum: Percentage::<PercentagePrecision>::from_percent(20),
             passing_threshold: Percentage::<PercentagePrecision>::from_percent(20),
+            mode: None,
         }
     }
 }
@@ -80,9 +217,22 @@ impl Validate for ProposalPassingThreshold {
 }
 
 impl ProposalPassingThreshold {
+    /// The mode is not compared, since every mode is at least as strict as its `quorum` and `passing_threshold`.
     pub fn all_fields_gte(&self, other: &Self) -> bool {
         self.quorum >= other.quorum && self.passing_threshold >= other.passing_threshold
     }
+
+    /// Raises `quorum` and `passing_threshold` to at least those of `floor`. The mode is kept.
+    pub fn raised_to(&self, floor: &Self) -> Self {
+        Self {
+            quorum: self.quorum.clone().max(floor.quorum.clone()),
+            passing_threshold: self
+                .passing_threshold
+                .clone()
+                .max(floor.passing_threshold.clone()),
+            mode: self.mode.clone(),
+        }
+    }
 }
 
 /// Schedule is either an absolute time or a relative time.
@@ -186,6 +336,10 @@ impl Percentage<u16> {
     pub fn from_ppb(ppb: u32) -> Self {
         Self((ppb / 25_000) as u16)
     }
+    /// The exact percentage as (numerator, denominator).
+    pub fn as_fraction(&self) -> (u128, u128) {
+        (self.0 as u128, 40_000)
+    }
 }
 
 impl From<f32> for Percentage<u16> {
@@ -205,3 +359,123 @@ impl Validate for Percentage<u16> {
         self.0 <= 40_000
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: TimeNs = 60 * 1_000_000_000;
+
+    fn limits(max_submissions: u64) -> SubmissionLimits {
+        SubmissionLimits {
+            max_submissions_per_window: Some(max_submissions),
+            rate_limit_window: WINDOW,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rate_limit_counts_within_window() {
+        let limits = limits(2);
+        let window = limits.count_submissions(None, 100, 1).unwrap();
+        assert_eq!(window, (100, 1));
+        let window = limits.count_submissions(Some(window), 200, 1).unwrap();
+        assert_eq!(window, (100, 2));
+        assert_eq!(
+            limits.count_submissions(Some(window), 100 + WINDOW - 1, 1),
+            Err(ReturnError::RateLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn rate_limit_resets_after_window() {
+        let limits = limits(1);
+        let window = limits.count_submissions(None, 100, 1).unwrap();
+        assert!(limits.count_submissions(Some(window), 101, 1).is_err());
+        assert_eq!(
+            limits.count_submissions(Some(window), 100 + WINDOW, 1),
+            Ok((100 + WINDOW, 1))
+        );
+    }
+
+    #[test]
+    fn rate_limit_counts_batches_whole() {
+        let limits = limits(3);
+        let window = limits.count_submissions(None, 100, 2).unwrap();
+        assert_eq!(window, (100, 2));
+        assert_eq!(
+            limits.count_submissions(Some(window), 200, 2),
+            Err(ReturnError::RateLimitExceeded)
+        );
+        assert_eq!(limits.count_submissions(Some(window), 200, 1), Ok((100, 3)));
+    }
+
+    #[test]
+    fn disabled_limits_allow_everything() {
+        let limits = SubmissionLimits {
+            rate_limit_window: WINDOW,
+            ..Default::default()
+        };
+        assert_eq!(
+            limits.count_submissions(Some((0, u64::MAX - 1)), 0, 1),
+            Ok((0, u64::MAX))
+        );
+        assert!(limits.is_metadata_size_allowed(usize::MAX));
+        assert!(limits.is_payload_size_allowed(usize::MAX));
+    }
+
+    #[test]
+    fn thresholds_raised_to_floor() {
+        let threshold = ProposalPassingThreshold {
+            quorum: Percentage::<PercentagePrecision>::from_percent(30),
+            passing_threshold: Percentage::<PercentagePrecision>::from_percent(50),
+            mode: Some(PassingMode::TwoThirds),
+        };
+        let floor = ProposalPassingThreshold {
+            quorum: Percentage::<PercentagePrecision>::from_percent(40),
+            passing_threshold: Percentage::<PercentagePrecision>::from_percent(20),
+            mode: None,
+        };
+        let raised = threshold.raised_to(&floor);
+        assert_eq!(raised.quorum, floor.quorum);
+        assert_eq!(raised.passing_threshold, threshold.passing_threshold);
+        assert_eq!(raised.mode, Some(PassingMode::TwoThirds));
+        assert!(raised.all_fields_gte(&threshold) && raised.all_fields_gte(&floor));
+    }
+
+    #[test]
+    fn emergency_thresholds_must_not_be_weaker() {
+        let mut config = Config::default();
+        assert!(config.is_emergency_config_valid());
+        config.min_passing_threshold = ProposalPassingThreshold::default();
+        config.emergency = Some(EmergencyConfig {
+            min_voting_period: 0,
+            min_passing_threshold: ProposalPassingThreshold {
+                quorum: Percentage::<PercentagePrecision>::from_percent(60),
+                passing_threshold: Percentage::<PercentagePrecision>::from_percent(10),
+                mode: None,
+            },
+        });
+        assert!(!config.is_emergency_config_valid());
+        config
+            .emergency
+            .as_mut()
+            .unwrap()
+            .min_passing_threshold
+            .passing_threshold = Percentage::<PercentagePrecision>::from_percent(67);
+        assert!(config.is_emergency_config_valid());
+    }
+
+    #[test]
+    fn size_limits_are_inclusive() {
+        let limits = SubmissionLimits {
+            max_metadata_bytes: Some(10),
+            max_payload_bytes: Some(20),
+            ..Default::default()
+        };
+        assert!(limits.is_metadata_size_allowed(10));
+        assert!(!limits.is_metadata_size_allowed(11));
+        assert!(limits.is_payload_size_allowed(20));
+        assert!(!limits.is_payload_size_allowed(21));
+    }
+}
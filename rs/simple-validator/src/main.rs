@@ -7,7 +7,7 @@ use crate::access::*;
 use crate::memory::*;
 use crate::types::*;
 
-use candid::{decode_one, encode_args, encode_one, Principal};
+use candid::{decode_one, encode_args, encode_one, Principal, Reserved};
 use ic_cdk_macros::{query, update};
 
 /// The governance canister may notify this canister of a new proposal.
@@ -15,7 +15,32 @@ use ic_cdk_macros::{query, update};
 #[update]
 pub async fn notify_validator(proposal_id: Index, payload: ProposalPayload) {
     assert_eq!(ic_cdk::api::caller(), get_governance());
-    let _ = validate_and_respond(proposal_id, &payload).await;
+    // Left to `sync_with_governance` if the class cannot be fetched.
+    if let Ok(class) = fetch_proposal_class(get_governance(), proposal_id).await {
+        let _ = validate_and_respond(proposal_id, std::slice::from_ref(&payload), &class).await;
+    }
+}
+
+async fn fetch_proposal_class(
+    governance: Principal,
+    proposal_id: Index,
+) -> Result<ProposalClass, ReturnError> {
+    let res = ic_cdk::api::call::call_raw128(
+        governance,
+        "get_proposal_states_with_class",
+        &encode_args((proposal_id, 1u64)).unwrap(),
+        0,
+    )
+    .await
+    .map_err(|_| ReturnError::InterCanisterCallError)?;
+    // The state is not needed here, so it is not decoded.
+    let states: Vec<(Reserved, ProposalClass)> =
+        decode_one(&res).map_err(|_| ReturnError::InterCanisterCallError)?;
+    states
+        .into_iter()
+        .next()
+        .map(|(_, class)| class)
+        .ok_or(ReturnError::InterCanisterCallError)
 }
 
 #[update]
@@ -23,29 +48,29 @@ pub async fn sync_with_governance() -> Result<(), ReturnError> {
     let config = memory::get_config()?;
     let res = ic_cdk::api::call::call_raw128(
         config.governance_canister,
-        "get_all_submitted_proposal_ids",
+        "get_all_submitted_proposal_ids_with_class",
         &encode_one(()).unwrap(),
         0,
     )
     .await
     .map_err(|_| ReturnError::InterCanisterCallError)?;
-    let result: Vec<Index> = decode_one(&res).unwrap();
+    let result: Vec<(Index, ProposalClass)> = decode_one(&res).unwrap();
 
     // Set PropsalState for each non-existing proposal
-    for index in result {
+    for (index, class) in result {
         if get_proposal_validation(index).is_none() {
             let res = ic_cdk::api::call::call_raw128(
                 config.governance_canister,
-                "get_proposal_payload",
+                "get_proposal_choice_payloads",
                 &encode_one(index).unwrap(),
                 0,
             )
             .await
             .map_err(|_| ReturnError::InterCanisterCallError)?;
-            let result: Option<ProposalPayload> = decode_one(&res).unwrap();
+            let payloads: Vec<ProposalPayload> = decode_one(&res).unwrap();
 
-            if let Some(payload) = result {
-                let validated = validate_and_respond(index, &payload).await;
+            if !payloads.is_empty() {
+                let validated = validate_and_respond(index, &payloads, &class).await;
                 add_proposal_validation(index, validated);
             }
         }
@@ -54,11 +79,16 @@ pub async fn sync_with_governance() -> Result<(), ReturnError> {
     Ok(())
 }
 
-pub async fn validate_and_respond(proposal_id: Index, payload: &ProposalPayload) -> bool {
-    let validated = validate_payload(proposal_id, payload);
+/// Validates every candidate payload of a proposal. A multi-option proposal has several.
+pub async fn validate_and_respond(
+    proposal_id: Index,
+    payloads: &[ProposalPayload],
+    class: &ProposalClass,
+) -> bool {
+    let validated = payloads.iter().all(|p| validate_payload(proposal_id, p));
     let args_raw = match validated {
         true => {
-            let (voting_end_time, threshold) = set_threshold(payload);
+            let (voting_end_time, threshold) = set_threshold(&payloads[0], class);
 
             encode_args((proposal_id, Some(voting_end_time), Some(threshold), true)).unwrap()
         }
@@ -80,7 +110,14 @@ pub async fn validate_and_respond(proposal_id: Index, payload: &ProposalPayload)
 /// This example uses a simple canister id whitelist.
 pub fn validate_payload(proposal: Index, payload: &ProposalPayload) -> bool {
     // Cannot depend on itself or future proposals
-    if payload.depends_on.iter().max().cloned() >= Some(proposal) {
+    if payload
+        .depends_on
+        .iter()
+        .chain(payload.depends_on_any.as_deref().unwrap_or_default())
+        .max()
+        .cloned()
+        >= Some(proposal)
+    {
         return false;
     }
     // Check if all canisters are in the whitelist
@@ -104,12 +141,27 @@ pub fn validate_payload(proposal: Index, payload: &ProposalPayload) -> bool {
 
 /// Modify this function to set the voting end time and the passing threshold for a proposal according to the payload.
 /// Proposal type may be determined by inspecting the payload.
-pub fn set_threshold(_payload: &ProposalPayload) -> (TimeNs, ProposalPassingThreshold) {
+/// Emergency proposals must meet the emergency floors configured in the governance canister.
+pub fn set_threshold(
+    _payload: &ProposalPayload,
+    class: &ProposalClass,
+) -> (TimeNs, ProposalPassingThreshold) {
+    if *class == ProposalClass::Emergency {
+        return (
+            86400 * 1_000_000_000 + ic_cdk::api::time(),
+            ProposalPassingThreshold {
+                quorum: Percentage::<PercentagePrecision>::from_percent(50),
+                passing_threshold: Percentage::<PercentagePrecision>::from_percent(67),
+                mode: Some(PassingMode::TwoThirds),
+            },
+        );
+    }
     (
         (86400 * 5) * 1_000_000_000 + ic_cdk::api::time(),
         ProposalPassingThreshold {
             quorum: Percentage::<PercentagePrecision>::from_percent(20),
             passing_threshold: Percentage::<PercentagePrecision>::from_percent(20),
+            mode: None,
         },
     )
 }
@@ -88,6 +88,8 @@ pub struct ProposalPayload {
     /// If the vector is empty, it means this proposal does not depend on any other proposal.
     /// ForceExecute cannot bypass this dependency.
     pub depends_on: Vec<Index>,
+    /// Ids of alternative proposals, at least one of which must be executed successfully first.
+    pub depends_on_any: Option<Vec<Index>>,
     /// Messages to be executed.
     pub messages: Vec<CanisterMessage>,
 }
@@ -98,6 +100,31 @@ pub struct ProposalPassingThreshold {
     pub quorum: Percentage<PercentagePrecision>,
     /// The percentage of yes/(yes+no) for a proposal to pass.
     pub passing_threshold: Percentage<PercentagePrecision>,
+    /// How the votes are counted. If `None`, the governance canister uses yes/(yes+no).
+    pub mode: Option<PassingMode>,
+}
+
+/// Class of a proposal, declared at submission time.
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ProposalClass {
+    #[default]
+    Standard,
+    /// Fast-track class. Governance holds it to higher thresholds, but allows a shorter voting period.
+    Emergency,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PassingMode {
+    /// More than half of yes+no.
+    SimpleMajority,
+    /// More than half of the total voting power.
+    AbsoluteMajority,
+    /// At least two thirds of yes+no.
+    TwoThirds,
+    /// At least one "yes" vote and no "no" votes.
+    Unanimity,
+    /// More than half of yes+no+abstain.
+    AbstainInDenominator,
 }
 
 #[derive(